@@ -0,0 +1,22 @@
+#![no_main]
+//! Arbitrary memo data never panics, and whatever decodes re-encodes to itself.
+use libfuzzer_sys::fuzz_target;
+use solnado::memo::{PoolMemo, SmallTreeMemo, SubBatchMemo};
+
+fuzz_target!(|data: &[u8]| {
+    let any = PoolMemo::decode(data);
+    match SubBatchMemo::decode(data) {
+        Ok(memo) => {
+            assert_eq!(any, Ok(PoolMemo::SubBatch(memo)));
+            assert_eq!(SubBatchMemo::decode(&memo.encode()), Ok(memo));
+        }
+        Err(_) => assert!(!matches!(any, Ok(PoolMemo::SubBatch(_)))),
+    }
+    match SmallTreeMemo::decode(data) {
+        Ok(memo) => {
+            assert_eq!(any, Ok(PoolMemo::SmallTree(memo)));
+            assert_eq!(SmallTreeMemo::decode(&memo.encode()), Ok(memo));
+        }
+        Err(_) => assert!(!matches!(any, Ok(PoolMemo::SmallTree(_)))),
+    }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+//! What clients encode, the program decodes unchanged.
+use libfuzzer_sys::fuzz_target;
+use solnado::memo::{PoolMemo, SmallTreeMemo, SubBatchMemo};
+
+fuzz_target!(|input: (u64, [[u8; 32]; 8], bool)| {
+    let (batch_number, leaves, small_tree) = input;
+    if small_tree {
+        let memo = SmallTreeMemo {
+            closed_batch: batch_number,
+            root: leaves[0],
+        };
+        assert_eq!(PoolMemo::decode(&memo.encode()), Ok(PoolMemo::SmallTree(memo)));
+    } else {
+        let memo = SubBatchMemo {
+            batch_number,
+            leaves,
+        };
+        assert_eq!(PoolMemo::decode(&memo.encode()), Ok(PoolMemo::SubBatch(memo)));
+    }
+});
@@ -0,0 +1,327 @@
+//! Association sets (proof of innocence). Curators the pool authority approves publish
+//! roots of curated subsets of the pool's deposits, and `withdraw_with_association`
+//! proves in the `withdraw_association` circuit that the spent note is a leaf of both the
+//! pool's tree and one of those subsets.
+//!
+//! `withdraw_association` public inputs, in order: amount, asset_id, nullifier, root,
+//! association_root, and the recipient as two 16 byte limbs, so only the key the proof
+//! was made for can take the withdrawal.
+//!
+//! The circuit only has development keys so far, anyone can forge proofs for them:
+//! without the `dev-keys` feature every withdrawal fails with `CircuitNotDeployed`.
+use crate::error::ErrorCode;
+use crate::events::{FeeCollected, Withdrawal};
+use crate::inputs::AssociationWithdrawInputs;
+use crate::nullifier::{spend_nullifier, NullifierAccounts};
+use crate::reserve::RentReserve;
+use crate::shard::POOL_FEE;
+use crate::utils::verify_withdraw_association_proof;
+use crate::MerkleMountainRange;
+use anchor_lang::prelude::*;
+
+pub const MAX_CURATORS: usize = 8;
+/// Roots a withdrawal can be made against, a curator republishing often doesn't void
+/// proofs in flight
+pub const ASSOCIATION_ROOT_HISTORY: usize = 32;
+
+#[account]
+pub struct AssociationRegistry {
+    pub curators: [Pubkey; MAX_CURATORS],
+    pub curator_count: u8,
+    /// Ring buffer of the latest published roots
+    pub roots: [[u8; 32]; ASSOCIATION_ROOT_HISTORY],
+    /// Slot the next root goes in
+    pub next_root: u8,
+    pub root_count: u8,
+}
+
+impl AssociationRegistry {
+    pub const SIZE: usize = 32 * MAX_CURATORS + 1 + 32 * ASSOCIATION_ROOT_HISTORY + 1 + 1;
+
+    pub fn new() -> Self {
+        AssociationRegistry {
+            curators: [Pubkey::default(); MAX_CURATORS],
+            curator_count: 0,
+            roots: [[0u8; 32]; ASSOCIATION_ROOT_HISTORY],
+            next_root: 0,
+            root_count: 0,
+        }
+    }
+
+    pub fn curators(&self) -> &[Pubkey] {
+        &self.curators[..self.curator_count as usize]
+    }
+
+    pub fn is_curator(&self, key: &Pubkey) -> bool {
+        self.curators().contains(key)
+    }
+
+    /// Approves or revokes `curator`, either is a no-op when already done
+    pub fn set_curator(&mut self, curator: Pubkey, approved: bool) -> Result<()> {
+        let count = self.curator_count as usize;
+        match (self.curators().iter().position(|c| *c == curator), approved) {
+            (None, true) => {
+                require!(count < MAX_CURATORS, ErrorCode::TooManyCurators);
+                self.curators[count] = curator;
+                self.curator_count += 1;
+            }
+            (Some(at), false) => {
+                self.curators[at] = self.curators[count - 1];
+                self.curators[count - 1] = Pubkey::default();
+                self.curator_count -= 1;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Published roots, oldest first
+    pub fn roots(&self) -> Vec<[u8; 32]> {
+        let count = self.root_count as usize;
+        let next = self.next_root as usize;
+        (0..count)
+            .map(|i| {
+                self.roots[(next + ASSOCIATION_ROOT_HISTORY - count + i) % ASSOCIATION_ROOT_HISTORY]
+            })
+            .collect()
+    }
+
+    pub fn knows(&self, root: &[u8; 32]) -> bool {
+        self.roots[..self.root_count as usize].contains(root)
+    }
+
+    /// Records `root`, pushing out the oldest once the history is full
+    pub fn publish(&mut self, root: [u8; 32]) {
+        self.roots[self.next_root as usize] = root;
+        self.next_root = ((self.next_root as usize + 1) % ASSOCIATION_ROOT_HISTORY) as u8;
+        if (self.root_count as usize) < ASSOCIATION_ROOT_HISTORY {
+            self.root_count += 1;
+        }
+    }
+}
+
+impl Default for AssociationRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn derive_association_registry_pda_key(pool_id: [u8; 16]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"association_registry", &pool_id], &crate::ID)
+}
+
+#[derive(Accounts)]
+pub struct InitializeAssociationRegistry<'info> {
+    #[account(
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAction
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AssociationRegistry::SIZE,
+        seeds = [ b"association_registry", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub association_registry: Account<'info, AssociationRegistry>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn initialize_association_registry(ctx: Context<InitializeAssociationRegistry>) -> Result<()> {
+    ctx.accounts
+        .association_registry
+        .set_inner(AssociationRegistry::new());
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetAssociationCurator<'info> {
+    #[account(
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAction
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    #[account(
+        mut,
+        seeds = [ b"association_registry", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub association_registry: Account<'info, AssociationRegistry>,
+
+    pub authority: Signer<'info>,
+}
+
+pub fn set_association_curator(
+    ctx: Context<SetAssociationCurator>,
+    curator: Pubkey,
+    approved: bool,
+) -> Result<()> {
+    ctx.accounts
+        .association_registry
+        .set_curator(curator, approved)?;
+    msg!("Curator {} approved: {}", curator, approved);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct PublishAssociationRoot<'info> {
+    #[account(
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    #[account(
+        mut,
+        seeds = [ b"association_registry", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub association_registry: Account<'info, AssociationRegistry>,
+
+    pub curator: Signer<'info>,
+}
+
+pub fn publish_association_root(
+    ctx: Context<PublishAssociationRoot>,
+    root: [u8; 32],
+) -> Result<()> {
+    let registry = &mut ctx.accounts.association_registry;
+    require!(
+        registry.is_curator(&ctx.accounts.curator.key()),
+        ErrorCode::UnauthorizedCurator
+    );
+    registry.publish(root);
+    msg!("Association root published: {:?}", root);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct WithdrawWithAssociation<'info> {
+    #[account(
+        mut,
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    #[account(
+        seeds = [ b"association_registry", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub association_registry: Account<'info, AssociationRegistry>,
+
+    ///CHECK: shard or nullifier PDA, checked by the pool's nullifier store
+    #[account(mut)]
+    pub nullifier_shard: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    ///CHECK: SYSVAR_INSTRUCTIONS must be passed to read the Memo
+    pub instruction_account: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    ///CHECK: This can be used by different functions
+    #[account(mut)]
+    pub dummy0_account: AccountInfo<'info>,
+    ///CHECK: This can be used by different functions
+    #[account(mut)]
+    pub dummy1_account: AccountInfo<'info>,
+    ///CHECK: live shard list, checked and updated when a shard splits
+    #[account(mut)]
+    pub shard_directory: AccountInfo<'info>,
+    /// Receives the pool fee, pays for splits
+    #[account(
+        mut,
+        seeds = [ b"rent_reserve", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub rent_reserve: Account<'info, RentReserve>,
+}
+
+/// `withdraw_variable_shard` mode 0, with the note also proven a member of a published
+/// association set
+pub fn withdraw_with_association(
+    ctx: Context<WithdrawWithAssociation>,
+    proof: [u8; 256],
+    inputs: AssociationWithdrawInputs,
+) -> Result<()> {
+    let AssociationWithdrawInputs {
+        nullifier,
+        amount,
+        root,
+        association_root,
+        recipient,
+        ..
+    } = inputs;
+    require!(
+        recipient == ctx.accounts.user.key(),
+        ErrorCode::InvalidWithdrawerKey
+    );
+
+    require!(
+        ctx.accounts.association_registry.knows(&association_root),
+        ErrorCode::UnknownAssociationRoot
+    );
+    let pool = &ctx.accounts.pool;
+    require!(pool.deep_root == root, ErrorCode::InvalidPublicInputRoot);
+    verify_withdraw_association_proof(&proof, &inputs)?;
+
+    let user = ctx.accounts.user.to_account_info();
+    let reserve = ctx.accounts.rent_reserve.to_account_info();
+    let reserve_before = reserve.lamports();
+    spend_nullifier(
+        &NullifierAccounts {
+            pool: &ctx.accounts.pool,
+            record: &ctx.accounts.nullifier_shard,
+            child0: &ctx.accounts.dummy0_account,
+            child1: &ctx.accounts.dummy1_account,
+            directory: &ctx.accounts.shard_directory,
+            reserve: &reserve,
+            instructions: &ctx.accounts.instruction_account,
+            payer: &user,
+            system_program: &ctx.accounts.system_program,
+        },
+        &nullifier,
+    )?;
+    let rent = reserve_before.saturating_sub(reserve.lamports());
+
+    let net_amount = amount
+        .checked_sub(POOL_FEE)
+        .ok_or(ErrorCode::InvalidArgument)?;
+    **ctx
+        .accounts
+        .pool
+        .to_account_info()
+        .try_borrow_mut_lamports()? -= amount;
+    **user.try_borrow_mut_lamports()? += net_amount;
+    **reserve.try_borrow_mut_lamports()? += POOL_FEE;
+    let ledger = &mut ctx.accounts.pool.ledger;
+    ledger.withdraw(net_amount, POOL_FEE)?;
+    ledger.spend_rent(rent)?;
+
+    let pool = &ctx.accounts.pool;
+    emit!(Withdrawal {
+        pool: pool.identifier,
+        recipient: ctx.accounts.user.key(),
+        amount: net_amount,
+        fees: POOL_FEE,
+    });
+    emit!(FeeCollected {
+        pool: pool.identifier,
+        payer: pool.key(),
+        recipient: ctx.accounts.rent_reserve.key(),
+        amount: POOL_FEE,
+    });
+    Ok(())
+}
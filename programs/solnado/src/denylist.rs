@@ -0,0 +1,124 @@
+//! Depositor screening. The pool authority keeps a sorted list of flagged addresses in the
+//! pool's denylist (PDA `["denylist", pool_id]`), every deposit path runs
+//! `screen_depositor` before taking lamports in. A pool without a denylist admits everyone.
+use crate::error::ErrorCode;
+use crate::events::DepositDenied;
+use crate::MerkleMountainRange;
+use anchor_lang::prelude::*;
+
+pub const MAX_DENIED: usize = 256;
+
+#[account]
+#[derive(Default)]
+pub struct Denylist {
+    /// Sorted, no duplicates
+    pub addresses: Vec<Pubkey>,
+}
+
+impl Denylist {
+    pub const SIZE: usize = 4 + 32 * MAX_DENIED;
+
+    pub fn contains(&self, key: &Pubkey) -> bool {
+        self.addresses.binary_search(key).is_ok()
+    }
+
+    /// Drops `remove` then adds `add`, addresses already in or out are skipped
+    pub fn update(&mut self, add: &[Pubkey], remove: &[Pubkey]) -> Result<()> {
+        for key in remove {
+            if let Ok(at) = self.addresses.binary_search(key) {
+                self.addresses.remove(at);
+            }
+        }
+        for key in add {
+            if let Err(at) = self.addresses.binary_search(key) {
+                require!(self.addresses.len() < MAX_DENIED, ErrorCode::DenylistFull);
+                self.addresses.insert(at, *key);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn derive_denylist_pda_key(pool_id: [u8; 16]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"denylist", &pool_id], &crate::ID)
+}
+
+/// Fails with `DepositorDenied` if `denylist`, the pool's denylist PDA, lists `depositor`.
+/// The PDA is checked by the caller's seeds, it has no data until initialized.
+pub fn screen_depositor(
+    denylist: &AccountInfo,
+    pool_id: [u8; 16],
+    depositor: &Pubkey,
+) -> Result<()> {
+    if denylist.owner != &crate::ID || denylist.data_is_empty() {
+        return Ok(());
+    }
+    let list = Denylist::try_deserialize(&mut &denylist.try_borrow_data()?[..])?;
+    if list.contains(depositor) {
+        emit!(DepositDenied {
+            pool: pool_id,
+            depositor: *depositor,
+        });
+        return err!(ErrorCode::DepositorDenied);
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeDenylist<'info> {
+    #[account(
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAction
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Denylist::SIZE,
+        seeds = [ b"denylist", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub denylist: Account<'info, Denylist>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn initialize_denylist(ctx: Context<InitializeDenylist>) -> Result<()> {
+    ctx.accounts.denylist.set_inner(Denylist::default());
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateDenylist<'info> {
+    #[account(
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAction
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    #[account(
+        mut,
+        seeds = [ b"denylist", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub denylist: Account<'info, Denylist>,
+
+    pub authority: Signer<'info>,
+}
+
+pub fn update_denylist(
+    ctx: Context<UpdateDenylist>,
+    add: Vec<Pubkey>,
+    remove: Vec<Pubkey>,
+) -> Result<()> {
+    let denylist = &mut ctx.accounts.denylist;
+    denylist.update(&add, &remove)?;
+    msg!("Denylist updated, {} listed", denylist.addresses.len());
+    Ok(())
+}
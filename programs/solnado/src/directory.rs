@@ -0,0 +1,150 @@
+//! Directory of the live shards of a sharded pool. Splits close a shard and create its
+//! two children, the directory tracks that so a client can pick the shard owning its
+//! nullifier (and the children a split would create) without probing accounts.
+//!
+//! Account data: discriminator(8) || `ShardDirectory` || `ShardPrefix` * count, the
+//! prefixes sorted by the key range they cover.
+use crate::error::ErrorCode;
+use crate::reserve::pay_rent;
+use crate::shard::{derive_shard_pda_key, leading_bits, PREFIX_BYTES};
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+
+/// Directory header, the prefixes follow it
+#[account(zero_copy)]
+pub struct ShardDirectory {
+    pub count: u32,
+}
+
+/// One live shard, its prefix packed as in `BitShard`
+#[zero_copy]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShardPrefix {
+    pub prefix: [u8; PREFIX_BYTES],
+    pub len: u8,
+}
+
+pub const DIRECTORY_HEADER: usize = 8 + std::mem::size_of::<ShardDirectory>();
+pub const SHARD_PREFIX_SIZE: usize = std::mem::size_of::<ShardPrefix>();
+/// Space of a fresh directory, the two depth one shards
+pub const DIRECTORY_INIT_SPACE: usize = directory_space(2);
+
+pub const fn directory_space(count: usize) -> usize {
+    DIRECTORY_HEADER + count * SHARD_PREFIX_SIZE
+}
+
+impl ShardPrefix {
+    pub fn new(len: u8, prefix: [u8; PREFIX_BYTES]) -> Self {
+        ShardPrefix { prefix, len }
+    }
+
+    /// Whether the shard's range holds `nullifier`
+    pub fn contains(&self, nullifier: &[u8; 32]) -> bool {
+        leading_bits(nullifier, self.len) == self.prefix
+    }
+
+    /// Prefixes of the two shards a split creates, `len` must be under `MAX_PREFIX_BITS`
+    pub fn children(&self) -> [ShardPrefix; 2] {
+        let i = self.len as usize;
+        let mut right = self.prefix;
+        right[i / 8] |= 1 << (7 - i % 8);
+        [
+            ShardPrefix::new(self.len + 1, self.prefix),
+            ShardPrefix::new(self.len + 1, right),
+        ]
+    }
+
+    pub fn pda(&self, pool_id: [u8; 16]) -> Pubkey {
+        derive_shard_pda_key(pool_id, &self.prefix, self.len).0
+    }
+}
+
+pub fn derive_directory_pda_key(pool_id: [u8; 16]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"shard_directory", &pool_id], &crate::ID)
+}
+
+/// Live shards listed in directory account data
+pub fn directory_entries(data: &[u8]) -> Result<&[ShardPrefix]> {
+    require!(
+        data.len() >= DIRECTORY_HEADER && data[..8] == *ShardDirectory::DISCRIMINATOR,
+        ErrorCode::InvalidShardDirectory
+    );
+    let header: &ShardDirectory = bytemuck::from_bytes(&data[8..DIRECTORY_HEADER]);
+    let end = directory_space(header.count as usize);
+    require!(data.len() >= end, ErrorCode::InvalidShardDirectory);
+    Ok(bytemuck::cast_slice(&data[DIRECTORY_HEADER..end]))
+}
+
+/// The live shard whose range holds `nullifier`
+pub fn find_shard<'a>(entries: &'a [ShardPrefix], nullifier: &[u8; 32]) -> Option<&'a ShardPrefix> {
+    // ranges are disjoint, the owner is the last one starting at or before the key
+    let key = &nullifier[..PREFIX_BYTES];
+    let after = entries.partition_point(|e| e.prefix.as_slice() <= key);
+    let entry = entries.get(after.checked_sub(1)?)?;
+    entry.contains(nullifier).then_some(entry)
+}
+
+/// Swaps `parent` for its children in directory data sized for one more entry
+pub fn replace_with_children(data: &mut [u8], parent: &ShardPrefix) -> Result<()> {
+    let entries = directory_entries(data)?;
+    let count = entries.len();
+    let index = match entries.binary_search_by(|e| e.prefix.cmp(&parent.prefix)) {
+        Ok(i) if entries[i] == *parent => i,
+        _ => return err!(ErrorCode::InvalidShardDirectory),
+    };
+    require!(
+        data.len() >= directory_space(count + 1),
+        ErrorCode::InvalidShardDirectory
+    );
+
+    data.copy_within(
+        directory_space(index + 1)..directory_space(count),
+        directory_space(index + 2),
+    );
+    let children = parent.children();
+    data[directory_space(index)..directory_space(index + 2)]
+        .copy_from_slice(bytemuck::cast_slice(&children));
+    let header: &mut ShardDirectory = bytemuck::from_bytes_mut(&mut data[8..DIRECTORY_HEADER]);
+    header.count += 1;
+    Ok(())
+}
+
+fn check_directory(directory_ai: &AccountInfo, pool_id: [u8; 16]) -> Result<()> {
+    require!(
+        directory_ai.key() == derive_directory_pda_key(pool_id).0
+            && directory_ai.owner == &crate::ID,
+        ErrorCode::InvalidShardDirectory
+    );
+    Ok(())
+}
+
+/// The live shard the pool's directory lists for `nullifier`
+pub fn listed_shard(
+    directory_ai: &AccountInfo,
+    pool_id: [u8; 16],
+    nullifier: &[u8; 32],
+) -> Result<Option<ShardPrefix>> {
+    check_directory(directory_ai, pool_id)?;
+    let data = directory_ai.try_borrow_data()?;
+    Ok(find_shard(directory_entries(&data)?, nullifier).copied())
+}
+
+/// Records a split of `parent` in the pool's directory, growing it by one entry at the
+/// rent reserve's expense
+pub fn record_split<'info>(
+    directory_ai: &AccountInfo<'info>,
+    reserve_ai: &AccountInfo<'info>,
+    pool_id: [u8; 16],
+    parent: &ShardPrefix,
+) -> Result<()> {
+    check_directory(directory_ai, pool_id)?;
+
+    let new_len = directory_ai.data_len() + SHARD_PREFIX_SIZE;
+    let rent = Rent::get()?.minimum_balance(new_len);
+    let delta = rent.saturating_sub(directory_ai.lamports());
+    pay_rent(reserve_ai, pool_id, directory_ai, delta)?;
+    directory_ai.resize(new_len)?;
+
+    let mut data = directory_ai.try_borrow_mut_data()?;
+    replace_with_children(&mut data, parent)
+}
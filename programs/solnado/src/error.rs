@@ -112,4 +112,109 @@ pub enum ErrorCode {
 
     #[msg("Insufficient funds in pool for withdrawal")]
     InsufficientFunds,
-}
\ No newline at end of file
+
+    #[msg("Nullifier shard is full")]
+    ShardFull,
+
+    #[msg("Shard split threshold out of range")]
+    InvalidSplitThreshold,
+
+    #[msg("Nullifier shards already initialized")]
+    ShardsAlreadyInitialized,
+
+    #[msg("Invalid shard directory")]
+    InvalidShardDirectory,
+
+    #[msg("Missing nullifier insertion proof")]
+    MissingInsertionProof,
+
+    #[msg("Insertion proof made against an older nullifier tree")]
+    StaleNullifierTree,
+
+    #[msg("Nullifier tree is full")]
+    NullifierTreeFull,
+
+    #[msg("Invalid rent reserve account")]
+    InvalidRentReserve,
+
+    #[msg("Rent reserve can't cover the shard rent, fund it first")]
+    RentReserveShort,
+
+    #[msg("Pool ledger counter overflow")]
+    LedgerOverflow,
+
+    #[msg("Pool lamports don't cover the outstanding notes")]
+    PoolInsolvent,
+
+    #[msg("Deposit above the pool's per deposit cap")]
+    DepositTooLarge,
+
+    #[msg("Pool's deposit cap for this epoch reached")]
+    EpochDepositCapReached,
+
+    #[msg("Pool's total value cap reached")]
+    TvlCapReached,
+
+    #[msg("Per epoch deposit cap needs an epoch length")]
+    InvalidDepositLimits,
+
+    #[msg("Association registry already has the maximum number of curators")]
+    TooManyCurators,
+
+    #[msg("Signer isn't an approved curator of this pool")]
+    UnauthorizedCurator,
+
+    #[msg("Association root not published in the pool's registry")]
+    UnknownAssociationRoot,
+
+    #[msg("More encrypted notes than output leaves")]
+    TooManyNotes,
+
+    #[msg("Encrypted note too large")]
+    NoteTooLarge,
+
+    #[msg("Denylist already holds the maximum number of addresses")]
+    DenylistFull,
+
+    #[msg("Depositor is on the pool's denylist")]
+    DepositorDenied,
+
+    #[msg("Pool already has the current layout")]
+    PoolUpToDate,
+
+    #[msg("Pool name too long")]
+    PoolNameTooLong,
+
+    #[msg("Identifier isn't the creator's namespaced identifier for this name")]
+    NamespaceMismatch,
+
+    #[msg("Identifiers starting with the namespace tag belong to namespaced pools")]
+    ReservedPoolIdentifier,
+
+    #[msg("Pool is sunset or closed, deposits are off")]
+    DepositsClosed,
+
+    #[msg("Pool was already sunset")]
+    PoolNotActive,
+
+    #[msg("Pool must be sunset before it closes")]
+    PoolNotSunset,
+
+    #[msg("Sunset grace period hasn't elapsed")]
+    SunsetGracePending,
+
+    #[msg("Account isn't an indexer or shard of this pool")]
+    InvalidReclaimAccount,
+
+    #[msg("Shard still holds nullifiers")]
+    ShardNotEmpty,
+
+    #[msg("A pool without an authority needs one to migrate")]
+    MissingPoolAuthority,
+
+    #[msg("Withdrawer key isn't below the BN254 scalar field modulus")]
+    WithdrawerNotAFieldElement,
+
+    #[msg("Circuit has no verifying key from a ceremony, only the dev-keys build takes it")]
+    CircuitNotDeployed,
+}
@@ -0,0 +1,124 @@
+//! Typed events of the pool's state transitions, what monitoring and indexers parse
+//! instead of the `msg!` text. `emit!` logs each as a `Program data:` line holding the
+//! event's discriminator and Borsh encoding. `pool` is always the pool identifier.
+use anchor_lang::prelude::*;
+
+/// A leaf joined the tree at `index`, counting from the pool's first leaf
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct LeafInserted {
+    pub pool: [u8; 16],
+    pub index: u64,
+    pub leaf: [u8; 32],
+}
+
+/// Batch `batch_number` filled up and was folded into the peaks
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct BatchRolledOver {
+    pub pool: [u8; 16],
+    pub batch_number: u64,
+    pub batch_root: [u8; 32],
+    /// Root of the whole tree once the batch is in
+    pub tree_root: [u8; 32],
+}
+
+/// A small tree completed with batch `batch_number`, the next deposit posts its root
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct SmallTreeClosed {
+    pub pool: [u8; 16],
+    pub batch_number: u64,
+    pub small_tree_root: [u8; 32],
+}
+
+/// `nullifier` was recorded in `shard`, the shard, nullifier PDA or nullifier tree of
+/// the pool's store
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct NullifierSpent {
+    pub pool: [u8; 16],
+    pub shard: Pubkey,
+    pub nullifier: [u8; 32],
+}
+
+/// A full shard was replaced by its two children
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShardSplit {
+    pub pool: [u8; 16],
+    pub shard: Pubkey,
+    pub child0: Pubkey,
+    pub child1: Pubkey,
+    /// Nullifiers each child took over, the one being spent not included
+    pub count0: u32,
+    pub count1: u32,
+}
+
+/// A note's lamports left the pool, `amount` to `recipient` and `fees` to the fee takers
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Withdrawal {
+    pub pool: [u8; 16],
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub fees: u64,
+}
+
+/// `amount` of fees moved from `payer` to `recipient`: the rent reserve for the pool fee,
+/// the relayer for the on-behalf fee
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct FeeCollected {
+    pub pool: [u8; 16],
+    pub payer: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
+/// A depositor on the pool's denylist tried to deposit, the transaction fails but its
+/// logs stay
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct DepositDenied {
+    pub pool: [u8; 16],
+    pub depositor: Pubkey,
+}
+
+/// The authority sunset the pool, it can be closed from `closes_after` on
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct PoolSunset {
+    pub pool: [u8; 16],
+    pub closes_after: i64,
+}
+
+/// The sunset pool closed, no more spends
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct PoolClosed {
+    pub pool: [u8; 16],
+}
+
+/// An empty indexer or shard of a closed pool was closed, its `amount` of rent going to
+/// the authority
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct RentReclaimed {
+    pub pool: [u8; 16],
+    pub account: Pubkey,
+    pub amount: u64,
+}
+
+/// `audit_pool`'s breakdown: the pool's ledger and the `lamports` it holds, rent included.
+/// Emitted before the solvency check, so a failing audit logs it too
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct PoolAudited {
+    pub pool: [u8; 16],
+    pub deposited: u64,
+    pub withdrawn: u64,
+    pub fees: u64,
+    pub rent_spent: u64,
+    pub lamports: u64,
+}
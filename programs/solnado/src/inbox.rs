@@ -0,0 +1,35 @@
+//! Encrypted note inbox. Every leaf-creating instruction takes an optional note per output
+//! leaf, encrypted by the sender to the recipient's X25519 key, and logs it next to the
+//! leaf. The program never reads the notes, recipients trial-decrypt the stream of
+//! `Program data: "note" || leaf || ephemeral_key || ciphertext` logs to find theirs.
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::log::sol_log_data;
+
+/// Tag of the note logs
+pub const NOTE_LOG_TAG: &[u8] = b"note";
+/// Room for a leaf preimage (amount, nullifier, asset id) plus a short memo and the tag
+pub const MAX_NOTE_CIPHERTEXT: usize = 160;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedNote {
+    /// X25519 key the sender generated for this note alone
+    pub ephemeral_key: [u8; 32],
+    /// ChaCha20-Poly1305 ciphertext, tag included
+    pub ciphertext: Vec<u8>,
+}
+
+/// Logs `notes[i]` next to `leaves[i]`. Notes follow the output leaves in order, there
+/// can't be more than leaves and any of them may be missing.
+pub fn publish_notes(leaves: &[[u8; 32]], notes: &[Option<EncryptedNote>]) -> Result<()> {
+    require!(notes.len() <= leaves.len(), ErrorCode::TooManyNotes);
+    for (leaf, note) in leaves.iter().zip(notes) {
+        let Some(note) = note else { continue };
+        require!(
+            note.ciphertext.len() <= MAX_NOTE_CIPHERTEXT,
+            ErrorCode::NoteTooLarge
+        );
+        sol_log_data(&[NOTE_LOG_TAG, leaf, &note.ephemeral_key, &note.ciphertext]);
+    }
+    Ok(())
+}
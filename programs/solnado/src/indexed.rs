@@ -0,0 +1,219 @@
+//! Indexed Merkle tree nullifier set. Leaves `(value, next_index, next_value)` form a
+//! linked list sorted by value, leaf 0 being the `(0, 0, 0)` head. Spending `n` proves in
+//! the `indexed_insert` circuit that the low leaf (the one with `value < n < next_value`,
+//! or `next_value == 0` for the tail) is in the tree, then points it at `n` and appends
+//! `(n, low.next_index, low.next_value)`.
+//!
+//! On-chain only the root and the append frontier are kept, rent doesn't grow with the
+//! spends. The circuit hands over the subtree holding the updated low leaf, which is the
+//! one frontier node it changes, and the program appends the new leaf itself.
+//!
+//! `indexed_insert` public inputs, in order: nullifier, old_root, next_index, low_index,
+//! low_subtree, new_leaf. `low_subtree` is the root, after the update, of the subtree at
+//! level `frontier_level(low_index, next_index)` holding the low leaf.
+//!
+//! Like `withdraw_association`, the circuit only has development keys: without the
+//! `dev-keys` feature no indexed pool or tree can be created.
+use crate::error::ErrorCode;
+use crate::inputs::{amount_to_field, IndexedInsertInputs};
+use crate::nullifier::{NullifierAccounts, NullifierStore, NullifierStoreKind};
+use crate::utils::{get_default_root_depth, verify_nullifier_insertion};
+use crate::{MerkleMountainRange, DEFAULT_LEAF, TARGET_DEPTH_LARGE};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions;
+use anchor_lang::Discriminator;
+use solana_poseidon::{hashv, Endianness, Parameters};
+
+/// Room for as many nullifiers as the pool has leaves
+pub const INDEXED_TREE_DEPTH: usize = TARGET_DEPTH_LARGE;
+
+#[account]
+pub struct NullifierTree {
+    pub root: [u8; 32],
+    /// Where the next leaf goes
+    pub next_index: u64,
+    /// `frontier[l]` is the complete left sibling at level `l` of the next leaf's path,
+    /// meaningful where bit `l` of `next_index` is set
+    pub frontier: [[u8; 32]; INDEXED_TREE_DEPTH],
+}
+
+fn hash(inputs: &[&[u8]]) -> [u8; 32] {
+    hashv(Parameters::Bn254X5, Endianness::BigEndian, inputs)
+        .unwrap()
+        .to_bytes()
+}
+
+/// Parent of two tree nodes
+pub fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hash(&[left, right])
+}
+
+/// Empty subtree of height `level`
+pub fn zero_subtree(level: usize) -> [u8; 32] {
+    match level {
+        0 => DEFAULT_LEAF,
+        l => get_default_root_depth(l),
+    }
+}
+
+pub fn indexed_leaf(value: &[u8; 32], next_index: u64, next_value: &[u8; 32]) -> [u8; 32] {
+    hash(&[value, &amount_to_field(next_index), next_value])
+}
+
+/// Level of the frontier node holding `low_index`, the highest bit where it and
+/// `next_index` differ
+pub fn frontier_level(low_index: u64, next_index: u64) -> usize {
+    63 - (low_index ^ next_index).leading_zeros() as usize
+}
+
+impl NullifierTree {
+    pub const SIZE: usize = 32 + 8 + 32 * INDEXED_TREE_DEPTH;
+
+    /// Tree holding only the list head
+    pub fn new() -> Self {
+        let mut tree = NullifierTree {
+            root: DEFAULT_LEAF,
+            next_index: 0,
+            frontier: [DEFAULT_LEAF; INDEXED_TREE_DEPTH],
+        };
+        tree.append(indexed_leaf(&DEFAULT_LEAF, 0, &DEFAULT_LEAF));
+        tree
+    }
+
+    fn append(&mut self, leaf: [u8; 32]) {
+        let mut node = leaf;
+        for level in 0..INDEXED_TREE_DEPTH {
+            if self.next_index >> level & 1 == 1 {
+                node = node_hash(&self.frontier[level], &node);
+            } else {
+                self.frontier[level] = node;
+                node = node_hash(&node, &zero_subtree(level));
+            }
+        }
+        self.root = node;
+        self.next_index += 1;
+    }
+
+    /// Applies a proven insertion: swaps in the updated low leaf subtree, then appends
+    /// the new leaf. The proof must have been checked against this tree's root.
+    pub fn insert(
+        &mut self,
+        low_index: u64,
+        low_subtree: [u8; 32],
+        new_leaf: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            self.next_index < 1 << INDEXED_TREE_DEPTH,
+            ErrorCode::NullifierTreeFull
+        );
+        require!(low_index < self.next_index, ErrorCode::InvalidInputs);
+        self.frontier[frontier_level(low_index, self.next_index)] = low_subtree;
+        self.append(new_leaf);
+        Ok(())
+    }
+}
+
+impl Default for NullifierTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn derive_nullifier_tree_pda_key(pool_id: [u8; 16]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"nullifier_tree", &pool_id], &crate::ID)
+}
+
+/// Insertion proof for `nullifier` among the transaction's `nullifier_insertion_proof`
+/// instructions
+pub fn find_insertion_proof(
+    sysvar_account: &AccountInfo,
+    nullifier: &[u8; 32],
+) -> Result<([u8; 256], IndexedInsertInputs)> {
+    use crate::instruction::NullifierInsertionProof;
+    (0..)
+        .map_while(|i| instructions::load_instruction_at_checked(i, sysvar_account).ok())
+        .filter(|ix| {
+            ix.program_id == crate::ID
+                && ix.data.starts_with(NullifierInsertionProof::DISCRIMINATOR)
+        })
+        .filter_map(|ix| {
+            NullifierInsertionProof::try_from_slice(
+                &ix.data[NullifierInsertionProof::DISCRIMINATOR.len()..],
+            )
+            .ok()
+        })
+        .find(|carrier| carrier.inputs.nullifier == *nullifier)
+        .map(|carrier| (carrier.proof, carrier.inputs))
+        .ok_or_else(|| error!(ErrorCode::MissingInsertionProof))
+}
+
+pub struct IndexedStore<'s, 'a, 'info>(pub &'s NullifierAccounts<'a, 'info>);
+
+impl NullifierStore for IndexedStore<'_, '_, '_> {
+    fn spend(&self, nullifier: &[u8; 32]) -> Result<Pubkey> {
+        let a = self.0;
+        require!(
+            a.record.key() == derive_nullifier_tree_pda_key(a.pool.identifier).0
+                && a.record.owner == &crate::ID,
+            ErrorCode::InvalidNullifierAccount
+        );
+        let mut tree = NullifierTree::try_deserialize(&mut &a.record.try_borrow_data()?[..])?;
+
+        let (proof, inputs) = find_insertion_proof(a.instructions, nullifier)?;
+        // spends land one after the other, a proof made against an older root is void
+        require!(
+            inputs.old_root == tree.root && inputs.next_index == tree.next_index,
+            ErrorCode::StaleNullifierTree
+        );
+        verify_nullifier_insertion(&proof, &inputs)?;
+        tree.insert(inputs.low_index, inputs.low_subtree, inputs.new_leaf)?;
+
+        tree.try_serialize(&mut &mut a.record.try_borrow_mut_data()?[..])?;
+        Ok(a.record.key())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeNullifierTree<'info> {
+    #[account(
+        seeds = [ b"variable_pool", &pool.identifier ],
+        bump
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + NullifierTree::SIZE,
+        seeds = [ b"nullifier_tree", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub nullifier_tree: Account<'info, NullifierTree>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn initialize_nullifier_tree(ctx: Context<InitializeNullifierTree>) -> Result<()> {
+    require!(cfg!(feature = "dev-keys"), ErrorCode::CircuitNotDeployed);
+    require!(
+        ctx.accounts.pool.nullifier_store == NullifierStoreKind::Indexed,
+        ErrorCode::InvalidMode
+    );
+    ctx.accounts.nullifier_tree.set_inner(NullifierTree::new());
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CarryInsertionProof {}
+
+/// No effect by itself, the spend in the same transaction reads and checks the proof
+pub fn carry_insertion_proof(
+    _ctx: Context<CarryInsertionProof>,
+    _proof: [u8; 256],
+    _inputs: IndexedInsertInputs,
+) -> Result<()> {
+    Ok(())
+}
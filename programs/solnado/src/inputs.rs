@@ -0,0 +1,201 @@
+use anchor_lang::prelude::*;
+
+//Typed public inputs for every proof-carrying instruction.
+//Field order follows the byte layout clients used to pack by hand, the verifier
+//ordering (which is what the circuits expect) is produced by `to_field_elements`.
+
+/// Conversion into the `[[u8; 32]; N]` ordering expected by the Groth16 verifier
+pub trait PublicInputs<const N: usize> {
+    fn to_field_elements(&self) -> [[u8; 32]; N];
+}
+
+/// Amounts are u64 on the wire but 32-byte big-endian field elements in the circuits
+pub fn amount_to_field(amount: u64) -> [u8; 32] {
+    let mut field = [0u8; 32];
+    field[32 - 8..].copy_from_slice(&amount.to_be_bytes());
+    field
+}
+
+/// Order of the BN254 scalar field, big-endian
+pub const FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58,
+    0x5d, 0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00,
+    0x00, 0x01,
+];
+
+/// A pubkey as two field elements, its high and low 16 bytes. A key can be above the
+/// modulus, the limbs hold any of them whole
+pub fn pubkey_to_limbs(key: &Pubkey) -> [[u8; 32]; 2] {
+    let bytes = key.to_bytes();
+    let mut limbs = [[0u8; 32]; 2];
+    limbs[0][16..].copy_from_slice(&bytes[..16]);
+    limbs[1][16..].copy_from_slice(&bytes[16..]);
+    limbs
+}
+
+/// `deposit_variable`: one or two new leaves, `leaf2` is left as DEFAULT_LEAF for the single leaf circuit
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepositInputs {
+    pub amount: u64,
+    pub leaf1: [u8; 32],
+    pub leaf2: [u8; 32],
+}
+
+//Single leaf deposit circuit: [amount, leaf1]
+impl PublicInputs<2> for DepositInputs {
+    fn to_field_elements(&self) -> [[u8; 32]; 2] {
+        [amount_to_field(self.amount), self.leaf1]
+    }
+}
+
+//Double leaf deposit circuit: [amount, leaf1, leaf2]
+impl PublicInputs<3> for DepositInputs {
+    fn to_field_elements(&self) -> [[u8; 32]; 3] {
+        [amount_to_field(self.amount), self.leaf1, self.leaf2]
+    }
+}
+
+/// `combine_deposit_shard_single`: one nullifier spent, two leaves created (1 -> 2 circuit)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CombineSingleInputs {
+    pub nullifier: [u8; 32],
+    pub leaf1: [u8; 32],
+    pub leaf2: [u8; 32],
+    pub root: [u8; 32],
+}
+
+impl PublicInputs<4> for CombineSingleInputs {
+    fn to_field_elements(&self) -> [[u8; 32]; 4] {
+        [self.nullifier, self.leaf1, self.leaf2, self.root]
+    }
+}
+
+/// `combine_deposit_shard_double`: two nullifiers spent, one leaf created (2 -> 1 circuit)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CombineDoubleInputs {
+    pub nullifier1: [u8; 32],
+    pub nullifier2: [u8; 32],
+    pub new_leaf: [u8; 32],
+    pub root: [u8; 32],
+}
+
+impl PublicInputs<4> for CombineDoubleInputs {
+    fn to_field_elements(&self) -> [[u8; 32]; 4] {
+        [self.nullifier1, self.nullifier2, self.new_leaf, self.root]
+    }
+}
+
+/// `withdraw_variable_shard`: mode 0 ignores `new_leaf`, mode 1 re-deposits the change into `new_leaf`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WithdrawInputs {
+    pub nullifier: [u8; 32],
+    /// Must be all zeroes for SOL
+    pub asset_id: [u8; 32],
+    pub amount: u64,
+    pub root: [u8; 32],
+    pub new_leaf: [u8; 32],
+}
+
+//Withdraw circuit: [amount, asset_id, nullifier, root]
+impl PublicInputs<4> for WithdrawInputs {
+    fn to_field_elements(&self) -> [[u8; 32]; 4] {
+        [
+            amount_to_field(self.amount),
+            self.asset_id,
+            self.nullifier,
+            self.root,
+        ]
+    }
+}
+
+//Withdraw and add circuit: [amount, asset_id, nullifier, new_leaf, root]
+impl PublicInputs<5> for WithdrawInputs {
+    fn to_field_elements(&self) -> [[u8; 32]; 5] {
+        [
+            amount_to_field(self.amount),
+            self.asset_id,
+            self.nullifier,
+            self.new_leaf,
+            self.root,
+        ]
+    }
+}
+
+/// `nullifier_insertion_proof`: a nullifier entering a pool's indexed nullifier tree
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexedInsertInputs {
+    pub nullifier: [u8; 32],
+    pub old_root: [u8; 32],
+    pub next_index: u64,
+    pub low_index: u64,
+    pub low_subtree: [u8; 32],
+    pub new_leaf: [u8; 32],
+}
+
+//Indexed insert circuit: [nullifier, old_root, next_index, low_index, low_subtree, new_leaf]
+impl PublicInputs<6> for IndexedInsertInputs {
+    fn to_field_elements(&self) -> [[u8; 32]; 6] {
+        [
+            self.nullifier,
+            self.old_root,
+            amount_to_field(self.next_index),
+            amount_to_field(self.low_index),
+            self.low_subtree,
+            self.new_leaf,
+        ]
+    }
+}
+
+/// `withdraw_on_behalf_shard`: the proof binds the funds to `withdrawer`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OnBehalfInputs {
+    pub nullifier: [u8; 32],
+    pub amount: u64,
+    pub root: [u8; 32],
+    pub withdrawer: Pubkey,
+}
+
+//On behalf circuit: [amount, nullifier, withdrawer, root]
+//The withdrawer is a single field element, the instruction refuses keys at or above
+//the modulus so the proof binds the whole key
+impl PublicInputs<4> for OnBehalfInputs {
+    fn to_field_elements(&self) -> [[u8; 32]; 4] {
+        [
+            amount_to_field(self.amount),
+            self.nullifier,
+            self.withdrawer.to_bytes(),
+            self.root,
+        ]
+    }
+}
+
+/// `withdraw_with_association`: a withdrawal whose note is also a member of a curated
+/// association set
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssociationWithdrawInputs {
+    pub nullifier: [u8; 32],
+    /// Must be all zeroes for SOL
+    pub asset_id: [u8; 32],
+    pub amount: u64,
+    pub root: [u8; 32],
+    pub association_root: [u8; 32],
+    /// Receives the withdrawal, the proof binds it
+    pub recipient: Pubkey,
+}
+
+//Withdraw association circuit:
+//[amount, asset_id, nullifier, root, association_root, recipient_hi, recipient_lo]
+impl PublicInputs<7> for AssociationWithdrawInputs {
+    fn to_field_elements(&self) -> [[u8; 32]; 7] {
+        let [recipient_hi, recipient_lo] = pubkey_to_limbs(&self.recipient);
+        [
+            amount_to_field(self.amount),
+            self.asset_id,
+            self.nullifier,
+            self.root,
+            self.association_root,
+            recipient_hi,
+            recipient_lo,
+        ]
+    }
+}
@@ -0,0 +1,89 @@
+//! Solvency accounting. Every instruction moving the notes' lamports records it in the
+//! pool's ledger, and `audit_pool` checks the pool still holds what the unspent notes are
+//! worth on top of its own rent.
+use crate::error::ErrorCode;
+use crate::events::PoolAudited;
+use crate::reserve::RentReserve;
+use crate::MerkleMountainRange;
+use anchor_lang::prelude::*;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolLedger {
+    /// Lamports deposited into notes
+    pub deposited: u64,
+    /// Lamports paid out to note owners
+    pub withdrawn: u64,
+    /// Fees taken out of spent notes, the pool fee and relayer fees. Fees a signer pays
+    /// on top (combines) never were pool lamports and aren't counted.
+    pub fees: u64,
+    /// Shard rent the rent reserve paid, net of the rent split shards gave back
+    pub rent_spent: u64,
+}
+
+fn add(counter: &mut u64, amount: u64) -> Result<()> {
+    *counter = counter
+        .checked_add(amount)
+        .ok_or(ErrorCode::LedgerOverflow)?;
+    Ok(())
+}
+
+impl PoolLedger {
+    pub const SIZE: usize = 4 * 8;
+
+    pub fn deposit(&mut self, amount: u64) -> Result<()> {
+        add(&mut self.deposited, amount)
+    }
+
+    /// A spent note: `paid` to its owner, `fees` kept out of it
+    pub fn withdraw(&mut self, paid: u64, fees: u64) -> Result<()> {
+        add(&mut self.withdrawn, paid)?;
+        add(&mut self.fees, fees)
+    }
+
+    pub fn spend_rent(&mut self, rent: u64) -> Result<()> {
+        add(&mut self.rent_spent, rent)
+    }
+
+    /// What the unspent notes are worth. Pools created before the ledger count from zero
+    /// and may have spent more than they recorded, that floors at 0.
+    pub fn outstanding(&self) -> u64 {
+        self.deposited
+            .saturating_sub(self.withdrawn.saturating_add(self.fees))
+    }
+}
+
+#[derive(Accounts)]
+pub struct AuditPool<'info> {
+    #[account(
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    #[account(
+        seeds = [ b"rent_reserve", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub rent_reserve: Account<'info, RentReserve>,
+}
+
+/// Emits the ledger and the pool's lamports, then fails with `PoolInsolvent` unless
+/// `lamports >= rent_exempt + deposited - withdrawn - fees`
+pub fn audit_pool(ctx: Context<AuditPool>) -> Result<()> {
+    let pool_ai = ctx.accounts.pool.to_account_info();
+    let ledger = ctx.accounts.pool.ledger;
+    let lamports = pool_ai.lamports();
+    let rent_exempt = Rent::get()?.minimum_balance(pool_ai.data_len());
+    let owed = rent_exempt.saturating_add(ledger.outstanding());
+
+    emit!(PoolAudited {
+        pool: ctx.accounts.pool.identifier,
+        deposited: ledger.deposited,
+        withdrawn: ledger.withdrawn,
+        fees: ledger.fees,
+        rent_spent: ledger.rent_spent,
+        lamports,
+    });
+    require!(lamports >= owed, ErrorCode::PoolInsolvent);
+    Ok(())
+}
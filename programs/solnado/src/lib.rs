@@ -1,9 +1,27 @@
 use anchor_lang::prelude::*;
+pub mod association;
+pub mod denylist;
+pub mod directory;
 pub mod error;
+pub mod events;
+pub mod inbox;
+pub mod indexed;
+pub mod inputs;
+pub mod ledger;
+pub mod limits;
+pub mod memo;
+pub mod migrate;
+pub mod nullifier;
+pub mod registry;
+pub mod reserve;
 pub mod shard;
 pub mod state;
+pub mod stealth;
+pub mod sunset;
 pub mod utils;
 pub mod verifying_key;
+use crate::inputs::*;
+use crate::nullifier::NullifierStoreKind;
 use crate::state::*;
 use crate::utils::*;
 use anchor_lang::solana_program::{
@@ -12,6 +30,16 @@ use anchor_lang::solana_program::{
 };
 
 use crate::shard::*;
+use crate::indexed::*;
+use crate::reserve::*;
+use crate::ledger::*;
+use crate::limits::*;
+use crate::association::*;
+use crate::inbox::*;
+use crate::denylist::*;
+use crate::migrate::*;
+use crate::registry::*;
+use crate::sunset::*;
 
 pub const DEFAULT_LEAF: [u8; 32] = [0u8; 32];
 pub const TREE_DEPTH: u8 = 4;
@@ -25,9 +53,9 @@ const MIN_PDA_SIZE: usize = 1;
 
 
 declare_id!("2xJgeatVVK3u3SNf4pyXuLuc2UrzEQBprPds2qfJSuEt");
-const TARGET_DEPTH_LARGE_ARRAY: usize = 26;
-const TARGET_DEPTH_LARGE: usize = 30;
-const BATCHES_PER_SMALL_TREE: u64 = 4096; //Corresponds to 2^16 leaves --> about 9 rpc calls
+pub const TARGET_DEPTH_LARGE_ARRAY: usize = 26;
+pub const TARGET_DEPTH_LARGE: usize = 30;
+pub const BATCHES_PER_SMALL_TREE: u64 = 4096; //Corresponds to 2^16 leaves --> about 9 rpc calls
 const SMALL_TREE_BATCH_DEPTH: usize = 16; //This 64 000 leaves
                                           // const ADMIN_KEY: Pubkey = pubkey!("EJZQiTeikeg8zgU7YgRfwZCxc9GdhTsYR3fQrXv3uK9V");
 // const ADMIN_KEY: Pubkey = pubkey!("BSpEVXMrA3C1myPSUmT8hQSecrvJaUin8vnQTfzGGf17");
@@ -51,23 +79,39 @@ pub mod solnado {
     pub fn initialize_variable_pool(
         ctx: Context<InitializeVariablePool>,
         identifier: [u8; 16],
+        nullifier_store: NullifierStoreKind,
+        name: String,
+        namespaced: bool,
     ) -> Result<()> {
         msg!("Initializing a variable pool, will be used for variable amounts deposits");
+        require!(
+            nullifier_store != NullifierStoreKind::Indexed || cfg!(feature = "dev-keys"),
+            ErrorCode::CircuitNotDeployed
+        );
 
+        register_pool(
+            &mut ctx.accounts.pool_registry,
+            &mut ctx.accounts.pool_entry,
+            identifier,
+            ctx.accounts.authority.key(),
+            NATIVE_MINT,
+            &name,
+            namespaced,
+        )?;
         let pool = &mut ctx.accounts.pool;
 
         pool.identifier = identifier;
-        let pool_string = std::str::from_utf8(&identifier)
-            .unwrap_or("Invalid utf_8")
-            .trim_end_matches(char::from(0));
-        pool.batch_leaves = default_leaves();
-        pool.merkle_root_batch = get_root(&pool.batch_leaves);
+        pool.reset_batch();
         pool.batch_number = 0;
         pool.depth = [0; TARGET_DEPTH_LARGE_ARRAY];
         pool.number_of_peaks = 0;
         pool.peaks = [DEFAULT_LEAF; TARGET_DEPTH_LARGE_ARRAY];
+        pool.refresh_deep_root();
+        pool.version = POOL_VERSION;
         pool.max_leaves = (2_u64).pow(TARGET_DEPTH_LARGE as u32);
         pool.min_deposit_amount = 5_000_000;
+        pool.nullifier_store = nullifier_store;
+        pool.authority = ctx.accounts.authority.key();
         // At least a 0.005 SOL DEPOSIT per action of use
 
         msg!(
@@ -75,7 +119,7 @@ pub mod solnado {
         Pool name: {}\n
         Pool max leaves: {}\n",
             ctx.accounts.authority.key(),
-            pool_string,
+            name,
             pool.max_leaves
         );
 
@@ -148,34 +192,38 @@ pub mod solnado {
     pub fn deposit_variable(
         ctx: Context<DepositVariable>,
         proof: [u8; 256],
-        public_inputs: [u8; 72],
+        inputs: DepositInputs,
+        notes: Vec<Option<EncryptedNote>>,
     ) -> Result<()> {
+        screen_depositor(
+            &ctx.accounts.denylist,
+            ctx.accounts.pool.identifier,
+            &ctx.accounts.depositor.key(),
+        )?;
+        require_deposits_open(&ctx.accounts.pool)?;
         let pool = &mut ctx.accounts.pool;
         let depositor = ctx.accounts.depositor.to_account_info();
         let pool_ai = pool.to_account_info();
         let sysvar_ai = &ctx.accounts.instruction_account;
 
-        let null_leaf2: [u8; 32] = public_inputs[40..72].try_into().expect("Failed converting");
-
-        let (deposit_sum, leaves) = match null_leaf2 == DEFAULT_LEAF {
+        let deposit_sum = inputs.amount;
+        let leaves = match inputs.leaf2 == DEFAULT_LEAF {
             false => {
                 // two-leaf proof
-                let (sum_be, leaf1, leaf2) = verify_deposit_proof(&proof, &public_inputs)
-                    .map_err(|_| ErrorCode::InvalidProof)?;
-                let sum = u64::from_be_bytes(sum_be.try_into().unwrap());
-                msg!("Leaf1: {:?}, leaf2: {:?}, sum: {}", leaf1, leaf2, sum);
-                (sum, vec![leaf1, leaf2])
+                verify_deposit_proof(&proof, &inputs).map_err(|_| ErrorCode::InvalidProof)?;
+                msg!("Leaf1: {:?}, leaf2: {:?}, sum: {}", inputs.leaf1, inputs.leaf2, deposit_sum);
+                vec![inputs.leaf1, inputs.leaf2]
             }
             true => {
                 // single-leaf proof
-                let (sum_be, leaf1) = verify_single_deposit_proof(&proof, &public_inputs)
-                    .map_err(|_| ErrorCode::InvalidProof)?;
-                let sum = u64::from_be_bytes(sum_be.try_into().unwrap());
-                msg!("Leaf1: {:?}, sum {}", leaf1, sum);
-                (sum, vec![leaf1])
+                verify_single_deposit_proof(&proof, &inputs).map_err(|_| ErrorCode::InvalidProof)?;
+                msg!("Leaf1: {:?}, sum {}", inputs.leaf1, deposit_sum);
+                vec![inputs.leaf1]
             }
-            _ => return Err(ErrorCode::InvalidArgument.into()),
         };
+        publish_notes(&leaves, &notes)?;
+
+        admit_deposit(pool, deposit_sum, Clock::get()?.unix_timestamp)?;
 
         // 2) Transfer lamports
         invoke(
@@ -190,16 +238,14 @@ pub mod solnado {
                 ctx.accounts.system_program.to_account_info(),
             ],
         )?;
+        pool.ledger.deposit(deposit_sum)?;
 
         let mut idx = pool.find_first_match() as usize;
         require!(idx < LEAVES_LENGTH, ErrorCode::InvalidIndexing);
 
         for (_, leaf) in leaves.into_iter().enumerate() {
-            // 1) insert
-            pool.batch_leaves[idx] = leaf;
-            // 2) update root
-            pool.merkle_root_batch = get_root(&pool.batch_leaves);
-            msg!("Leaf that was inserted: {:?}", leaf);
+            // 1) insert 2) update root
+            pool.insert_leaf(idx, leaf);
 
             // 3) did we just cross the 8‐leaf mark?
             if idx + 1 == SUB_BATCH_SIZE {
@@ -238,14 +284,7 @@ pub mod solnado {
                     &pool.batch_leaves[SUB_BATCH_SIZE..LEAVES_LENGTH],
                 )?;
                 // rollover into peaks, bump batch_number, reset leaves
-                let batch_root = pool.merkle_root_batch;
-
-                pool.update_peaks(batch_root);
-                pool.batch_number = pool.batch_number.checked_add(1).unwrap();
-                pool.whole_tree_root = pool.compute_root_from_peaks();
-
-                pool.batch_leaves = default_leaves();
-                pool.merkle_root_batch = get_root(&pool.batch_leaves);
+                pool.roll_over_batch();
                 // after rollover, the *next* leaves go at slot 0
                 idx = 0;
                 continue;
@@ -282,50 +321,148 @@ pub mod solnado {
 
             idx += 1;
         }
+        pool.refresh_deep_root();
         Ok(())
     }
 
-        pub fn initialize_shards(ctx: Context<InitializeNullifierShards>) -> Result<()> {
-        initialize_nullifier_shards(ctx)
+        pub fn initialize_shards(ctx: Context<InitializeNullifierShards>, split_threshold: u16) -> Result<()> {
+        initialize_nullifier_shards(ctx, split_threshold)
+    }
+
+    /// Pool authority only, zero caps lift the limit
+    pub fn set_deposit_limits(ctx: Context<SetDepositLimits>, limits: DepositLimits) -> Result<()> {
+        limits::set_deposit_limits(ctx, limits)
     }
 
-    pub fn reset_shards(ctx: Context<ResetNullifierShards>) -> Result<()>{
-        reset_nullifier_shards(ctx)
+    /// Checks the pool covers its outstanding notes and logs the breakdown, open to anyone
+    pub fn audit_pool(ctx: Context<AuditPool>) -> Result<()> {
+        ledger::audit_pool(ctx)
+    }
+
+    /// Tops up the pool's rent reserve, open to anyone
+    pub fn fund_rent_reserve(ctx: Context<FundRentReserve>, amount: u64) -> Result<()> {
+        reserve::fund_rent_reserve(ctx, amount)
+    }
+
+    /// Fails with `CircuitNotDeployed` unless built with `dev-keys`
+    pub fn initialize_nullifier_tree(ctx: Context<InitializeNullifierTree>) -> Result<()> {
+        indexed::initialize_nullifier_tree(ctx)
+    }
+
+    /// Carries the insertion proof of an indexed pool spend in the same transaction
+    pub fn nullifier_insertion_proof(
+        ctx: Context<CarryInsertionProof>,
+        proof: [u8; 256],
+        inputs: IndexedInsertInputs,
+    ) -> Result<()> {
+        carry_insertion_proof(ctx, proof, inputs)
     }
 
     pub fn combine_deposit_shard_single<'info>(
         ctx: Context<CombineDepositShardSingle>,
         proof: [u8; 256],
-        public_inputs: [u8; 128],
+        inputs: CombineSingleInputs,
+        notes: Vec<Option<EncryptedNote>>,
     ) -> Result<()> {
-        combine_deposit_shard_single_nullifier(ctx, proof, public_inputs)
+        combine_deposit_shard_single_nullifier(ctx, proof, inputs, notes)
     }
 
         pub fn combine_deposit_shard_double<'info>(
         ctx: Context<CombineDepositShardDouble>,
         same_shard: u8,
         proof: [u8; 256],
-        public_inputs: [u8; 128],
+        inputs: CombineDoubleInputs,
+        notes: Vec<Option<EncryptedNote>>,
     ) -> Result<()> {
-        combine_deposit_shard_double_nullifier(ctx, same_shard, proof, public_inputs)
+        combine_deposit_shard_double_nullifier(ctx, same_shard, proof, inputs, notes)
     }
 
     pub fn withdraw_variable_shard(
         ctx: Context<WithdrawVariableShard>,
         mode: u8,
         proof: [u8; 256],
-        public_inputs: [u8; 136],
+        inputs: WithdrawInputs,
+        notes: Vec<Option<EncryptedNote>>,
     ) -> Result<()> {
-        withdraw_variable_shard_nullifier(ctx, mode, proof, public_inputs)
+        withdraw_variable_shard_nullifier(ctx, mode, proof, inputs, notes)
     }
 
     pub fn withdraw_on_behalf_shard(
         ctx: Context<WithdrawOnBehalfShard>,
         proof: [u8; 256],
-        public_inputs: [u8; 104], // nullifier(32)||amount(8)||root(32)||withdrawer_pubkey(32)
+        inputs: OnBehalfInputs,
+        stealth_ephemeral: Option<[u8; 32]>,
+    ) -> Result<()> {
+        withdraw_on_behalf_with_shard(ctx, proof, inputs, stealth_ephemeral)
+    }
+
+    /// Pool authority only
+    pub fn initialize_association_registry(ctx: Context<InitializeAssociationRegistry>) -> Result<()> {
+        association::initialize_association_registry(ctx)
+    }
+
+    /// Pool authority only, approves or revokes a curator
+    pub fn set_association_curator(
+        ctx: Context<SetAssociationCurator>,
+        curator: Pubkey,
+        approved: bool,
+    ) -> Result<()> {
+        association::set_association_curator(ctx, curator, approved)
+    }
+
+    /// Approved curators only
+    pub fn publish_association_root(ctx: Context<PublishAssociationRoot>, root: [u8; 32]) -> Result<()> {
+        association::publish_association_root(ctx, root)
+    }
+
+    /// Fails with `CircuitNotDeployed` unless built with `dev-keys`
+    pub fn withdraw_with_association(
+        ctx: Context<WithdrawWithAssociation>,
+        proof: [u8; 256],
+        inputs: AssociationWithdrawInputs,
+    ) -> Result<()> {
+        association::withdraw_with_association(ctx, proof, inputs)
+    }
+
+    /// Pool authority only
+    pub fn initialize_denylist(ctx: Context<InitializeDenylist>) -> Result<()> {
+        denylist::initialize_denylist(ctx)
+    }
+
+    /// Pool authority only, removals apply before additions
+    pub fn update_denylist(
+        ctx: Context<UpdateDenylist>,
+        add: Vec<Pubkey>,
+        remove: Vec<Pubkey>,
     ) -> Result<()> {
-        withdraw_on_behalf_with_shard(ctx, proof, public_inputs)
+        denylist::update_denylist(ctx, add, remove)
+    }
+
+    /// Creates the global pool registry, once, open to anyone
+    pub fn initialize_pool_registry(ctx: Context<InitializePoolRegistry>) -> Result<()> {
+        registry::initialize_pool_registry(ctx)
+    }
+
+    /// Upgrades a pool to the current layout, open to anyone. The rent reserve pays for
+    /// any growth. Pools from before the authority field need the program's upgrade
+    /// authority, who gives them `authority`
+    pub fn migrate_pool(
+        ctx: Context<MigratePool>,
+        identifier: [u8; 16],
+        authority: Option<Pubkey>,
+    ) -> Result<()> {
+        migrate::migrate_pool(ctx, identifier, authority)
+    }
+
+    /// Pool authority only. Stops deposits, spends go on
+    pub fn sunset_pool(ctx: Context<SunsetPool>) -> Result<()> {
+        sunset::sunset_pool(ctx)
+    }
 
+    /// Pool authority only. Closes a sunset pool once the grace period is over, and
+    /// reclaims the rent of the empty indexers and shards passed as remaining accounts
+    pub fn close_pool<'info>(ctx: Context<'_, '_, 'info, 'info, ClosePool<'info>>) -> Result<()> {
+        sunset::close_pool(ctx)
     }
 
 
@@ -0,0 +1,111 @@
+//! Deposit guardrails of a pool: a cap per deposit, per epoch window and on the value the
+//! pool holds. Every deposit path runs `admit_deposit` before taking lamports in, the pool
+//! authority sets the caps. A zero cap doesn't limit.
+use crate::error::ErrorCode;
+use crate::MerkleMountainRange;
+use anchor_lang::prelude::*;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepositLimits {
+    /// Largest single deposit
+    pub max_deposit: u64,
+    /// Lamports deposited per epoch window
+    pub max_per_epoch: u64,
+    /// Epoch window length in seconds, needed by `max_per_epoch`
+    pub epoch_seconds: u64,
+    /// Largest value of the outstanding notes
+    pub max_tvl: u64,
+}
+
+/// Deposits of the current epoch window
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepositEpoch {
+    /// Unix time the window opened, a multiple of `epoch_seconds`
+    pub start: i64,
+    pub deposited: u64,
+}
+
+impl DepositLimits {
+    pub const SIZE: usize = 4 * 8;
+
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.max_per_epoch == 0 || (1..=i64::MAX as u64).contains(&self.epoch_seconds),
+            ErrorCode::InvalidDepositLimits
+        );
+        Ok(())
+    }
+}
+
+impl DepositEpoch {
+    pub const SIZE: usize = 8 + 8;
+}
+
+/// Checks `amount` against the pool's caps at unix time `now` and counts it in the
+/// epoch window. The caller records the deposit in the ledger.
+pub fn admit_deposit(pool: &mut MerkleMountainRange, amount: u64, now: i64) -> Result<()> {
+    let limits = pool.deposit_limits;
+    require!(
+        limits.max_deposit == 0 || amount <= limits.max_deposit,
+        ErrorCode::DepositTooLarge
+    );
+    require!(
+        limits.max_tvl == 0
+            || pool
+                .ledger
+                .outstanding()
+                .checked_add(amount)
+                .is_some_and(|tvl| tvl <= limits.max_tvl),
+        ErrorCode::TvlCapReached
+    );
+
+    if limits.max_per_epoch != 0 {
+        let length = limits.epoch_seconds as i64;
+        let start = now - now.rem_euclid(length);
+        if pool.deposit_epoch.start != start {
+            pool.deposit_epoch = DepositEpoch {
+                start,
+                deposited: 0,
+            };
+        }
+        let deposited = pool
+            .deposit_epoch
+            .deposited
+            .checked_add(amount)
+            .filter(|d| *d <= limits.max_per_epoch)
+            .ok_or(ErrorCode::EpochDepositCapReached)?;
+        pool.deposit_epoch.deposited = deposited;
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetDepositLimits<'info> {
+    #[account(
+        mut,
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAction
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    pub authority: Signer<'info>,
+}
+
+pub fn set_deposit_limits(ctx: Context<SetDepositLimits>, limits: DepositLimits) -> Result<()> {
+    limits.validate()?;
+    let pool = &mut ctx.accounts.pool;
+    // a new window length starts counting afresh
+    if limits.epoch_seconds != pool.deposit_limits.epoch_seconds {
+        pool.deposit_epoch = DepositEpoch::default();
+    }
+    pool.deposit_limits = limits;
+    msg!(
+        "Deposit caps: {} per deposit, {} per {}s epoch, {} total",
+        limits.max_deposit,
+        limits.max_per_epoch,
+        limits.epoch_seconds,
+        limits.max_tvl
+    );
+    Ok(())
+}
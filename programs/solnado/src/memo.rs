@@ -0,0 +1,188 @@
+//! Memo payloads the deposit paths require next to their instruction. Indexers rebuild
+//! the tree from them, so decoding lives here and is shared with off-chain code, see
+//! the SDK's `memo` module.
+//!
+//! Memo data is the standard base64 of:
+//! - sub batch:  batch_number_be(8) || leaf(32) * SUB_BATCH_SIZE
+//! - small tree: closed_batch_be(8) || small_tree_root(32)
+use crate::error::ErrorCode;
+use crate::utils::{MEMO_PROGRAM_ID, SUB_BATCH_SIZE};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar::instructions;
+use base64::{engine::general_purpose, Engine as _};
+use std::fmt;
+
+pub const SUB_BATCH_MEMO_LEN: usize = 8 + SUB_BATCH_SIZE * 32;
+pub const SMALL_TREE_MEMO_LEN: usize = 8 + 32;
+/// Instructions before the program call that may carry the memo
+pub const MEMO_SEARCH_WINDOW: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoError {
+    NotUtf8,
+    NotBase64,
+    InvalidLength { expected: usize, got: usize },
+}
+
+impl fmt::Display for MemoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoError::NotUtf8 => write!(f, "memo is not utf8"),
+            MemoError::NotBase64 => write!(f, "memo is not base64"),
+            MemoError::InvalidLength { expected, got } => {
+                write!(f, "memo payload is {} bytes, expected {}", got, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MemoError {}
+
+impl From<MemoError> for ErrorCode {
+    fn from(e: MemoError) -> Self {
+        match e {
+            MemoError::NotUtf8 => ErrorCode::InvalidMemoUtf8,
+            MemoError::NotBase64 => ErrorCode::InvalidMemoBase64,
+            MemoError::InvalidLength { .. } => ErrorCode::InvalidMemoLength,
+        }
+    }
+}
+
+impl From<MemoError> for anchor_lang::error::Error {
+    fn from(e: MemoError) -> Self {
+        ErrorCode::from(e).into()
+    }
+}
+
+/// Memo data to raw payload, length unchecked
+pub fn decode_payload(data: &[u8]) -> std::result::Result<Vec<u8>, MemoError> {
+    let text = std::str::from_utf8(data).map_err(|_| MemoError::NotUtf8)?;
+    general_purpose::STANDARD
+        .decode(text)
+        .map_err(|_| MemoError::NotBase64)
+}
+
+fn decode_exact<const N: usize>(data: &[u8]) -> std::result::Result<[u8; N], MemoError> {
+    let payload = decode_payload(data)?;
+    payload
+        .as_slice()
+        .try_into()
+        .map_err(|_| MemoError::InvalidLength {
+            expected: N,
+            got: payload.len(),
+        })
+}
+
+fn split_be_u64(payload: &[u8]) -> (u64, &[u8]) {
+    let (head, rest) = payload.split_at(8);
+    let mut be = [0u8; 8];
+    be.copy_from_slice(head);
+    (u64::from_be_bytes(be), rest)
+}
+
+/// Leaves of a sub batch, posted with the deposit completing it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubBatchMemo {
+    pub batch_number: u64,
+    pub leaves: [[u8; 32]; SUB_BATCH_SIZE],
+}
+
+impl SubBatchMemo {
+    pub fn decode(data: &[u8]) -> std::result::Result<Self, MemoError> {
+        let payload = decode_exact::<SUB_BATCH_MEMO_LEN>(data)?;
+        let (batch_number, rest) = split_be_u64(&payload);
+        let mut leaves = [[0u8; 32]; SUB_BATCH_SIZE];
+        for (leaf, chunk) in leaves.iter_mut().zip(rest.chunks_exact(32)) {
+            leaf.copy_from_slice(chunk);
+        }
+        Ok(SubBatchMemo {
+            batch_number,
+            leaves,
+        })
+    }
+
+    /// Memo instruction data
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(SUB_BATCH_MEMO_LEN);
+        payload.extend_from_slice(&self.batch_number.to_be_bytes());
+        for leaf in &self.leaves {
+            payload.extend_from_slice(leaf);
+        }
+        general_purpose::STANDARD.encode(payload).into_bytes()
+    }
+}
+
+/// Root of the small tree just closed, posted with the first deposit of the next one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SmallTreeMemo {
+    pub closed_batch: u64,
+    pub root: [u8; 32],
+}
+
+impl SmallTreeMemo {
+    pub fn decode(data: &[u8]) -> std::result::Result<Self, MemoError> {
+        let payload = decode_exact::<SMALL_TREE_MEMO_LEN>(data)?;
+        let (closed_batch, rest) = split_be_u64(&payload);
+        let mut root = [0u8; 32];
+        root.copy_from_slice(rest);
+        Ok(SmallTreeMemo { closed_batch, root })
+    }
+
+    /// Memo instruction data
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(SMALL_TREE_MEMO_LEN);
+        payload.extend_from_slice(&self.closed_batch.to_be_bytes());
+        payload.extend_from_slice(&self.root);
+        general_purpose::STANDARD.encode(payload).into_bytes()
+    }
+}
+
+/// Either memo, told apart by payload length
+#[allow(clippy::large_enum_variant)] // decoded one at a time, boxing buys nothing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolMemo {
+    SubBatch(SubBatchMemo),
+    SmallTree(SmallTreeMemo),
+}
+
+impl PoolMemo {
+    pub fn decode(data: &[u8]) -> std::result::Result<Self, MemoError> {
+        match decode_payload(data)?.len() {
+            SUB_BATCH_MEMO_LEN => SubBatchMemo::decode(data).map(PoolMemo::SubBatch),
+            SMALL_TREE_MEMO_LEN => SmallTreeMemo::decode(data).map(PoolMemo::SmallTree),
+            got => Err(MemoError::InvalidLength {
+                expected: SUB_BATCH_MEMO_LEN,
+                got,
+            }),
+        }
+    }
+
+    /// Memo instruction data
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            PoolMemo::SubBatch(memo) => memo.encode(),
+            PoolMemo::SmallTree(memo) => memo.encode(),
+        }
+    }
+}
+
+/// Memo instruction within `MEMO_SEARCH_WINDOW` of the transaction whose payload is
+/// `payload_len` bytes, a deposit closing a batch and a small tree carries one of each
+/// kind. Without one of that length, the first memo, for its decoding to fail on
+pub fn find_memo_instruction(
+    sysvar_account: &AccountInfo,
+    payload_len: usize,
+) -> Result<Instruction> {
+    let mut memos = (0..MEMO_SEARCH_WINDOW)
+        .map_while(|i| instructions::load_instruction_at_checked(i, sysvar_account).ok())
+        .filter(|ix| ix.program_id == MEMO_PROGRAM_ID)
+        .peekable();
+    let first = memos
+        .peek()
+        .cloned()
+        .ok_or_else(|| error!(ErrorCode::MissingMemoInstruction))?;
+    Ok(memos
+        .find(|ix| decode_payload(&ix.data).is_ok_and(|p| p.len() == payload_len))
+        .unwrap_or(first))
+}
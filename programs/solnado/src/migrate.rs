@@ -0,0 +1,181 @@
+//! Pool layout versions. `MerkleMountainRange::version` says which layout a pool account
+//! holds, new fields go at the end of the struct and `migrate_pool` grows older pools to
+//! the current size, at the rent reserve's expense, then runs the upgrade steps in order.
+//! Bytes a realloc adds are zero, so a new field reads as zero until its step fills it.
+//!
+//! Pools created before the authority, ledger and rent reserve read a zero authority and
+//! ledger and have no reserve. Only the program's upgrade authority migrates those: it
+//! names the pool's authority, pays for the growth and the reserve, and the ledger starts
+//! from what the pool holds over its rent, all of it notes.
+use crate::error::ErrorCode;
+use crate::ledger::PoolLedger;
+use crate::reserve::{derive_rent_reserve_pda_key, pay_rent, RentReserve, RENT_RESERVE_SPACE};
+use crate::utils::batch_nodes;
+use crate::MerkleMountainRange;
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+
+/// Layout `initialize_variable_pool` writes
+pub const POOL_VERSION: u8 = 2;
+
+#[derive(Accounts)]
+#[instruction(identifier: [u8; 16])]
+pub struct MigratePool<'info> {
+    ///CHECK: an old layout may be too short to deserialize, it's read after the realloc
+    #[account(
+        mut,
+        seeds = [ b"variable_pool", identifier.as_ref() ],
+        bump,
+        owner = crate::ID
+    )]
+    pub pool: AccountInfo<'info>,
+
+    ///CHECK: checked by `pay_rent`, or created here for a pool that predates it
+    #[account(mut)]
+    pub rent_reserve: AccountInfo<'info>,
+
+    /// The program's upgrade authority, for pools without an authority
+    #[account(mut)]
+    pub admin: Option<Signer<'info>>,
+
+    #[account(
+        seeds = [ crate::ID.as_ref() ],
+        bump,
+        seeds::program = ProgramData::owner()
+    )]
+    pub program_data: Option<Account<'info, ProgramData>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// The admin signer if one was passed, once checked against the upgrade authority
+fn checked_admin<'a, 'info>(accounts: &'a MigratePool<'info>) -> Result<Option<&'a Signer<'info>>> {
+    let Some(admin) = &accounts.admin else {
+        return Ok(None);
+    };
+    let upgrade_authority = accounts
+        .program_data
+        .as_ref()
+        .and_then(|data| data.upgrade_authority_address);
+    require!(
+        upgrade_authority == Some(admin.key()),
+        ErrorCode::UnauthorizedAction
+    );
+    Ok(Some(admin))
+}
+
+/// Creates the rent reserve of a pool from before the reserves, at `admin`'s expense
+fn create_rent_reserve<'info>(
+    accounts: &MigratePool<'info>,
+    admin: &Signer<'info>,
+    identifier: [u8; 16],
+) -> Result<()> {
+    let reserve = &accounts.rent_reserve;
+    let (key, bump) = derive_rent_reserve_pda_key(identifier);
+    require!(reserve.key() == key, ErrorCode::InvalidRentReserve);
+    system_program::create_account(
+        CpiContext::new_with_signer(
+            accounts.system_program.to_account_info(),
+            system_program::CreateAccount {
+                from: admin.to_account_info(),
+                to: reserve.clone(),
+            },
+            &[&[b"rent_reserve", identifier.as_ref(), &[bump]]],
+        ),
+        Rent::get()?.minimum_balance(RENT_RESERVE_SPACE),
+        RENT_RESERVE_SPACE as u64,
+        &crate::ID,
+    )?;
+    reserve.try_borrow_mut_data()?[..8].copy_from_slice(RentReserve::DISCRIMINATOR);
+    Ok(())
+}
+
+/// Upgrades `pool` from its version to the next one
+fn upgrade(pool: &mut MerkleMountainRange) {
+    match pool.version {
+        // before the version byte: pools may predate the batch cache and deep root, and
+        // their roots were bagged pairwise, which isn't the depth 30 tree the circuits
+        // prove membership in once there are three peaks or more. Rebag both roots, the
+        // peaks themselves are unchanged
+        0 => {
+            pool.batch_nodes = batch_nodes(&pool.batch_leaves);
+            if pool.number_of_peaks > 0 {
+                pool.whole_tree_root = pool.compute_root_from_peaks();
+            }
+            pool.refresh_deep_root();
+        }
+        // lifecycle fields: zero reads as active, never sunset
+        1 => {}
+        _ => unreachable!(),
+    }
+    pool.version += 1;
+}
+
+pub fn migrate_pool(
+    ctx: Context<MigratePool>,
+    identifier: [u8; 16],
+    authority: Option<Pubkey>,
+) -> Result<()> {
+    let admin = checked_admin(ctx.accounts)?;
+    if let Some(admin) = admin {
+        if ctx.accounts.rent_reserve.lamports() == 0 {
+            create_rent_reserve(ctx.accounts, admin, identifier)?;
+        }
+    }
+
+    let pool_ai = &ctx.accounts.pool;
+    let space = 8 + MerkleMountainRange::SIZE;
+    let old_len = pool_ai.data_len();
+    let mut rent_paid = 0;
+    if old_len < space {
+        // the pool's lamports back the notes, the reserve or the admin pays for the growth
+        let rent = Rent::get()?;
+        let growth = rent
+            .minimum_balance(space)
+            .saturating_sub(rent.minimum_balance(old_len));
+        match admin {
+            Some(admin) => system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: admin.to_account_info(),
+                        to: pool_ai.clone(),
+                    },
+                ),
+                growth,
+            )?,
+            None => {
+                pay_rent(&ctx.accounts.rent_reserve, identifier, pool_ai, growth)?;
+                rent_paid = growth;
+            }
+        }
+        pool_ai.resize(space)?;
+    }
+
+    let mut pool = MerkleMountainRange::try_deserialize(&mut &pool_ai.try_borrow_data()?[..])?;
+    require!(pool.version < POOL_VERSION, ErrorCode::PoolUpToDate);
+    let from = pool.version;
+    while pool.version < POOL_VERSION {
+        upgrade(&mut pool);
+    }
+    if pool.authority == Pubkey::default() {
+        require!(admin.is_some(), ErrorCode::UnauthorizedAction);
+        pool.authority = authority.ok_or(ErrorCode::MissingPoolAuthority)?;
+        if pool.ledger == PoolLedger::default() {
+            let rent_exempt = Rent::get()?.minimum_balance(pool_ai.data_len());
+            pool.ledger.deposited = pool_ai.lamports().saturating_sub(rent_exempt);
+        }
+    } else {
+        require!(authority.is_none(), ErrorCode::InvalidArgument);
+    }
+    pool.ledger.spend_rent(rent_paid)?;
+    pool.try_serialize(&mut &mut pool_ai.try_borrow_mut_data()?[..])?;
+
+    msg!(
+        "Pool migrated from version {} to {}, {} bytes",
+        from,
+        POOL_VERSION,
+        pool_ai.data_len()
+    );
+    Ok(())
+}
@@ -0,0 +1,136 @@
+//! Spent nullifier storage. A pool picks its store once, at creation: sharded `BitShard`
+//! tries (cheap rent, splits as they fill), one PDA per nullifier (never splits, costs
+//! the spender a rent exempt account each time) or an indexed Merkle tree (constant rent,
+//! each spend carries an insertion proof).
+use crate::error::ErrorCode;
+use crate::events::NullifierSpent;
+use crate::indexed::IndexedStore;
+use crate::shard::process_one_nullifier_ai;
+use crate::state::MerkleMountainRange;
+use crate::MIN_PDA_SIZE;
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NullifierStoreKind {
+    #[default]
+    Sharded,
+    /// One PDA per spent nullifier. Its rent, `minimum_balance(MIN_PDA_SIZE)` (897 840
+    /// lamports), comes from the spend's payer on top of the fees, not from the rent
+    /// reserve: more than `POOL_FEE` refills it with. On-behalf withdrawals make the
+    /// relayer pay it, well beyond `ON_BEHALF_FEE`
+    PerNullifier,
+    /// Indexed Merkle tree, see `indexed`. Only with the `dev-keys` feature
+    Indexed,
+}
+
+/// Records spent nullifiers, whatever the backing accounts
+pub trait NullifierStore {
+    /// Marks `nullifier` spent, `NullifierAlreadyUsed` if it already was. Returns the
+    /// account it was recorded in
+    fn spend(&self, nullifier: &[u8; 32]) -> Result<Pubkey>;
+}
+
+/// Accounts a spend instruction passes for one nullifier. `record` is the shard in
+/// sharded pools and the nullifier PDA otherwise, the children only serve shard splits.
+pub struct NullifierAccounts<'a, 'info> {
+    pub pool: &'a Account<'info, MerkleMountainRange>,
+    pub record: &'a AccountInfo<'info>,
+    pub child0: &'a AccountInfo<'info>,
+    pub child1: &'a AccountInfo<'info>,
+    /// Live shard list of sharded pools, unused otherwise
+    pub directory: &'a AccountInfo<'info>,
+    /// Pool's rent reserve, pays for shard splits
+    pub reserve: &'a AccountInfo<'info>,
+    /// Instructions sysvar, where indexed pools find the insertion proof
+    pub instructions: &'a AccountInfo<'info>,
+    pub payer: &'a AccountInfo<'info>,
+    pub system_program: &'a Program<'info, System>,
+}
+
+pub struct ShardStore<'s, 'a, 'info>(pub &'s NullifierAccounts<'a, 'info>);
+
+impl NullifierStore for ShardStore<'_, '_, '_> {
+    fn spend(&self, nullifier: &[u8; 32]) -> Result<Pubkey> {
+        process_one_nullifier_ai(self.0, *nullifier)
+    }
+}
+
+pub struct PdaStore<'s, 'a, 'info>(pub &'s NullifierAccounts<'a, 'info>);
+
+impl NullifierStore for PdaStore<'_, '_, '_> {
+    fn spend(&self, nullifier: &[u8; 32]) -> Result<Pubkey> {
+        let a = self.0;
+        let (pda, bump) = derive_nullifier_pda_key(a.pool.identifier, nullifier);
+        require!(pda == a.record.key(), ErrorCode::InvalidNullifierAccount);
+        // a spent nullifier is an account we own
+        require!(
+            a.record.owner != &crate::ID,
+            ErrorCode::NullifierAlreadyUsed
+        );
+
+        let rent = Rent::get()?.minimum_balance(MIN_PDA_SIZE);
+        let seeds: &[&[u8]] = &[
+            b"nullifier",
+            a.pool.identifier.as_ref(),
+            nullifier.as_ref(),
+            &[bump],
+        ];
+        let system = a.system_program.to_account_info();
+
+        // lamports sent to the address beforehand would make create_account fail
+        let top_up = rent.saturating_sub(a.record.lamports());
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system.clone(),
+                    system_program::Transfer {
+                        from: a.payer.clone(),
+                        to: a.record.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                system.clone(),
+                system_program::Allocate {
+                    account_to_allocate: a.record.clone(),
+                },
+                &[seeds],
+            ),
+            MIN_PDA_SIZE as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                system,
+                system_program::Assign {
+                    account_to_assign: a.record.clone(),
+                },
+                &[seeds],
+            ),
+            &crate::ID,
+        )?;
+        Ok(pda)
+    }
+}
+
+/// Records `nullifier` in the store the pool was created with
+pub fn spend_nullifier(accounts: &NullifierAccounts, nullifier: &[u8; 32]) -> Result<()> {
+    let shard = match accounts.pool.nullifier_store {
+        NullifierStoreKind::Sharded => ShardStore(accounts).spend(nullifier),
+        NullifierStoreKind::PerNullifier => PdaStore(accounts).spend(nullifier),
+        NullifierStoreKind::Indexed => IndexedStore(accounts).spend(nullifier),
+    }?;
+    emit!(NullifierSpent {
+        pool: accounts.pool.identifier,
+        shard,
+        nullifier: *nullifier,
+    });
+    Ok(())
+}
+
+pub fn derive_nullifier_pda_key(pool_id: [u8; 16], nullifier: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"nullifier", &pool_id, nullifier], &crate::ID)
+}
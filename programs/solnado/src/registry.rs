@@ -0,0 +1,131 @@
+//! Pool registry. `initialize_variable_pool` lists every new pool in its own entry account
+//! (PDA `["pool_entry", identifier]`) with its creator, asset, display name, creation
+//! slot and index, numbered by the global counter (PDA `["pool_registry"]`). Creating a pool
+//! costs the same however many exist, indexers list the entries by their discriminator.
+//!
+//! A pool can also be created under its creator's namespace: its identifier is then
+//! `namespaced_identifier(creator, name)`, which starts with `NAMESPACE_TAG`. Plain
+//! identifiers can't start with the tag, so no one else can take a creator's names.
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+
+pub const MAX_POOL_NAME: usize = 32;
+/// First byte of every namespaced identifier, never the start of a UTF-8 name
+pub const NAMESPACE_TAG: u8 = 0xff;
+/// Asset of the SOL pools, the wrapped SOL mint
+pub const NATIVE_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+
+#[account]
+#[derive(Copy, Debug, PartialEq, Eq)]
+pub struct PoolEntry {
+    pub identifier: [u8; 16],
+    pub creator: Pubkey,
+    /// Mint of the pool's asset, `NATIVE_MINT` for SOL
+    pub asset: Pubkey,
+    /// UTF-8, zero padded
+    pub name: [u8; MAX_POOL_NAME],
+    pub created_slot: u64,
+    /// Pools listed before this one
+    pub index: u64,
+}
+
+impl PoolEntry {
+    pub const SIZE: usize = 16 + 32 + 32 + MAX_POOL_NAME + 8 + 8;
+
+    pub fn name(&self) -> &str {
+        std::str::from_utf8(&self.name)
+            .unwrap_or_default()
+            .trim_end_matches(char::from(0))
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct PoolRegistry {
+    /// Pools listed so far
+    pub pool_count: u64,
+}
+
+impl PoolRegistry {
+    pub const SIZE: usize = 8;
+}
+
+pub fn derive_pool_registry_pda_key() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"pool_registry"], &crate::ID)
+}
+
+pub fn derive_pool_entry_pda_key(identifier: [u8; 16]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"pool_entry", &identifier], &crate::ID)
+}
+
+/// Identifier of `creator`'s pool named `name`
+pub fn namespaced_identifier(creator: &Pubkey, name: &str) -> [u8; 16] {
+    let hash = hashv(&[b"solnado pool", creator.as_ref(), name.as_bytes()]);
+    let mut identifier = [0u8; 16];
+    identifier[0] = NAMESPACE_TAG;
+    identifier[1..].copy_from_slice(&hash.to_bytes()[..15]);
+    identifier
+}
+
+/// Checks `identifier` against the creator's namespace when `namespaced`, or that it stays
+/// out of the namespaces otherwise, and lists the pool in `entry`
+pub fn register_pool(
+    registry: &mut PoolRegistry,
+    entry: &mut PoolEntry,
+    identifier: [u8; 16],
+    creator: Pubkey,
+    asset: Pubkey,
+    name: &str,
+    namespaced: bool,
+) -> Result<()> {
+    require!(name.len() <= MAX_POOL_NAME, ErrorCode::PoolNameTooLong);
+    if namespaced {
+        require!(
+            identifier == namespaced_identifier(&creator, name),
+            ErrorCode::NamespaceMismatch
+        );
+    } else {
+        require!(
+            identifier[0] != NAMESPACE_TAG,
+            ErrorCode::ReservedPoolIdentifier
+        );
+    }
+
+    let mut padded = [0u8; MAX_POOL_NAME];
+    padded[..name.len()].copy_from_slice(name.as_bytes());
+    *entry = PoolEntry {
+        identifier,
+        creator,
+        asset,
+        name: padded,
+        created_slot: Clock::get()?.slot,
+        index: registry.pool_count,
+    };
+    registry.pool_count += 1;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializePoolRegistry<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PoolRegistry::SIZE,
+        seeds = [ b"pool_registry".as_ref() ],
+        bump
+    )]
+    pub pool_registry: Account<'info, PoolRegistry>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn initialize_pool_registry(ctx: Context<InitializePoolRegistry>) -> Result<()> {
+    ctx.accounts
+        .pool_registry
+        .set_inner(PoolRegistry::default());
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Per-pool rent reserve. Spend fees are paid into it and it alone pays shard rent (new
+//! children, directory growth), so a split never takes lamports backing the notes. A
+//! reserve too short for a split fails the spend, anyone can top it up meanwhile.
+use crate::error::ErrorCode;
+use crate::MerkleMountainRange;
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+
+#[account]
+pub struct RentReserve {} // Dummy, holds lamports only
+
+pub const RENT_RESERVE_SPACE: usize = 8;
+
+pub fn derive_rent_reserve_pda_key(pool_id: [u8; 16]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"rent_reserve", &pool_id], &crate::ID)
+}
+
+/// Lamports the reserve can spend and stay rent exempt
+pub fn available(reserve_ai: &AccountInfo) -> Result<u64> {
+    let floor = Rent::get()?.minimum_balance(reserve_ai.data_len());
+    Ok(reserve_ai.lamports().saturating_sub(floor))
+}
+
+/// Moves `amount` of rent from the pool's reserve to `to`
+pub fn pay_rent<'info>(
+    reserve_ai: &AccountInfo<'info>,
+    pool_id: [u8; 16],
+    to: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    require!(
+        reserve_ai.key() == derive_rent_reserve_pda_key(pool_id).0
+            && reserve_ai.owner == &crate::ID,
+        ErrorCode::InvalidRentReserve
+    );
+    require!(
+        available(reserve_ai)? >= amount,
+        ErrorCode::RentReserveShort
+    );
+    **reserve_ai.try_borrow_mut_lamports()? -= amount;
+    **to.try_borrow_mut_lamports()? += amount;
+    Ok(())
+}
+
+/// Charges a spend fee to a system account signer
+pub fn collect_fee<'info>(
+    payer: &AccountInfo<'info>,
+    reserve: &AccountInfo<'info>,
+    system: &Program<'info, System>,
+    fee: u64,
+) -> Result<()> {
+    system_program::transfer(
+        CpiContext::new(
+            system.to_account_info(),
+            system_program::Transfer {
+                from: payer.clone(),
+                to: reserve.clone(),
+            },
+        ),
+        fee,
+    )
+}
+
+#[derive(Accounts)]
+pub struct FundRentReserve<'info> {
+    #[account(
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    #[account(
+        mut,
+        seeds = [ b"rent_reserve", pool.identifier.as_ref() ],
+        bump
+    )]
+    pub rent_reserve: Account<'info, RentReserve>,
+
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn fund_rent_reserve(ctx: Context<FundRentReserve>, amount: u64) -> Result<()> {
+    collect_fee(
+        &ctx.accounts.funder.to_account_info(),
+        &ctx.accounts.rent_reserve.to_account_info(),
+        &ctx.accounts.system_program,
+        amount,
+    )
+}
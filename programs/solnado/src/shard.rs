@@ -1,20 +1,31 @@
+use crate::directory::*;
 use crate::error::ErrorCode;
+use crate::events::*;
 use crate::id;
+use crate::inbox::*;
+use crate::inputs::*;
 use crate::utils::*;
+use crate::nullifier::*;
+use crate::reserve::*;
+use crate::stealth::*;
 use crate::MerkleMountainRange;
-use crate::TARGET_DEPTH_LARGE;
 use crate::{BATCHES_PER_SMALL_TREE, LEAVES_LENGTH};
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::log::sol_log_compute_units;
-use anchor_lang::solana_program::program::invoke_signed;
-use anchor_lang::solana_program::system_instruction;
-use borsh::{BorshDeserialize, BorshSerialize};
+use std::cell::RefMut;
 //The pool fee covers nullifier storage
 
-pub const SHARD_SIZE: usize = 8;
-pub const SHARD_SPLITTING_THRESHOLD : usize = 512;
+/// Nullifier slots per shard, the account must stay under the 10KiB a CPI can allocate
+pub const SHARD_CAPACITY: usize = 256;
 pub const POOL_FEE: u64 = 300_000;
-pub const PREFIX_LENGTH: usize = 8;
+/// Bytes holding a shard prefix, the nullifier's leading bits packed MSB first
+pub const PREFIX_BYTES: usize = 8;
+/// Deepest a shard trie goes, 2^64 shards is far past any pool's nullifier count
+pub const MAX_PREFIX_BITS: usize = PREFIX_BYTES * 8;
+/// A split where every nullifier takes the same side leaves that child one over the
+/// threshold, this happens at most once per prefix bit
+pub const MAX_SPLIT_THRESHOLD: usize = SHARD_CAPACITY - MAX_PREFIX_BITS;
+/// Used by pools that didn't pick one
+pub const SHARD_SPLITTING_THRESHOLD: usize = MAX_SPLIT_THRESHOLD;
 pub const ON_BEHALF_FEE: u64 = 50_000;
 
 //Fix the borrow mut data (makes program panic)
@@ -29,7 +40,7 @@ pub struct WithdrawVariableShard<'info> {
       )]
     pub pool: Account<'info, MerkleMountainRange>,
 
-    ///CHECK :The nullifier shard
+    ///CHECK: shard or nullifier PDA, checked by the pool's nullifier store
     #[account(mut)]
     pub nullifier_shard: AccountInfo<'info>,
 
@@ -47,6 +58,16 @@ pub struct WithdrawVariableShard<'info> {
     ///CHECK: This can be used by different functions
     #[account(mut)]
     pub dummy1_account: AccountInfo<'info>,
+    ///CHECK: live shard list, checked and updated when a shard splits
+    #[account(mut)]
+    pub shard_directory: AccountInfo<'info>,
+    /// Receives the pool fee, pays for splits
+    #[account(
+        mut,
+        seeds = [b"rent_reserve".as_ref(), pool.identifier.as_ref()],
+        bump
+    )]
+    pub rent_reserve: Account<'info, RentReserve>,
 }
 
 //For combine deposit where we nullify only 1 leaf
@@ -68,9 +89,9 @@ pub struct CombineDepositShardSingle<'info> {
 
     pub system_program: Program<'info, System>,
 
-    /// CHECK: This is always present
+    ///CHECK: shard or nullifier PDA, checked by the pool's nullifier store
     #[account(mut)]
-    pub nullifier_shard: Account<'info, BitShard>,
+    pub nullifier_shard: AccountInfo<'info>,
 
     ///CHECK: This can be used by different functions
     #[account(mut)]
@@ -78,73 +99,75 @@ pub struct CombineDepositShardSingle<'info> {
     ///CHECK: This can be used by different functions
     #[account(mut)]
     pub dummy1_account: AccountInfo<'info>,
+    ///CHECK: live shard list, checked and updated when a shard splits
+    #[account(mut)]
+    pub shard_directory: AccountInfo<'info>,
+    /// Receives the pool fee, pays for splits
+    #[account(
+        mut,
+        seeds = [b"rent_reserve".as_ref(), pool.identifier.as_ref()],
+        bump
+    )]
+    pub rent_reserve: Account<'info, RentReserve>,
 }
 
 //Corresponds to mode 1
 pub fn combine_deposit_shard_single_nullifier<'info>(
     ctx: Context<CombineDepositShardSingle>,
     proof: [u8; 256],
-    public_inputs: [u8; 128],
+    inputs: CombineSingleInputs,
+    notes: Vec<Option<EncryptedNote>>,
 ) -> Result<()> {
     let pool = &ctx.accounts.pool;
     let sysvar = &ctx.accounts.instruction_account;
 
-    //Unpack the nullifier
-    let (n, leaf1, leaf2, r) =
-        verify_one_null_two_leaves(&proof, &public_inputs).map_err(|_| ErrorCode::InvalidProof)?;
-
-    let temp_batch = get_root(&pool.batch_leaves);
-    // sol_log_compute_units();
-    msg!("Temp batch root: {:?}", temp_batch);
-    let temp_root = pool.update_peaks_temp(temp_batch);
-    sol_log_compute_units();
-    msg!("Temp root: {:?}", temp_root);
-    sol_log_compute_units();
+    verify_one_null_two_leaves(&proof, &inputs).map_err(|_| ErrorCode::InvalidProof)?;
+    let CombineSingleInputs { nullifier: n, leaf1, leaf2, root: r } = inputs;
+    publish_notes(&[leaf1, leaf2], &notes)?;
+
     // 2) Check the root against our on‐chain deepened root
-    require!(
-        pool.deepen_temp(temp_root, TARGET_DEPTH_LARGE) == r,
-        ErrorCode::InvalidPublicInputRoot
-    );
+    require!(pool.deep_root == r, ErrorCode::InvalidPublicInputRoot);
 
-        // Collect pool fee for nullifier processing (moved to end to avoid borrowing conflicts)
-        let transfer_ix = anchor_lang::solana_program::system_instruction::transfer(
-            &ctx.accounts.user.key(),
-            &ctx.accounts.pool.key(),
+        // Collect pool fee for nullifier processing, it funds the rent reserve
+        collect_fee(
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.rent_reserve.to_account_info(),
+            &ctx.accounts.system_program,
             POOL_FEE,
-        );
-        
-        anchor_lang::solana_program::program::invoke(
-            &transfer_ix,
-            &[
-                ctx.accounts.user.to_account_info(),
-                ctx.accounts.pool.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
         )?;
-        
-        msg!("Collected {} lamports as pool fee for nullifier processing", POOL_FEE);
+        emit!(FeeCollected {
+            pool: pool.identifier,
+            payer: ctx.accounts.user.key(),
+            recipient: ctx.accounts.rent_reserve.key(),
+            amount: POOL_FEE,
+        });
     
 
-    let shard = &mut ctx.accounts.nullifier_shard;
-    //We take the data from the shard account
-    process_one_nullifier_ai(
-        &ctx.accounts.pool,
-        ctx.bumps.pool,
-        &mut shard.to_account_info(),
-        &ctx.accounts.dummy0_account,
-        &ctx.accounts.dummy1_account,
-        n,
-        &ctx.accounts.user.to_account_info(),
-        &ctx.accounts.system_program,
-        ctx.program_id,
+    let user = ctx.accounts.user.to_account_info();
+    let reserve = ctx.accounts.rent_reserve.to_account_info();
+    let reserve_before = reserve.lamports();
+    spend_nullifier(
+        &NullifierAccounts {
+            pool: &ctx.accounts.pool,
+            record: &ctx.accounts.nullifier_shard,
+            child0: &ctx.accounts.dummy0_account,
+            child1: &ctx.accounts.dummy1_account,
+            directory: &ctx.accounts.shard_directory,
+            reserve: &reserve,
+            instructions: &ctx.accounts.instruction_account,
+            payer: &user,
+            system_program: &ctx.accounts.system_program,
+        },
+        &n,
     )?;
 
     let pool = &mut ctx.accounts.pool;
+    pool.ledger
+        .spend_rent(reserve_before.saturating_sub(reserve.lamports()))?;
 
     for leaf in [leaf1, leaf2].iter() {
         let idx = pool.find_first_match();
-        pool.batch_leaves[idx] = *leaf;
-        pool.merkle_root_batch = get_root(&pool.batch_leaves);
+        pool.insert_leaf(idx, *leaf);
 
         // a) first sub‐batch boundary?
         if idx + 1 == SUB_BATCH_SIZE {
@@ -187,12 +210,7 @@ pub fn combine_deposit_shard_single_nullifier<'info>(
             )?;
 
             // rollover:
-            let batch_root = pool.merkle_root_batch;
-            pool.update_peaks(batch_root);
-            pool.batch_number = pool.batch_number.checked_add(1).unwrap();
-            pool.whole_tree_root = pool.compute_root_from_peaks();
-            pool.batch_leaves = default_leaves();
-            pool.merkle_root_batch = get_root(&pool.batch_leaves);
+            pool.roll_over_batch();
         }
         // c) small‐tree boundary? Post the subtree root of the previuous subtree when first depositing
         else if pool.batch_number % BATCHES_PER_SMALL_TREE == 0 && idx == 0 {
@@ -221,7 +239,7 @@ pub fn combine_deposit_shard_single_nullifier<'info>(
             enforce_small_tree_memo(&sysvar, pool.batch_number - 1, pool.last_small_tree_root)?;
         }
     }
-
+    pool.refresh_deep_root();
 
     Ok(())
 }
@@ -243,9 +261,9 @@ pub struct CombineDepositShardDouble<'info> {
 
     pub system_program: Program<'info, System>,
 
-    /// CHECK: This is always present
+    ///CHECK: shard or nullifier PDA, checked by the pool's nullifier store
     #[account(mut)]
-    pub nullifier_shard1: Box<Account<'info, BitShard>>,
+    pub nullifier_shard1: AccountInfo<'info>,
 
     ///CHECK: For the splitting of the first shard
     #[account(mut)]
@@ -256,7 +274,7 @@ pub struct CombineDepositShardDouble<'info> {
 
     ///CHECK : Second nullfier shard, might be useless if both nullifers are supposed to go on same shard
     #[account(mut)]
-    pub nullifier_shard2: Box<Account<'info, BitShard>>,
+    pub nullifier_shard2: AccountInfo<'info>,
     //this is a temporary adjustement, needs to be solved to allow for the edge case of two shards being full at the same time and being split
     //Currently this blows up the stack size
     /// CHECK: For the splitting of the second shard
@@ -265,82 +283,109 @@ pub struct CombineDepositShardDouble<'info> {
     ///CHECK: For the splitting of the second shard
     #[account(mut)]
     pub dummy21_account: AccountInfo<'info>,
+    ///CHECK: live shard list, checked and updated when a shard splits
+    #[account(mut)]
+    pub shard_directory: AccountInfo<'info>,
+    /// Receives the pool fee, pays for splits
+    #[account(
+        mut,
+        seeds = [b"rent_reserve".as_ref(), pool.identifier.as_ref()],
+        bump
+    )]
+    pub rent_reserve: Account<'info, RentReserve>,
 }
 
 pub fn combine_deposit_shard_double_nullifier<'info>(
     ctx: Context<CombineDepositShardDouble>,
     same_shard: u8,
     proof: [u8; 256],
-    public_inputs: [u8; 128],
+    inputs: CombineDoubleInputs,
+    notes: Vec<Option<EncryptedNote>>,
 ) -> Result<()> {
     let pool = &ctx.accounts.pool;
     let sysvar = &ctx.accounts.instruction_account;
 
 
     // --- two nullifiers → one leaf (old behavior) ---
-    let (n1, n2, leaf, r) =
-        verify_combine_proof(&proof, &public_inputs).map_err(|_| ErrorCode::InvalidProof)?;
-
-    let temp_batch = get_root(&pool.batch_leaves);
-    // sol_log_compute_units();
-    msg!("Temp batch root: {:?}", temp_batch);
-    let temp_root = pool.update_peaks_temp(temp_batch);
-    sol_log_compute_units();
-    msg!("Temp root: {:?}", temp_root);
-    sol_log_compute_units();
+    verify_combine_proof(&proof, &inputs).map_err(|_| ErrorCode::InvalidProof)?;
+    let CombineDoubleInputs { nullifier1: n1, nullifier2: n2, new_leaf: leaf, root: r } = inputs;
+    publish_notes(&[leaf], &notes)?;
+
     // 2) Check the root against our on‐chain deepened root
-    require!(
-        pool.deepen_temp(temp_root, TARGET_DEPTH_LARGE) == r,
-        ErrorCode::InvalidPublicInputRoot
-    );
+    require!(pool.deep_root == r, ErrorCode::InvalidPublicInputRoot);
 
-    let shard1 = &mut ctx.accounts.nullifier_shard1;
-    process_one_nullifier_ai(
-        &ctx.accounts.pool,
-        ctx.bumps.pool,
-        &mut shard1.to_account_info(),
-        &ctx.accounts.dummy10_account,
-        &ctx.accounts.dummy11_account,
-        n1,
+    // Collect the pool fee of both nullifiers first, it funds the rent reserve
+    collect_fee(
         &ctx.accounts.user.to_account_info(),
+        &ctx.accounts.rent_reserve.to_account_info(),
         &ctx.accounts.system_program,
-        ctx.program_id,
+        POOL_FEE * 2,
+    )?;
+    emit!(FeeCollected {
+        pool: pool.identifier,
+        payer: ctx.accounts.user.key(),
+        recipient: ctx.accounts.rent_reserve.key(),
+        amount: POOL_FEE * 2,
+    });
+
+    let user = ctx.accounts.user.to_account_info();
+    let reserve = ctx.accounts.rent_reserve.to_account_info();
+    let reserve_before = reserve.lamports();
+    spend_nullifier(
+        &NullifierAccounts {
+            pool: &ctx.accounts.pool,
+            record: &ctx.accounts.nullifier_shard1,
+            child0: &ctx.accounts.dummy10_account,
+            child1: &ctx.accounts.dummy11_account,
+            directory: &ctx.accounts.shard_directory,
+            reserve: &reserve,
+            instructions: &ctx.accounts.instruction_account,
+            payer: &user,
+            system_program: &ctx.accounts.system_program,
+        },
+        &n1,
     )?;
 
     
 
-    if same_shard == 1 {
-        process_one_nullifier_ai(
-            &ctx.accounts.pool,
-            ctx.bumps.pool,
-            &mut shard1.to_account_info(),
-            &ctx.accounts.dummy10_account,
-            &ctx.accounts.dummy11_account,
-            n2,
-            &ctx.accounts.user.to_account_info(),
-            &ctx.accounts.system_program,
-            ctx.program_id,
+    // both nullifiers may share a shard, a nullifier PDA is always its own
+    if same_shard == 1 && ctx.accounts.pool.nullifier_store == NullifierStoreKind::Sharded {
+        spend_nullifier(
+            &NullifierAccounts {
+                pool: &ctx.accounts.pool,
+                record: &ctx.accounts.nullifier_shard1,
+                child0: &ctx.accounts.dummy10_account,
+                child1: &ctx.accounts.dummy11_account,
+                directory: &ctx.accounts.shard_directory,
+                reserve: &reserve,
+                instructions: &ctx.accounts.instruction_account,
+                payer: &user,
+                system_program: &ctx.accounts.system_program,
+            },
+            &n2,
         )?;
     } else {
-        let shard2 = &mut ctx.accounts.nullifier_shard2;
-        
-        process_one_nullifier_ai(
-            &ctx.accounts.pool,
-            ctx.bumps.pool,
-            &mut shard2.to_account_info(),
-            &ctx.accounts.dummy20_account.to_account_info(),
-            &ctx.accounts.dummy21_account.to_account_info(),
-            n2,
-            &ctx.accounts.user.to_account_info(),
-            &ctx.accounts.system_program,
-            ctx.program_id,
+        spend_nullifier(
+            &NullifierAccounts {
+                pool: &ctx.accounts.pool,
+                record: &ctx.accounts.nullifier_shard2,
+                child0: &ctx.accounts.dummy20_account,
+                child1: &ctx.accounts.dummy21_account,
+                directory: &ctx.accounts.shard_directory,
+                reserve: &reserve,
+                instructions: &ctx.accounts.instruction_account,
+                payer: &user,
+                system_program: &ctx.accounts.system_program,
+            },
+            &n2,
         )?;
     }
 
     let idx = pool.find_first_match() as usize;
     let pool = &mut ctx.accounts.pool;
-    pool.batch_leaves[idx] = leaf;
-    pool.merkle_root_batch = get_root(&pool.batch_leaves);
+    pool.ledger
+        .spend_rent(reserve_before.saturating_sub(reserve.lamports()))?;
+    pool.insert_leaf(idx, leaf);
 
     // a) first sub‐batch boundary?
     if idx + 1 == SUB_BATCH_SIZE {
@@ -377,13 +422,7 @@ pub fn combine_deposit_shard_double_nullifier<'info>(
         )?;
 
         // rollover:
-        let batch_root = pool.merkle_root_batch;
-        pool.update_peaks(batch_root);
-        pool.batch_number = pool.batch_number.checked_add(1).unwrap();
-        pool.whole_tree_root = pool.compute_root_from_peaks();
-
-        pool.batch_leaves = default_leaves();
-        pool.merkle_root_batch = get_root(&pool.batch_leaves);
+        pool.roll_over_batch();
     }
     // c) small‐tree boundary? Post the subtree root of the previuous subtree when first depositing
     else if pool.batch_number % BATCHES_PER_SMALL_TREE == 0 && idx == 0 {
@@ -404,198 +443,275 @@ pub fn combine_deposit_shard_double_nullifier<'info>(
         );
         enforce_small_tree_memo(&sysvar, pool.batch_number - 1, pool.last_small_tree_root)?;
     }
-
-    // Collect pool fees for nullifier processing (moved to end to avoid borrowing conflicts)
-    let fee_amount = if same_shard == 1 { POOL_FEE * 2 } else { POOL_FEE * 2 };
-    
-    let transfer_ix = anchor_lang::solana_program::system_instruction::transfer(
-        &ctx.accounts.user.key(),
-        &ctx.accounts.pool.key(),
-        fee_amount,
-    );
-    
-    anchor_lang::solana_program::program::invoke(
-        &transfer_ix,
-        &[
-            ctx.accounts.user.to_account_info(),
-            ctx.accounts.pool.to_account_info(),
-            ctx.accounts.system_program.to_account_info(),
-        ],
-    )?;
-    
-    msg!("Collected {} lamports as pool fee for {} nullifier processing", fee_amount, if same_shard == 1 { "2 (same shard)" } else { "2 (different shards)" });
+    pool.refresh_deep_root();
 
     Ok(())
 }
 
-pub fn check_prefix(null: &[u8; 32], shard_prefix: &[u8], shard_prefix_length: u8) -> Result<()> {
-    msg!("Checking prefix - length: {}, nullifier: {:?}", shard_prefix_length, null);
-    msg!("Shard prefix: {:?}", &shard_prefix[..shard_prefix_length as usize]);
-    
-    for i in 0..shard_prefix_length as usize {
-        let over: u8 = if null[i] > 127 { 1 } else { 0 };
-        msg!("Position {}: nullifier[{}] = {}, over = {}, shard_prefix[{}] = {}", 
-             i, i, null[i], over, i, shard_prefix[i]);
-        if over != shard_prefix[i] {
-            return err!(ErrorCode::InvalidNullifierBits);
-        }
+/// Trie bit `i` of a nullifier, its `i`th bit counting from the most significant
+pub fn prefix_bit(null: &[u8; 32], i: usize) -> u8 {
+    (null[i / 8] >> (7 - i % 8)) & 1
+}
+
+/// The first `len` bits of a nullifier packed as a shard prefix, later bits zeroed
+pub fn leading_bits(null: &[u8; 32], len: u8) -> [u8; PREFIX_BYTES] {
+    let len = len as usize;
+    let mut prefix = [0u8; PREFIX_BYTES];
+    let full = len / 8;
+    prefix[..full].copy_from_slice(&null[..full]);
+    if len % 8 != 0 {
+        prefix[full] = null[full] & (0xff << (8 - len % 8));
     }
+    prefix
+}
+
+/// Bytes of a packed prefix that carry its `len` bits, the PDA seed
+pub fn prefix_seed(prefix: &[u8], len: u8) -> &[u8] {
+    &prefix[..(len as usize).div_ceil(8)]
+}
+
+pub fn check_prefix(null: &[u8; 32], shard_prefix: &[u8], shard_prefix_length: u8) -> Result<()> {
+    let expected = leading_bits(null, shard_prefix_length);
+    require!(
+        prefix_seed(&expected, shard_prefix_length)
+            == prefix_seed(shard_prefix, shard_prefix_length),
+        ErrorCode::InvalidNullifierBits
+    );
 
     Ok(())
 }
 
-fn process_one_nullifier_ai<'info>(
-    pool: &Account<'info, MerkleMountainRange>,
-    pool_bump: u8,
-    shard_ai: &mut AccountInfo<'info>,
-    child0_ai: &AccountInfo<'info>,
-    child1_ai: &AccountInfo<'info>,
+pub(crate) fn process_one_nullifier_ai(
+    accounts: &NullifierAccounts,
     null_be: [u8; 32],
-    user_ai: &AccountInfo<'info>,
-    system_program: &Program<'info, System>,
-    program_id: &Pubkey,
-) -> Result<()> {
-    // 1) Slurp the account bytes into a local Vec and then drop the borrow immediately
+) -> Result<Pubkey> {
+    let NullifierAccounts {
+        pool,
+        record: shard_ai,
+        child0: child0_ai,
+        child1: child1_ai,
+        directory: directory_ai,
+        reserve: reserve_ai,
+        system_program,
+        ..
+    } = *accounts;
+    if shard_ai.data_is_empty() {
+        // a live shard `close_pool` reclaimed while empty is created again, at the
+        // reserve's expense
+        if let Some(listed) = listed_shard(directory_ai, pool.identifier, &null_be)? {
+            if listed.pda(pool.identifier) == shard_ai.key() {
+                create_shard_account(
+                    shard_ai,
+                    reserve_ai,
+                    &pool.identifier,
+                    &listed.prefix,
+                    listed.len,
+                    system_program,
+                    &crate::ID,
+                )?;
+                load_shard_mut(shard_ai)?.insert(&null_be)?;
+                return Ok(shard_ai.key());
+            }
+        }
 
-    let raw_body = {
-        let data = shard_ai.data.borrow();
-        let v = data[8..].to_vec();
-        drop(data);
-        v
-    };
+        // otherwise it was split earlier in this instruction (two nullifiers on one
+        // shard), the nullifier then goes to the child its next bit picks
+        let child_ai = {
+            let child0 = load_shard_mut(child0_ai)?;
+            let parent_len = child0.prefix_len.saturating_sub(1) as usize;
+            if prefix_bit(&null_be, parent_len) == 1 {
+                child1_ai
+            } else {
+                child0_ai
+            }
+        };
+        let mut child = load_shard_mut(child_ai)?;
+        check_shard(pool, child_ai, &child, &null_be)?;
+        child.insert(&null_be)?;
+        return Ok(child_ai.key());
+    }
 
-    // now no active borrow on shard_ai.data
+    {
+        let mut shard = load_shard_mut(shard_ai)?;
+        check_shard(pool, shard_ai, &shard, &null_be)?;
 
-    // 2) Deserialize
-    let mut shard = BitShard::deserialize(&mut raw_body.as_slice())
-        .map_err(|_| ErrorCode::InvalidNullifierList)?;
+        if !shard.must_split(pool.split_threshold()) {
+            shard.insert(&null_be)?;
+            return Ok(shard_ai.key());
+        }
+    }
 
-    //Check that the crrect shard is being used
-    check_prefix(&null_be, &shard.prefix, shard.prefix_len)?;
+    split_shard_and_insert(accounts, &null_be)
+}
 
-    // 4) PDA check
+/// The nullifier belongs to `shard`, and `shard_ai` is that shard's PDA
+fn check_shard(
+    pool: &MerkleMountainRange,
+    shard_ai: &AccountInfo,
+    shard: &BitShard,
+    null_be: &[u8; 32],
+) -> Result<()> {
+    //Check that the crrect shard is being used
+    check_prefix(null_be, &shard.prefix, shard.prefix_len)?;
     let (expected_pda, _) = derive_shard_pda_key(pool.identifier, &shard.prefix, shard.prefix_len);
     require!(
         &expected_pda == shard_ai.key,
         ErrorCode::InvalidShardSelection
     );
+    Ok(())
+}
 
-    // 5) Insert or split
-    if shard.nullifiers.len() < SHARD_SPLITTING_THRESHOLD {
-        // insert into in‐memory struct
-        let pos = match shard.nullifiers.binary_search(&null_be) {
+/// Shard header followed by a fixed slot array, read and written in place.
+/// The first `count` slots hold the nullifiers, sorted.
+#[account(zero_copy)]
+pub struct BitShard {
+    pub count: u32,
+    /// how many bits of the nullifier we’ve consumed so far
+    pub prefix_len: u8,
+    /// those bits packed MSB first, the rest zero
+    pub prefix: [u8; PREFIX_BYTES],
+    pub _padding: [u8; 3],
+    pub slots: [[u8; 32]; SHARD_CAPACITY],
+}
+
+impl BitShard {
+    pub fn nullifiers(&self) -> &[[u8; 32]] {
+        &self.slots[..self.count as usize]
+    }
+
+    /// Binary search then shift the tail by one slot, no (de)serialization
+    pub fn insert(&mut self, nullifier: &[u8; 32]) -> Result<()> {
+        let len = self.count as usize;
+        let pos = match self.nullifiers().binary_search(nullifier) {
             Ok(_) => return err!(ErrorCode::NullifierAlreadyUsed),
             Err(p) => p,
         };
-        shard.nullifiers.insert(pos, null_be);
-        shard.count += 1;
-
-        // maybe grow the account
-        let info = shard_ai.to_account_info();
-        maybe_grow_shard_account(&info, &shard, &pool.to_account_info())?;
-        // now *one* mutable borrow to write back
-        {
-            let mut d = shard_ai.data.borrow_mut();
-            let buf = shard.try_to_vec()?;
-            d[8..8 + buf.len()].copy_from_slice(&buf);
-        }
-        
-    } else {
-        msg!("Splitting shard");
-        split_shard_and_insert(
-            shard_ai,
-            child0_ai,
-            child1_ai,
-            &pool.to_account_info(),
-            pool_bump,
-            &null_be,
-            &pool.identifier,
-            user_ai,
-            system_program,
-            program_id,
-        )?;
+        require!(len < SHARD_CAPACITY, ErrorCode::ShardFull);
+        self.slots.copy_within(pos..len, pos + 1);
+        self.slots[pos] = *nullifier;
+        self.count += 1;
+        Ok(())
     }
-    msg!("End of process_nullifier_ai ");
 
-    Ok(())
-}
+    /// Empties the shard and gives it a new prefix
+    pub fn reset(&mut self, prefix_len: u8, prefix: [u8; PREFIX_BYTES]) {
+        self.count = 0;
+        self.prefix_len = prefix_len;
+        self.prefix = prefix;
+        self.slots.fill([0u8; 32]);
+    }
 
-#[account]
-pub struct BitShard {
-    /// how many bits of the nullifier we’ve consumed so far
-    pub prefix_len: u8,
-    /// those bits, left‐aligned in this 32‐byte array
-    pub prefix: [u8; PREFIX_LENGTH],
+    /// Appends past the last slot, callers keep the order
+    fn push(&mut self, nullifier: &[u8; 32]) {
+        self.slots[self.count as usize] = *nullifier;
+        self.count += 1;
+    }
 
-    pub count: u32,
-    /// sorted list of nullifier hashes
-    pub nullifiers: Vec<[u8; 32]>,
+    /// Whether the next insertion splits the shard first. Shards at the deepest
+    /// prefix can't split, they fill up to capacity.
+    pub fn must_split(&self, threshold: usize) -> bool {
+        self.count as usize >= threshold && (self.prefix_len as usize) < MAX_PREFIX_BITS
+    }
+
+    /// The bit a split of this shard sorts `nullifier` on
+    pub fn next_bit(&self, nullifier: &[u8; 32]) -> u8 {
+        prefix_bit(nullifier, self.prefix_len as usize)
+    }
+
+    /// The shard's directory entry
+    pub fn key(&self) -> ShardPrefix {
+        ShardPrefix::new(self.prefix_len, self.prefix)
+    }
+
+    /// Prefix of the child on side `bit`
+    pub fn child_prefix(&self, bit: u8) -> [u8; PREFIX_BYTES] {
+        self.key().children()[bit as usize].prefix
+    }
+
+    /// Resets both children to the halves of this shard's range and moves every
+    /// nullifier to its side, however uneven. Sorted order carries over.
+    pub fn split_into(&self, child0: &mut BitShard, child1: &mut BitShard) {
+        child0.reset(self.prefix_len + 1, self.child_prefix(0));
+        child1.reset(self.prefix_len + 1, self.child_prefix(1));
+        for nf in self.nullifiers() {
+            if self.next_bit(nf) == 1 {
+                child1.push(nf);
+            } else {
+                child0.push(nf);
+            }
+        }
+    }
+}
+
+/// Borrows a shard account's data as a `BitShard`, checking owner and discriminator
+pub fn load_shard_mut<'a>(shard_ai: &'a AccountInfo) -> Result<RefMut<'a, BitShard>> {
+    require!(
+        shard_ai.owner == &crate::ID,
+        ErrorCode::InvalidShardSelection
+    );
+    let data = shard_ai.try_borrow_mut_data()?;
+    require!(
+        data.len() >= SHARD_SPACE && data[..8] == *BitShard::DISCRIMINATOR,
+        ErrorCode::InvalidNullifierList
+    );
+    Ok(RefMut::map(data, |d| {
+        bytemuck::from_bytes_mut(&mut d[8..SHARD_SPACE])
+    }))
 }
 
 pub fn withdraw_variable_shard_nullifier(
     ctx: Context<WithdrawVariableShard>,
     mode: u8,
     proof: [u8; 256],
-    public_inputs: [u8; 136],
+    inputs: WithdrawInputs,
+    notes: Vec<Option<EncryptedNote>>,
 ) -> Result<()> {
-    let public_inputs_slice = public_inputs.as_slice();
     let sysvar = &ctx.accounts.instruction_account;
 
-    let (secret_be, null_be, root_be, new_leaf) = match mode {
+    let new_leaf = match mode {
         0 => {
             //withdraw only
-            let (val_be, null_be, root_be) = verify_withdraw_proof(&proof, public_inputs_slice)
-                .map_err(|_| ErrorCode::InvalidProof)?;
-            (val_be, null_be, root_be, None)
+            verify_withdraw_proof(&proof, &inputs)?;
+            None
         }
         1 => {
             //Withdraw and add a leaf
-            let (val_be, null_be, root_be, new_leaf) =
-                verify_withdraw_and_add_proof(&proof, public_inputs_slice)
-                    .map_err(|_| ErrorCode::InvalidProof)?;
-            (val_be, null_be, root_be, Some(new_leaf))
+            verify_withdraw_and_add_proof(&proof, &inputs)?;
+            Some(inputs.new_leaf)
         }
         _ => return Err(ErrorCode::InvalidArgument.into()),
     };
+    publish_notes(new_leaf.as_slice(), &notes)?;
+    let WithdrawInputs { nullifier: null_be, root: root_be, amount, .. } = inputs;
 
-    let amount = u64::from_be_bytes(secret_be);
-    msg!("Amount: {}", amount);
     let pool = &ctx.accounts.pool;
 
-    // sol_log_compute_units();
-    // msg!("Temp batch: {:?}", &pool.batch_leaves);
-    let temp_batch = get_root(&pool.batch_leaves);
-    // sol_log_compute_units();
-    msg!("Temp batch root: {:?}", temp_batch);
-    let temp_root = pool.update_peaks_temp(temp_batch);
-    sol_log_compute_units();
-    msg!("Temp root: {:?}", temp_root);
-    sol_log_compute_units();
     // 2) Check the root against our on‐chain deepened root
-    require!(
-        pool.deepen_temp(temp_root, TARGET_DEPTH_LARGE) == root_be,
-        ErrorCode::InvalidPublicInputRoot
-    );
-    // let shard = &mut ctx.accounts.nullifier_shard;
-    sol_log_compute_units();
-    process_one_nullifier_ai(
-        &ctx.accounts.pool,
-        ctx.bumps.pool,
-        &mut ctx.accounts.nullifier_shard.to_account_info(),
-        &ctx.accounts.dummy0_account,
-        &ctx.accounts.dummy1_account,
-        null_be,
-        &ctx.accounts.user.to_account_info(),
-        &ctx.accounts.system_program,
-        &id(),
+    require!(pool.deep_root == root_be, ErrorCode::InvalidPublicInputRoot);
+    let user = ctx.accounts.user.to_account_info();
+    let reserve = ctx.accounts.rent_reserve.to_account_info();
+    let reserve_before = reserve.lamports();
+    spend_nullifier(
+        &NullifierAccounts {
+            pool: &ctx.accounts.pool,
+            record: &ctx.accounts.nullifier_shard,
+            child0: &ctx.accounts.dummy0_account,
+            child1: &ctx.accounts.dummy1_account,
+            directory: &ctx.accounts.shard_directory,
+            reserve: &reserve,
+            instructions: &ctx.accounts.instruction_account,
+            payer: &user,
+            system_program: &ctx.accounts.system_program,
+        },
+        &null_be,
     )?;
+    let rent = reserve_before.saturating_sub(reserve.lamports());
 
     //Add to the batch
     if mode == 1 {
         let idx = pool.find_first_match() as usize;
         let pool = &mut ctx.accounts.pool;
-        pool.batch_leaves[idx] = new_leaf.unwrap();
-        pool.merkle_root_batch = get_root(&pool.batch_leaves);
+        pool.insert_leaf(idx, new_leaf.unwrap());
 
         // a) first sub‐batch boundary?
         if idx + 1 == SUB_BATCH_SIZE {
@@ -632,10 +748,7 @@ pub fn withdraw_variable_shard_nullifier(
             )?;
 
             // rollover:
-            let batch_root = pool.merkle_root_batch;
-            pool.update_peaks(batch_root);
-            pool.batch_number = pool.batch_number.checked_add(1).unwrap();
-            pool.whole_tree_root = pool.compute_root_from_peaks();
+            pool.roll_over_batch();
         }
         // c) small‐tree boundary? Post the subtree root of the previuous subtree when first depositing
         else if pool.batch_number % BATCHES_PER_SMALL_TREE == 0 && idx == 0 {
@@ -656,239 +769,197 @@ pub fn withdraw_variable_shard_nullifier(
             );
             enforce_small_tree_memo(&sysvar, pool.batch_number - 1, pool.last_small_tree_root)?;
         }
+        pool.refresh_deep_root();
     }
 
-    let net_amount = amount.checked_sub(POOL_FEE).unwrap();
+    let net_amount = amount
+        .checked_sub(POOL_FEE)
+        .ok_or(ErrorCode::InvalidArgument)?;
+    require!(
+        ctx.accounts.pool.to_account_info().lamports() >= amount,
+        ErrorCode::InsufficientFunds
+    );
 
     **ctx
         .accounts
         .pool
         .to_account_info()
-        .try_borrow_mut_lamports()? -= net_amount;
+        .try_borrow_mut_lamports()? -= amount;
 
     **ctx
         .accounts
         .user
         .to_account_info()
         .try_borrow_mut_lamports()? += net_amount;
+    // the fee leaves the notes' lamports for the rent reserve
+    **reserve.try_borrow_mut_lamports()? += POOL_FEE;
+    let ledger = &mut ctx.accounts.pool.ledger;
+    ledger.withdraw(net_amount, POOL_FEE)?;
+    ledger.spend_rent(rent)?;
 
-    msg!(
-        "Withdrew {} total; {} for nullifier storage, {} net to user",
-        amount,
-        POOL_FEE,
-        net_amount
-    );
+    let pool = &ctx.accounts.pool;
+    emit!(Withdrawal {
+        pool: pool.identifier,
+        recipient: ctx.accounts.user.key(),
+        amount: net_amount,
+        fees: POOL_FEE,
+    });
+    emit!(FeeCollected {
+        pool: pool.identifier,
+        payer: pool.key(),
+        recipient: ctx.accounts.rent_reserve.key(),
+        amount: POOL_FEE,
+    });
     Ok(())
 }
 
 pub fn derive_shard_pda_key(pool_id: [u8; 16], prefix_bits: &[u8], prefix_len: u8) -> (Pubkey, u8) {
-    // how many bytes we need from prefix_bits?
-
     Pubkey::find_program_address(
         &[
             b"nullifier_shard",
             &pool_id,
-            &[prefix_len as u8],
-            &prefix_bits[..prefix_len as usize],
+            &[prefix_len],
+            prefix_seed(prefix_bits, prefix_len),
         ],
         &id(),
     )
 }
 
-//This will be useful when the shard accounts need to grow, not useful at the moment
-pub fn maybe_grow_shard_account<'info>(
-    shard_ai: &AccountInfo<'info>, // the shard PDA
-    shard: &BitShard,              // in‐memory struct
-    pool_ai: &AccountInfo<'info>,  // the pool PDA
-                                   // you need the pool bump to sign
-) -> Result<()> {
-    // figure out serialized size…
-
-    let data = shard
-        .try_to_vec()
-        .map_err(|_| ErrorCode::InvalidNullifierList)?;
-    let new_len = 8 + 32 + data.len();
-    msg!("Data length: {}", new_len);
-    let info = shard_ai.clone();
-
-    if new_len > info.data_len() {
-        // re‐alloc the shard account buffer
-        info.realloc(new_len, false)?;
-        msg!("Growing shard account!");
-        // compute how much extra rent we need
-        let rent = Rent::get()?;
-        let required = rent.minimum_balance(new_len);
-        let current = info.lamports();
-        let delta = required.saturating_sub(current);
-
-        if delta > 0 {
-            **pool_ai.try_borrow_mut_lamports()? -= delta;
-            **info.try_borrow_mut_lamports()? += delta;
-            msg!("Manually moved {} lamports from pool → shard", delta);
-        }
-    }
-
-    Ok(())
-}
-pub fn split_shard_and_insert<'info>(
-    old_ai: &AccountInfo<'info>,
-    child0_ai: &AccountInfo<'info>,
-    child1_ai: &AccountInfo<'info>,
-    pool_ai: &AccountInfo<'info>,
-    pool_bump: u8,
-    new_nullifier: &[u8; 32],
+/// Creates an empty shard PDA funded by the pool's rent reserve
+fn create_shard_account<'info>(
+    shard_ai: &AccountInfo<'info>,
+    reserve_ai: &AccountInfo<'info>,
     pool_id: &[u8; 16],
-    authority: &AccountInfo<'info>,
+    prefix: &[u8; PREFIX_BYTES],
+    prefix_len: u8,
     system_program: &Program<'info, System>,
     program_id: &Pubkey,
 ) -> Result<()> {
-    // 1) Load & deserialize the old shard
-    let shard = {
-        let data = old_ai.data.borrow();
-        let mut rdr: &[u8] = &data[8..];
-        BitShard::deserialize(&mut rdr).map_err(|_| ErrorCode::InvalidNullifierList)?
-    };
-    // must be full
-    assert_eq!(shard.nullifiers.len(), SHARD_SIZE);
-
-    // 2) New prefix length and split buffer for left/right
-    let new_prefix_len = shard.prefix_len + 1;
-    let mut left = Vec::with_capacity(SHARD_SIZE / 2);
-    let mut right = Vec::with_capacity(SHARD_SIZE / 2);
-
-    // check_prefix(nf, &shard.prefix, shard.prefix_len)?;
-    // for each existing nullifier, test its next bit
-    for nf in shard.nullifiers.iter() {
-        // this will err if the prefix bits don't match at all
-        // now check the *next* bit (i.e. at index = old prefix_len)
-        let bit = if nf[shard.prefix_len as usize] > 127 {
-            1
-        } else {
-            0
-        };
-        if bit == 0 {
-            left.push(*nf);
-        } else {
-            right.push(*nf);
-        }
-    }
-    // sanity
-    assert_eq!(left.len() + right.len(), SHARD_SIZE);
-
-    // 3) build the two new BitShard structs
-    let bs0 = BitShard {
-        prefix_len: new_prefix_len,
-        prefix: shard.prefix,
-        nullifiers: left,
-        count: (SHARD_SIZE / 2) as u32,
-    };
-    let mut bs1 = BitShard {
-        prefix_len: new_prefix_len,
-        prefix: shard.prefix,
-        nullifiers: right,
-        count: (SHARD_SIZE / 2) as u32,
-    };
-    // flip the new bit in bs1.prefix
-    {
-        bs1.prefix[(bs1.prefix_len - 1) as usize] = 1;
-    }
+    let (pda, bump) = derive_shard_pda_key(*pool_id, prefix, prefix_len);
+    require!(pda == shard_ai.key(), ErrorCode::InvalidShardSelection);
+
+    // the reserve holds data so it can't be a system transfer source, let the shard PDA
+    // allocate and assign itself, then move lamports directly. Rent exemption is only
+    // checked once the instruction ends, and no CPI sees an unbalanced account this way
+    let rent = Rent::get()?.minimum_balance(SHARD_SPACE);
+    let delta = rent.saturating_sub(shard_ai.lamports());
+
+    let seeds: &[&[u8]] = &[
+        b"nullifier_shard",
+        pool_id,
+        &[prefix_len],
+        prefix_seed(prefix, prefix_len),
+        &[bump],
+    ];
+    let system = system_program.to_account_info();
+    anchor_lang::system_program::allocate(
+        CpiContext::new_with_signer(
+            system.clone(),
+            anchor_lang::system_program::Allocate {
+                account_to_allocate: shard_ai.clone(),
+            },
+            &[seeds],
+        ),
+        SHARD_SPACE as u64,
+    )?;
+    anchor_lang::system_program::assign(
+        CpiContext::new_with_signer(
+            system,
+            anchor_lang::system_program::Assign {
+                account_to_assign: shard_ai.clone(),
+            },
+            &[seeds],
+        ),
+        program_id,
+    )?;
+    pay_rent(reserve_ai, *pool_id, shard_ai, delta)?;
 
-    // helper to create or realloc & write a child shard
-    let write_child = |child_ai: &AccountInfo<'info>, bs: &BitShard| -> Result<()> {
-        let body = bs
-            .try_to_vec()
-            .map_err(|_| ErrorCode::InvalidNullifierList)?;
-        let space = 8 + body.len();
-        let rent = Rent::get()?;
-        let min_bal = rent.minimum_balance(space);
-
-        // derive and check PDA
-        let (pda, _) = derive_shard_pda_key(
-            *pool_id,
-            &bs.prefix[..new_prefix_len as usize],
-            new_prefix_len,
-        );
-        require!(pda == child_ai.key(), ErrorCode::InvalidShardSelection);
-
-        // create if needed
-        if child_ai.lamports() < min_bal {
-            // transfer lamports out of the pool PDA into the child PDA:
-            let ix = system_instruction::create_account(
-                &pool_ai.key(), // **pool** pays
-                &pda,           // child PDA
-                min_bal,        // funding amount
-                space as u64,   // bytes of data
-                program_id,     // owned by your program
-            );
-            // now invoke with the pool PDA signing:
-            invoke_signed(
-                &ix,
-                &[
-                    pool_ai.clone(),  // must be writable & signer
-                    child_ai.clone(), // child PDA
-                    system_program.to_account_info(),
-                ],
-                &[&[
-                    b"variable_pool", // your seed tag
-                    pool_id,          // the 16-byte identifier
-                    &[pool_bump],     // bump
-                ]],
-            )?;
-        }
+    let mut data = shard_ai.try_borrow_mut_data()?;
+    data[..8].copy_from_slice(BitShard::DISCRIMINATOR);
+    let shard: &mut BitShard = bytemuck::from_bytes_mut(&mut data[8..SHARD_SPACE]);
+    shard.reset(prefix_len, *prefix);
+    Ok(())
+}
 
-        // write discriminator + body
-        let mut d = child_ai.data.borrow_mut();
-        d[..8].copy_from_slice(&BitShard::DISCRIMINATOR);
-        d[8..8 + body.len()].copy_from_slice(&body);
-        Ok(())
+/// Replaces the full shard `accounts.record` by its two children and records
+/// `new_nullifier` in the one it belongs to
+pub fn split_shard_and_insert(
+    accounts: &NullifierAccounts,
+    new_nullifier: &[u8; 32],
+) -> Result<Pubkey> {
+    let NullifierAccounts {
+        record: old_ai,
+        child0: child0_ai,
+        child1: child1_ai,
+        directory: directory_ai,
+        reserve: reserve_ai,
+        system_program,
+        ..
+    } = *accounts;
+    let pool_id = &accounts.pool.identifier;
+    let parent = {
+        let shard = load_shard_mut(old_ai)?;
+        require!(
+            (shard.prefix_len as usize) < MAX_PREFIX_BITS,
+            ErrorCode::ShardFull
+        );
+        shard.key()
     };
 
-    // 4) write both children
-    write_child(child0_ai, &bs0)?;
-    write_child(child1_ai, &bs1)?;
-
-    // 5) insert the NEW nullifier into the correct child
-    let next_bit = if new_nullifier[shard.prefix_len as usize] > 127 {
-        1
-    } else {
-        0
-    };
-    let target = if next_bit == 0 { child0_ai } else { child1_ai };
-    {
-        let mut buf = target.data.borrow_mut();
-        let mut rdr: &[u8] = &buf[8..];
-        let mut child: BitShard =
-            BitShard::deserialize(&mut rdr).map_err(|_| ErrorCode::InvalidNullifierList)?;
-        match child.nullifiers.binary_search(new_nullifier) {
-            Ok(_) => return err!(ErrorCode::NullifierAlreadyUsed),
-            Err(pos) => child.nullifiers.insert(pos, *new_nullifier),
-        }
-        // write back updated list
-        let body = child
-            .try_to_vec()
-            .map_err(|_| ErrorCode::InvalidNullifierList)?;
-        buf[..8].copy_from_slice(&BitShard::DISCRIMINATOR);
-        buf[8..8 + body.len()].copy_from_slice(&body);
+    // 1) the parent's rent goes back to the reserve first, it then pays for both
+    // children, one more prefix bit each, and lists them
+    let bal = old_ai.lamports();
+    **reserve_ai.try_borrow_mut_lamports()? += bal;
+    **old_ai.try_borrow_mut_lamports()? = 0;
+    msg!("Reclaimed {} lamports from the split shard", bal);
+    for (child_ai, child) in [child0_ai, child1_ai].into_iter().zip(parent.children()) {
+        create_shard_account(
+            child_ai,
+            reserve_ai,
+            pool_id,
+            &child.prefix,
+            child.len,
+            system_program,
+            &crate::ID,
+        )?;
     }
-
-    // 6) close (zero‐realloc + reclaim) the old shard
-    {
-        // shrink to zero
-        old_ai.realloc(0, false)?;
-        let bal = old_ai.lamports();
-        if bal > 0 {
-            **pool_ai.try_borrow_mut_lamports()? += bal;
-            **old_ai.try_borrow_mut_lamports()? = 0;
-            msg!("Closed old shard PDA, reclaimed {} lamports", bal);
+    record_split(directory_ai, reserve_ai, *pool_id, &parent)?;
+
+    // 2) move every nullifier to its side, then insert the new one
+    let recorded_in = {
+        let old = load_shard_mut(old_ai)?;
+        let mut child0 = load_shard_mut(child0_ai)?;
+        let mut child1 = load_shard_mut(child1_ai)?;
+        old.split_into(&mut child0, &mut child1);
+        emit!(ShardSplit {
+            pool: *pool_id,
+            shard: old_ai.key(),
+            child0: child0_ai.key(),
+            child1: child1_ai.key(),
+            count0: child0.count,
+            count1: child1.count,
+        });
+
+        // 3) insert the new nullifier into the correct child
+        if old.next_bit(new_nullifier) == 1 {
+            child1.insert(new_nullifier)?;
+            child1_ai.key()
+        } else {
+            child0.insert(new_nullifier)?;
+            child0_ai.key()
         }
-    }
+    };
 
-    Ok(())
+    // 4) close the old shard, its lamports went to the reserve
+    old_ai.resize(0)?;
+
+    Ok(recorded_in)
 }
 
-pub const SHARD_SPACE: usize = 8 + 1 + 32 + 4 + 32 * SHARD_SIZE;
+pub const SHARD_SPACE: usize = 8 + std::mem::size_of::<BitShard>();
 
 #[derive(Accounts)]
+#[instruction(split_threshold: u16)]
 pub struct InitializeNullifierShards<'info> {
     /// Your pool PDA (so we can seed the shards off it)
     #[account(
@@ -907,17 +978,27 @@ pub struct InitializeNullifierShards<'info> {
         seeds = [ b"nullifier_shard", pool.identifier.as_ref(), &[1_u8], &[0_u8] ],
         bump
     )]
-    pub shard0: Account<'info, BitShard>,
+    pub shard0: AccountLoader<'info, BitShard>,
 
     /// shard for bit=1 at prefix_len=1
     #[account(
         init,
         payer = authority,
         space = SHARD_SPACE,
-        seeds = [ b"nullifier_shard", pool.identifier.as_ref(), &[1_u8], &[1_u8] ],
+        seeds = [ b"nullifier_shard", pool.identifier.as_ref(), &[1_u8], &[0x80_u8] ],
+        bump
+    )]
+    pub shard1: AccountLoader<'info, BitShard>,
+
+    /// lists shard0 and shard1, then every split
+    #[account(
+        init,
+        payer = authority,
+        space = DIRECTORY_INIT_SPACE,
+        seeds = [ b"shard_directory", pool.identifier.as_ref() ],
         bump
     )]
-    pub shard1: Account<'info, BitShard>,
+    pub shard_directory: AccountLoader<'info, ShardDirectory>,
 
     #[account(mut)]
     pub authority: Signer<'info>,
@@ -925,60 +1006,45 @@ pub struct InitializeNullifierShards<'info> {
     pub system_program: Program<'info, System>,
 }
 
-pub fn initialize_nullifier_shards(ctx: Context<InitializeNullifierShards>) -> Result<()> {
+pub fn initialize_nullifier_shards(
+    ctx: Context<InitializeNullifierShards>,
+    split_threshold: u16,
+) -> Result<()> {
+    // set once, split shards are closed and must never be recreated from here
+    let pool = &mut ctx.accounts.pool;
+    require!(
+        pool.shard_split_threshold == 0,
+        ErrorCode::ShardsAlreadyInitialized
+    );
+    require!(
+        pool.nullifier_store == NullifierStoreKind::Sharded,
+        ErrorCode::InvalidMode
+    );
+    require!(
+        (1..=MAX_SPLIT_THRESHOLD).contains(&(split_threshold as usize)),
+        ErrorCode::InvalidSplitThreshold
+    );
+    pool.shard_split_threshold = split_threshold;
+
     // shard0: prefix_len=1, prefix bit=0
-    let s0 = &mut ctx.accounts.shard0;
-    s0.prefix_len = 1;
-    s0.prefix = [0u8; PREFIX_LENGTH]; // all bits zero
-    s0.nullifiers = Vec::new();
-    s0.count = 0;
+    ctx.accounts
+        .shard0
+        .load_init()?
+        .reset(1, [0u8; PREFIX_BYTES]);
 
     // shard1: prefix_len=1, prefix bit=1
-    let s1 = &mut ctx.accounts.shard1;
-    s1.prefix_len = 1;
-    let mut p1 = [0u8; PREFIX_LENGTH];
-    p1[0] = 1; // set the high bit
-    s1.prefix = p1;
-    s1.nullifiers = Vec::new();
-    s1.count = 0;
-
-    Ok(())
-}
-
-#[derive(Accounts)]
-pub struct ResetNullifierShards<'info> {
-    #[account(
-        mut,
-        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
-        bump
-    )]
-    pub pool: Account<'info, MerkleMountainRange>,
-
-    /// these must already exist and have the correct discriminator
-    #[account(mut)]
-    pub shard0: Account<'info, BitShard>,
-    #[account(mut)]
-    pub shard1: Account<'info, BitShard>,
-
-    pub authority: Signer<'info>,
-}
-
-pub fn reset_nullifier_shards(ctx: Context<ResetNullifierShards>) -> Result<()> {
-    // zero out shard0
-    let s0 = &mut ctx.accounts.shard0;
-    s0.prefix_len = 1;
-    s0.prefix = [0u8; PREFIX_LENGTH];
-    s0.count = 0;
-    s0.nullifiers.clear();
-
-    // zero out shard1
-    let s1 = &mut ctx.accounts.shard1;
-    s1.prefix_len = 1;
-    let mut p1 = [0u8; PREFIX_LENGTH];
-    p1[0] = 1;
-    s1.prefix = p1;
-    s1.count = 0;
-    s1.nullifiers.clear();
+    let mut p1 = [0u8; PREFIX_BYTES];
+    p1[0] = 0x80; // set the high bit
+    ctx.accounts.shard1.load_init()?.reset(1, p1);
+
+    ctx.accounts.shard_directory.load_init()?.count = 2;
+    let directory_ai = ctx.accounts.shard_directory.to_account_info();
+    let mut data = directory_ai.try_borrow_mut_data()?;
+    let entries = [
+        ShardPrefix::new(1, [0u8; PREFIX_BYTES]),
+        ShardPrefix::new(1, p1),
+    ];
+    data[DIRECTORY_HEADER..].copy_from_slice(bytemuck::cast_slice(&entries));
 
     Ok(())
 }
@@ -993,7 +1059,7 @@ pub struct WithdrawOnBehalfShard<'info> {
     )]
     pub pool: Account<'info, MerkleMountainRange>,
 
-    ///CHECK: The nullifier shard
+    ///CHECK: shard or nullifier PDA, checked by the pool's nullifier store
     #[account(mut)]
     pub nullifier_shard: AccountInfo<'info>,
 
@@ -1002,6 +1068,7 @@ pub struct WithdrawOnBehalfShard<'info> {
     pub withdrawer: AccountInfo<'info>,
 
     /// The transaction fee‐payer (must sign)
+    #[account(mut)]
     pub payer: Signer<'info>,
 
     ///CHECK: SYSVAR_INSTRUCTIONS must be passed to read the Memo
@@ -1015,51 +1082,56 @@ pub struct WithdrawOnBehalfShard<'info> {
     ///CHECK: This can be used by different functions
     #[account(mut)]
     pub dummy1_account: AccountInfo<'info>,
+    ///CHECK: live shard list, checked and updated when a shard splits
+    #[account(mut)]
+    pub shard_directory: AccountInfo<'info>,
+    /// Receives the pool fee, pays for splits
+    #[account(
+        mut,
+        seeds = [b"rent_reserve".as_ref(), pool.identifier.as_ref()],
+        bump
+    )]
+    pub rent_reserve: Account<'info, RentReserve>,
 }
 
 pub fn withdraw_on_behalf_with_shard(
     ctx: Context<WithdrawOnBehalfShard>,
     proof: [u8; 256],
-    public_inputs: [u8; 104], // nullifier(32)||amount(8)||root(32)||withdrawer_pubkey(32)
+    inputs: OnBehalfInputs,
+    stealth_ephemeral: Option<[u8; 32]>,
 ) -> Result<()> {
     let pool = &ctx.accounts.pool;
 
     //
-    // 1) Decode & verify, unpack secret_be, null_be, root_be, withdrawer_bytes
+    // 1) Verify, the proof binds amount, nullifier, root and withdrawer
     //
-    let (secret_be, null_be, withdrawer_bytes, root_be) =
-        verify_withdraw_on_behalf(&proof, &public_inputs)
-            .map_err(|_| ErrorCode::InvalidProof)?;
-
-    // reconstruct withdrawer pubkey and check it matches the passed-in account
-    let withdrawer_key = ctx.accounts.withdrawer.key.to_bytes();
+    // a key at or above the modulus would only be bound mod r, and anyone holding the
+    // proof could swap in an alias nobody has the private key of
+    require!(
+        inputs.withdrawer.to_bytes() < FIELD_MODULUS,
+        ErrorCode::WithdrawerNotAFieldElement
+    );
+    verify_withdraw_on_behalf(&proof, &inputs).map_err(|_| ErrorCode::InvalidProof)?;
+    let OnBehalfInputs { nullifier: null_be, amount, root: root_be, withdrawer } = inputs;
 
-    let expected_withdrawer = pubkey!(&withdrawer_bytes);
+    // check the withdrawer in the proof matches the passed-in account
     require!(
-        withdrawer_key == withdrawer_bytes,
+        ctx.accounts.withdrawer.key() == withdrawer,
         ErrorCode::InvalidWithdrawerKey
     );
 
     // also ensure the withdrawer is not the same as the fee‐payer
     require!(
-        expected_withdrawer != &ctx.accounts.payer.key().to_bytes(),
+        withdrawer != ctx.accounts.payer.key(),
         ErrorCode::InvalidArgument
     );
 
     // 2) Check the Merkle root
-    let temp_batch = get_root(&pool.batch_leaves);
-    let temp_root = pool.update_peaks_temp(temp_batch);
-    require!(
-        pool.deepen_temp(temp_root, TARGET_DEPTH_LARGE) == root_be,
-        ErrorCode::InvalidPublicInputRoot
-    );
+    require!(pool.deep_root == root_be, ErrorCode::InvalidPublicInputRoot);
 
     // 3) Compute amount and fee
-    let amount = u64::from_be_bytes(secret_be);
-    
     // Validate that the amount is reasonable (not too large)
     const MAX_REASONABLE_AMOUNT: u64 = 1_000_000_000_000; // 1000 SOL in lamports
-    msg!("Validating amount: {} <= {}", amount, MAX_REASONABLE_AMOUNT);
     require!(
         amount <= MAX_REASONABLE_AMOUNT,
         ErrorCode::InvalidArgument
@@ -1068,49 +1140,68 @@ pub fn withdraw_on_behalf_with_shard(
     let pool_fee = POOL_FEE; // Pool fee for nullifier storage
     let on_behalf_fee = ON_BEHALF_FEE; // Fee for on-behalf withdrawal
     let total_fees = pool_fee + on_behalf_fee;
-    msg!("Pool fee: {}, On-behalf fee: {}, Total fees: {}", pool_fee, on_behalf_fee, total_fees);
     let net_amount = amount
         .checked_sub(total_fees)
         .ok_or(ErrorCode::InvalidArgument)?;
-    msg!("Net amount after fees: {}", net_amount);
 
     // Check if pool has sufficient balance
     let pool_lamports = ctx.accounts.pool.to_account_info().lamports();
-    msg!("Pool lamports: {}", pool_lamports);
-    msg!("Amount: {}", amount);
     require!(
         pool_lamports >= amount,
         ErrorCode::InsufficientFunds
     );
 
-    // 4) Process nullifier using shard mechanism
-    process_one_nullifier_ai(
-        &ctx.accounts.pool,
-        ctx.bumps.pool,
-        &mut ctx.accounts.nullifier_shard.to_account_info(),
-        &ctx.accounts.dummy0_account,
-        &ctx.accounts.dummy1_account,
-        null_be,
-        &ctx.accounts.payer.to_account_info(),
-        &ctx.accounts.system_program,
-        &id(),
+    // 4) Record the nullifier in the pool's store
+    let payer = ctx.accounts.payer.to_account_info();
+    let reserve = ctx.accounts.rent_reserve.to_account_info();
+    let reserve_before = reserve.lamports();
+    spend_nullifier(
+        &NullifierAccounts {
+            pool: &ctx.accounts.pool,
+            record: &ctx.accounts.nullifier_shard,
+            child0: &ctx.accounts.dummy0_account,
+            child1: &ctx.accounts.dummy1_account,
+            directory: &ctx.accounts.shard_directory,
+            reserve: &reserve,
+            instructions: &ctx.accounts.instruction_account,
+            payer: &payer,
+            system_program: &ctx.accounts.system_program,
+        },
+        &null_be,
     )?;
+    let rent = reserve_before.saturating_sub(reserve.lamports());
 
-    // 5) Move lamports: pool → withdrawer + payer
-    // Subtract only the amount that leaves the pool (net_amount + on_behalf_fee)
-    let amount_leaving_pool = net_amount + on_behalf_fee;
-    **ctx.accounts.pool.to_account_info().try_borrow_mut_lamports()? -= amount_leaving_pool;
+    // 5) Move lamports: pool → withdrawer + payer + rent reserve
+    **ctx.accounts.pool.to_account_info().try_borrow_mut_lamports()? -= amount;
     **ctx.accounts.withdrawer.try_borrow_mut_lamports()? += net_amount;
     **ctx.accounts.payer.try_borrow_mut_lamports()? += on_behalf_fee;
-    // Pool fee stays in the pool (already accounted for in amount_leaving_pool calculation)
-
-    msg!(
-        "Withdrew {} total; {} for pool fee, {} for on-behalf fee, {} net to withdrawer",
-        amount,
-        pool_fee,
-        on_behalf_fee,
-        net_amount
-    );
+    **reserve.try_borrow_mut_lamports()? += pool_fee;
+    let ledger = &mut ctx.accounts.pool.ledger;
+    ledger.withdraw(net_amount, total_fees)?;
+    ledger.spend_rent(rent)?;
+
+    let pool = &ctx.accounts.pool;
+    emit!(Withdrawal {
+        pool: pool.identifier,
+        recipient: withdrawer,
+        amount: net_amount,
+        fees: total_fees,
+    });
+    emit!(FeeCollected {
+        pool: pool.identifier,
+        payer: pool.key(),
+        recipient: ctx.accounts.rent_reserve.key(),
+        amount: pool_fee,
+    });
+    emit!(FeeCollected {
+        pool: pool.identifier,
+        payer: pool.key(),
+        recipient: ctx.accounts.payer.key(),
+        amount: on_behalf_fee,
+    });
+    if let Some(ephemeral_key) = stealth_ephemeral {
+        publish_stealth(&withdrawer, &ephemeral_key);
+    }
 
     Ok(())
 }
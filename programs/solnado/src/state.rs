@@ -1,9 +1,19 @@
-use crate::utils::get_default_root_depth;
+use crate::events::{BatchRolledOver, LeafInserted, SmallTreeClosed};
+use crate::ledger::PoolLedger;
+use crate::limits::{DepositEpoch, DepositLimits};
+use crate::nullifier::NullifierStoreKind;
+use crate::registry::{PoolEntry, PoolRegistry};
+use crate::reserve::{RentReserve, RENT_RESERVE_SPACE};
+use crate::shard::SHARD_SPLITTING_THRESHOLD;
+use crate::sunset::PoolStatus;
+use crate::utils::{
+    default_batch_nodes, default_leaves, get_default_root_depth, insert_into_batch, BatchNodes,
+};
 use crate::{
-    utils::next_power_of_two_batch, DEFAULT_LEAF, MIN_PDA_SIZE, SMALL_TREE_BATCH_DEPTH,
-    TARGET_DEPTH_LARGE, TARGET_DEPTH_LARGE_ARRAY,
+    utils::next_power_of_two_batch, DEFAULT_LEAF, LEAVES_LENGTH, MIN_PDA_SIZE,
+    SMALL_TREE_BATCH_DEPTH, TARGET_DEPTH_LARGE, TARGET_DEPTH_LARGE_ARRAY,
 };
-use anchor_lang::{prelude::*, solana_program::log::sol_log_compute_units};
+use anchor_lang::prelude::*;
 use solana_poseidon::{hashv, Endianness, Parameters};
 pub const SHARD_SIZE: usize = 8;
 
@@ -14,7 +24,7 @@ pub struct InitializePool<'info> {
         init,
         payer = authority,
         // We'll allocate enough space for the Pool struct.
-        space = 8 + MerkleMountainRange::MAX_SIZE,
+        space = 8 + MerkleMountainRange::SIZE,
         seeds = [b"pool_merkle".as_ref(), &identifier],
         bump
     )]
@@ -32,7 +42,7 @@ pub struct InitializeVariablePool<'info> {
     #[account(
         init,
         payer = authority,
-        space = 8 + MerkleMountainRange::MAX_SIZE,
+        space = 8 + MerkleMountainRange::SIZE,
         seeds = [b"variable_pool".as_ref(), &identifier],
         bump
     )]
@@ -47,6 +57,34 @@ pub struct InitializeVariablePool<'info> {
     #[account(mut)]
     pub subtree_indexer: AccountInfo<'info>,
 
+    /// Collects the spend fees, pays shard rent
+    #[account(
+        init,
+        payer = authority,
+        space = RENT_RESERVE_SPACE,
+        seeds = [b"rent_reserve".as_ref(), &identifier],
+        bump
+    )]
+    pub rent_reserve: Account<'info, RentReserve>,
+
+    /// Numbers the new pool
+    #[account(
+        mut,
+        seeds = [b"pool_registry".as_ref()],
+        bump
+    )]
+    pub pool_registry: Account<'info, PoolRegistry>,
+
+    /// Lists the new pool, at the creator's expense
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PoolEntry::SIZE,
+        seeds = [b"pool_entry".as_ref(), &identifier],
+        bump
+    )]
+    pub pool_entry: Account<'info, PoolEntry>,
+
     #[account(mut)]
     pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
@@ -93,6 +131,13 @@ pub struct DepositVariable<'info> {
     #[account(mut)]
     pub depositor: Signer<'info>,
 
+    ///CHECK : the pool's denylist PDA, empty until initialized
+    #[account(
+        seeds = [b"denylist".as_ref(), &pool.identifier],
+        bump
+    )]
+    pub denylist: AccountInfo<'info>,
+
     ///CHECK : SYSVAR for instructions
     pub instruction_account: AccountInfo<'info>,
     pub system_program: Program<'info, System>,
@@ -272,6 +317,27 @@ pub struct MerkleMountainRange {
     pub depth: [u8; TARGET_DEPTH_LARGE_ARRAY],       //With each peak we associate a depth
     pub number_of_peaks: u8,                         //Max number of peaks is
     pub max_leaves: u64,                             //MAX number of leaves in a pool
+    /// Where spent nullifiers go, fixed at creation
+    pub nullifier_store: NullifierStoreKind,
+    /// Fill at which a shard splits, set with the shards. 0 on older pools
+    pub shard_split_threshold: u16,
+    /// Lamports in and out of the notes, checked by `audit_pool`
+    pub ledger: PoolLedger,
+    /// Sets the deposit caps, the pool's creator
+    pub authority: Pubkey,
+    pub deposit_limits: DepositLimits,
+    pub deposit_epoch: DepositEpoch,
+    /// Internal nodes of the current batch, so an insert only rehashes the leaf's path
+    pub batch_nodes: BatchNodes,
+    /// Root spends are proven against: the closed batches plus the in-flight one, deepened
+    /// to `TARGET_DEPTH_LARGE`. Kept current by `refresh_deep_root`
+    pub deep_root: [u8; 32],
+    /// Layout of this account, see `migrate`. 0 on pools from before the field
+    pub version: u8,
+    /// Lifecycle, see `sunset`
+    pub status: PoolStatus,
+    /// When the pool was sunset, 0 while active
+    pub sunset_at: i64,
                                                      //Creator is optional
                                                      // pub creator: Pubkey,
                                                      // //Creator fee is optional
@@ -280,7 +346,36 @@ pub struct MerkleMountainRange {
 }
 
 impl MerkleMountainRange {
-    pub const MAX_SIZE: usize = 32 + 512 + 16 + 8 + 32 + 32 + 26 * 32 + 26 + 1 + 8 + 32 + 8 + 100;
+    /// Serialized size of the current layout, without the discriminator
+    pub const SIZE: usize = 32
+        + LEAVES_LENGTH * 32
+        + 16
+        + 8
+        + 32
+        + 32
+        + 8
+        + TARGET_DEPTH_LARGE_ARRAY * 32
+        + TARGET_DEPTH_LARGE_ARRAY
+        + 1
+        + 8
+        + 1
+        + 2
+        + PoolLedger::SIZE
+        + 32
+        + DepositLimits::SIZE
+        + DepositEpoch::SIZE
+        + (LEAVES_LENGTH - 2) * 32
+        + 32
+        + 1
+        + 1
+        + 8;
+
+    pub fn split_threshold(&self) -> usize {
+        match self.shard_split_threshold {
+            0 => SHARD_SPLITTING_THRESHOLD,
+            t => t as usize,
+        }
+    }
 
     pub fn find_first_match(&self) -> usize {
         for (index, element) in self.batch_leaves.into_iter().enumerate() {
@@ -302,9 +397,6 @@ impl MerkleMountainRange {
         let mut peak_hashes = self.peaks;
         let mut peak_depths = self.depth;
         let mut count = self.number_of_peaks;
-        msg!("peaks before update: {:?}", &peak_hashes[..count as usize]);
-        msg!("depth before update: {:?}", &peak_depths[..count as usize]);
-        msg!("number of peaks before update: {}", count);
 
         // New batch has default depth 4 (since 16 leaves = 2^4).
         let new_peak_hash = new_batch;
@@ -337,7 +429,7 @@ impl MerkleMountainRange {
         }
 
         // Clear unused entries so that the arrays only reflect the active peaks.
-        for i in count as usize..16 {
+        for i in count as usize..TARGET_DEPTH_LARGE_ARRAY {
             peak_hashes[i] = [0u8; 32]; // or DEFAULT_LEAF if defined
             peak_depths[i] = 0;
         }
@@ -345,15 +437,55 @@ impl MerkleMountainRange {
         self.peaks = peak_hashes;
         self.depth = peak_depths;
         self.number_of_peaks = count;
-        msg!(
-            "peaks after update: {:?}",
-            &self.peaks[..self.number_of_peaks as usize]
-        );
-        msg!(
-            "depth after update: {:?}",
-            &self.depth[..self.number_of_peaks as usize]
-        );
-        msg!("number of peaks after update: {}", self.number_of_peaks);
+    }
+
+    /// Empties the current batch
+    pub fn reset_batch(&mut self) {
+        self.batch_leaves = default_leaves();
+        self.batch_nodes = default_batch_nodes();
+        self.merkle_root_batch = get_default_root_depth(4);
+    }
+
+    /// Puts `leaf` in slot `idx` of the current batch
+    pub fn insert_leaf(&mut self, idx: usize, leaf: [u8; 32]) {
+        self.merkle_root_batch =
+            insert_into_batch(&mut self.batch_leaves, &mut self.batch_nodes, idx, leaf);
+        emit!(LeafInserted {
+            pool: self.identifier,
+            index: self.batch_number * LEAVES_LENGTH as u64 + idx as u64,
+            leaf,
+        });
+    }
+
+    /// Folds the full batch into the peaks and opens the next one
+    pub fn roll_over_batch(&mut self) {
+        let batch_root = self.merkle_root_batch;
+        let small_tree_root = self.last_small_tree_root;
+        self.update_peaks(batch_root);
+        let batch_number = self.batch_number;
+        self.batch_number = batch_number.checked_add(1).unwrap();
+        self.whole_tree_root = self.compute_root_from_peaks();
+        self.reset_batch();
+
+        emit!(BatchRolledOver {
+            pool: self.identifier,
+            batch_number,
+            batch_root,
+            tree_root: self.whole_tree_root,
+        });
+        if self.last_small_tree_root != small_tree_root {
+            emit!(SmallTreeClosed {
+                pool: self.identifier,
+                batch_number,
+                small_tree_root: self.last_small_tree_root,
+            });
+        }
+    }
+
+    /// Recomputes `deep_root`, once an instruction's leaves are all in
+    pub fn refresh_deep_root(&mut self) {
+        let temp_root = self.update_peaks_temp(self.merkle_root_batch);
+        self.deep_root = self.deepen_temp(temp_root, TARGET_DEPTH_LARGE);
     }
 
     pub fn update_peaks_temp(&self, new_batch: [u8; 32]) -> [u8; 32] {
@@ -393,7 +525,7 @@ impl MerkleMountainRange {
         }
 
         // Clear unused entries so that the arrays only reflect the active peaks.
-        for i in count as usize..16 {
+        for i in count as usize..TARGET_DEPTH_LARGE_ARRAY {
             peak_hashes[i] = [0u8; 32]; // or DEFAULT_LEAF if defined
             peak_depths[i] = 0;
         }
@@ -454,34 +586,13 @@ impl MerkleMountainRange {
             .map(|i| (self.peaks[i], self.depth[i]))
             .collect();
 
-        // Merge pairwise until we have one node.
-        while nodes.len() > 1 {
-            let mut next_level: Vec<([u8; 32], u8)> = Vec::with_capacity((nodes.len() + 1) / 2);
-            let mut i = 0;
-            while i < nodes.len() {
-                if i + 1 < nodes.len() {
-                    // Merge two adjacent nodes (taking care of depth differences)
-                    let merged = Self::merge_nodes(nodes[i], nodes[i + 1]);
-                    next_level.push(merged);
-                } else {
-                    // If there's an odd node out, merge it with the default node for its depth.
-                    let (node, depth) = nodes[i];
-                    let default = get_default_root_depth(depth as usize);
-                    let merged = hashv(
-                        Parameters::Bn254X5,
-                        Endianness::BigEndian,
-                        &[&node, &default],
-                    )
-                    .unwrap()
-                    .to_bytes();
-                    next_level.push((merged, depth + 1));
-                }
-                i += 2;
-            }
-            nodes = next_level;
+        // Bag from the right: peaks have strictly decreasing depths, so each one is the
+        // left sibling of everything after it, once that is lifted to its depth.
+        let mut acc = nodes.pop().unwrap();
+        while let Some(peak) = nodes.pop() {
+            acc = Self::merge_nodes(peak, acc);
         }
-        // Return the computed root.
-        nodes[0].0
+        acc.0
     }
 
     pub fn compute_root_from_peaks_temp(
@@ -495,34 +606,13 @@ impl MerkleMountainRange {
             .map(|i| (peaks[i], depth[i]))
             .collect();
 
-        // Merge pairwise until we have one node.
-        while nodes.len() > 1 {
-            let mut next_level: Vec<([u8; 32], u8)> = Vec::with_capacity((nodes.len() + 1) / 2);
-            let mut i = 0;
-            while i < nodes.len() {
-                if i + 1 < nodes.len() {
-                    // Merge two adjacent nodes (taking care of depth differences)
-                    let merged = Self::merge_nodes(nodes[i], nodes[i + 1]);
-                    next_level.push(merged);
-                } else {
-                    // If there's an odd node out, merge it with the default node for its depth.
-                    let (node, depth) = nodes[i];
-                    let default = get_default_root_depth(depth as usize);
-                    let merged = hashv(
-                        Parameters::Bn254X5,
-                        Endianness::BigEndian,
-                        &[&node, &default],
-                    )
-                    .unwrap()
-                    .to_bytes();
-                    next_level.push((merged, depth + 1));
-                }
-                i += 2;
-            }
-            nodes = next_level;
+        // Bag from the right: peaks have strictly decreasing depths, so each one is the
+        // left sibling of everything after it, once that is lifted to its depth.
+        let mut acc = nodes.pop().unwrap();
+        while let Some(peak) = nodes.pop() {
+            acc = Self::merge_nodes(peak, acc);
         }
-        // Return the computed root.
-        nodes[0].0
+        acc.0
     }
 
     //this method allows
@@ -0,0 +1,14 @@
+//! Stealth withdrawals. The withdrawer of `withdraw_on_behalf_shard` may be a one-time
+//! address derived from the recipient's stealth meta-address, the ephemeral key it was
+//! derived with is logged next to it so the recipient can find it and derive its secret.
+//! The program doesn't check the derivation, a wrong key only hides the funds from the
+//! recipient.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::log::sol_log_data;
+
+/// Tag of the stealth withdrawal logs: `"stealth" || withdrawer || ephemeral_key`
+pub const STEALTH_LOG_TAG: &[u8] = b"stealth";
+
+pub fn publish_stealth(withdrawer: &Pubkey, ephemeral_key: &[u8; 32]) {
+    sol_log_data(&[STEALTH_LOG_TAG, withdrawer.as_ref(), ephemeral_key]);
+}
@@ -0,0 +1,123 @@
+//! Pool retirement. The authority sunsets a pool: deposits stop, spends go on. Once
+//! `SUNSET_GRACE_SECONDS` passed it closes the pool and reclaims the rent of the pool's
+//! empty indexers and shards. Spends still go on after that, so no note is ever stuck: a
+//! reclaimed shard stays in the directory and the first spend landing in it creates it
+//! again, at the rent reserve's expense.
+use crate::error::ErrorCode;
+use crate::events::{PoolClosed, PoolSunset, RentReclaimed};
+use crate::shard::{derive_shard_pda_key, load_shard_mut};
+use crate::MerkleMountainRange;
+use anchor_lang::prelude::*;
+
+pub const SUNSET_GRACE_SECONDS: i64 = 180 * 24 * 60 * 60;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoolStatus {
+    #[default]
+    Active,
+    /// No deposits, spends still allowed
+    Sunset,
+    /// No deposits, spends still allowed, empty accounts reclaimable
+    Closed,
+}
+
+/// Fails with `DepositsClosed` unless the pool is active
+pub fn require_deposits_open(pool: &MerkleMountainRange) -> Result<()> {
+    require!(pool.status == PoolStatus::Active, ErrorCode::DepositsClosed);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SunsetPool<'info> {
+    #[account(
+        mut,
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAction
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    pub authority: Signer<'info>,
+}
+
+pub fn sunset_pool(ctx: Context<SunsetPool>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    require!(pool.status == PoolStatus::Active, ErrorCode::PoolNotActive);
+    pool.status = PoolStatus::Sunset;
+    pool.sunset_at = Clock::get()?.unix_timestamp;
+    emit!(PoolSunset {
+        pool: pool.identifier,
+        closes_after: pool.sunset_at + SUNSET_GRACE_SECONDS,
+    });
+    Ok(())
+}
+
+/// Remaining accounts: the indexers and shards to close, each empty
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    #[account(
+        mut,
+        seeds = [ b"variable_pool", pool.identifier.as_ref() ],
+        bump,
+        has_one = authority @ ErrorCode::UnauthorizedAction
+    )]
+    pub pool: Account<'info, MerkleMountainRange>,
+
+    /// Receives the reclaimed rent
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+/// An indexer of the pool, which never holds data, or one of its shards with no nullifier
+fn check_reclaimable(pool_id: [u8; 16], account: &AccountInfo) -> Result<()> {
+    let indexers = [b"leaves_indexer".as_ref(), b"subtree_indexer".as_ref()]
+        .map(|seed| Pubkey::find_program_address(&[seed, &pool_id], &crate::ID).0);
+    if indexers.contains(account.key) {
+        require!(
+            account.owner == &crate::ID && account.try_borrow_data()?.iter().all(|b| *b == 0),
+            ErrorCode::InvalidReclaimAccount
+        );
+        return Ok(());
+    }
+    let shard = load_shard_mut(account).map_err(|_| ErrorCode::InvalidReclaimAccount)?;
+    let (expected, _) = derive_shard_pda_key(pool_id, &shard.prefix, shard.prefix_len);
+    require!(expected == *account.key, ErrorCode::InvalidReclaimAccount);
+    require!(shard.count == 0, ErrorCode::ShardNotEmpty);
+    Ok(())
+}
+
+/// Closes a sunset pool past its grace period, then or later closes the empty indexers
+/// and shards passed as remaining accounts, their rent going to the authority
+pub fn close_pool<'info>(ctx: Context<'_, '_, 'info, 'info, ClosePool<'info>>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    if pool.status == PoolStatus::Sunset {
+        require!(
+            Clock::get()?.unix_timestamp >= pool.sunset_at + SUNSET_GRACE_SECONDS,
+            ErrorCode::SunsetGracePending
+        );
+        pool.status = PoolStatus::Closed;
+        emit!(PoolClosed {
+            pool: pool.identifier,
+        });
+    }
+    require!(pool.status == PoolStatus::Closed, ErrorCode::PoolNotSunset);
+
+    let authority = ctx.accounts.authority.to_account_info();
+    for account in ctx.remaining_accounts {
+        check_reclaimable(pool.identifier, account)?;
+        // closed the way Anchor's `close` does, so later instructions of the transaction
+        // see a system account without data and not a live shard
+        let amount = account.lamports();
+        account.try_borrow_mut_data()?.fill(0);
+        **authority.try_borrow_mut_lamports()? += amount;
+        **account.try_borrow_mut_lamports()? = 0;
+        account.assign(&System::id());
+        account.resize(0)?;
+        emit!(RentReclaimed {
+            pool: pool.identifier,
+            account: account.key(),
+            amount,
+        });
+    }
+    Ok(())
+}
@@ -1,4 +1,8 @@
 use crate::error::ErrorCode;
+use crate::inputs::*;
+use crate::memo::{
+    find_memo_instruction, SmallTreeMemo, SubBatchMemo, SMALL_TREE_MEMO_LEN, SUB_BATCH_MEMO_LEN,
+};
 use crate::verifying_key::*;
 use crate::{DEFAULT_LEAF, LEAVES_LENGTH};
 use anchor_lang::prelude::*;
@@ -39,6 +43,73 @@ pub fn get_root(leaves: &LeavesArray) -> [u8; 32] {
     nodes[0]
 }
 
+/// Internal nodes of a batch below its root, level by level from the leaves up (8, 4, 2)
+pub type BatchNodes = [[u8; 32]; LEAVES_LENGTH - 2];
+
+/// Internal nodes of an empty batch
+pub fn default_batch_nodes() -> BatchNodes {
+    let mut nodes = [DEFAULT_LEAF; LEAVES_LENGTH - 2];
+    nodes[..8].fill(DEPTH_ONE);
+    nodes[8..12].fill(DEPTH_TWO);
+    nodes[12..].fill(DEPTH_THREE);
+    nodes
+}
+
+/// Internal nodes of the batch holding `leaves`
+pub fn batch_nodes(leaves: &LeavesArray) -> BatchNodes {
+    let mut nodes = default_batch_nodes();
+    let mut level = leaves.to_vec();
+    let mut start = 0;
+    while level.len() > 2 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                hashv(Parameters::Bn254X5, Endianness::BigEndian, &[&pair[0], &pair[1]])
+                    .unwrap()
+                    .to_bytes()
+            })
+            .collect();
+        nodes[start..start + level.len()].copy_from_slice(&level);
+        start += level.len();
+    }
+    nodes
+}
+
+/// Puts `leaf` in slot `idx` and rehashes its path only, 4 hashes instead of `get_root`'s 15.
+/// Returns the new batch root.
+pub fn insert_into_batch(
+    leaves: &mut LeavesArray,
+    nodes: &mut BatchNodes,
+    idx: usize,
+    leaf: [u8; 32],
+) -> [u8; 32] {
+    leaves[idx] = leaf;
+    let mut node = leaf;
+    let mut sibling = leaves[idx ^ 1];
+    let mut pos = idx;
+    // offset and width of the level being written in `nodes`
+    let mut start = 0;
+    let mut width = LEAVES_LENGTH / 2;
+    loop {
+        let (left, right) = if pos & 1 == 0 {
+            (node, sibling)
+        } else {
+            (sibling, node)
+        };
+        node = hashv(Parameters::Bn254X5, Endianness::BigEndian, &[&left, &right])
+            .unwrap()
+            .to_bytes();
+        pos /= 2;
+        if width == 1 {
+            return node;
+        }
+        nodes[start + pos] = node;
+        sibling = nodes[start + (pos ^ 1)];
+        start += width;
+        width /= 2;
+    }
+}
+
 fn change_endianness(bytes: &[u8]) -> Vec<u8> {
     let mut vec = Vec::new();
     for b in bytes.chunks(32) {
@@ -267,7 +338,7 @@ pub fn verify_withdraw_fixed_proof(proof: &[u8; 256], public_inputs: &[u8]) -> R
         .expect("Failed public_input_nullifier parsing");
 
     let public_inputs_array: &[[u8; 32]; 2] = &[public_input_nullifier, public_input_root];
-    let _ = proof_verification(proof, &VERIFYINGKEY, public_inputs_array);
+    proof_verification(proof, &VERIFYINGKEY, public_inputs_array)?;
 
     // let vk: Groth16Verifyingkey = VERIFYINGKEY;
     // let proof_a: G1 =
@@ -286,118 +357,45 @@ pub fn verify_withdraw_fixed_proof(proof: &[u8; 256], public_inputs: &[u8]) -> R
 }
 
 //For variable deposit amount, 2 leaves to one
-pub fn verify_deposit_proof(
-    proof: &[u8; 256],
-    public_inputs: &[u8],
-) -> Result<([u8; 8], [u8; 32], [u8; 32])> {
-    if public_inputs.len() != 72 {
-        msg!("Invalid public inputs length: {}", public_inputs.len());
-        return Err(ErrorCode::InvalidArgument.into());
-    }
-
-    // unpack the three outputs
-    let sum_be8: [u8; 8] = public_inputs[0..8]
-        .try_into()
-        .expect("Error converting type");
-    let mut secret_be = [0u8; 32];
-    // copy your 8 bytes into the *right* end of the 32-byte buffer
-    secret_be[32 - 8..].copy_from_slice(&sum_be8);
-    let leaf1: [u8; 32] = public_inputs[8..40]
-        .try_into()
-        .expect("Error converting type");
-    let leaf2: [u8; 32] = public_inputs[40..72]
-        .try_into()
-        .expect("Error converting type");
-
-    let inputs_arr: &[[u8; 32]; 3] = &[secret_be, leaf1, leaf2];
-
-    let _ = proof_verification(proof, &VERIFYINGKEY_DEPOSIT2, inputs_arr);
+pub fn verify_deposit_proof(proof: &[u8; 256], inputs: &DepositInputs) -> Result<()> {
+    let inputs_arr: [[u8; 32]; 3] = inputs.to_field_elements();
+    proof_verification(proof, &VERIFYINGKEY_DEPOSIT2, &inputs_arr)?;
 
     msg!("Double leaf deposit proof succesfully verified");
-
-    Ok((sum_be8, leaf1, leaf2))
+    Ok(())
 }
 
-pub fn verify_single_deposit_proof(
-    proof: &[u8; 256],
-    public_inputs: &[u8],
-) -> Result<([u8; 8], [u8; 32])> {
-    // must be exactly three 32‐byte words
-    if public_inputs.len() != 72 {
-        msg!("Invalid public inputs length: {}", public_inputs.len());
-        return Err(ErrorCode::InvalidArgument.into());
-    }
-
-    // unpack the three outputs
-    let sum_be8: [u8; 8] = public_inputs[0..8]
-        .try_into()
-        .expect("Error converting type");
-    let mut secret_be = [0u8; 32];
-    // copy your 8 bytes into the *right* end of the 32-byte buffer
-    secret_be[32 - 8..].copy_from_slice(&sum_be8);
-    let leaf1: [u8; 32] = public_inputs[8..40]
-        .try_into()
-        .expect("Error converting type");
-
-    let inputs_arr: &[[u8; 32]; 2] = &[secret_be, leaf1];
-    let _ = proof_verification(proof, &VERIFYINGKEY_DEPOSIT1, inputs_arr);
+pub fn verify_single_deposit_proof(proof: &[u8; 256], inputs: &DepositInputs) -> Result<()> {
+    let inputs_arr: [[u8; 32]; 2] = inputs.to_field_elements();
+    proof_verification(proof, &VERIFYINGKEY_DEPOSIT1, &inputs_arr)?;
 
     msg!("Proof single leaf deposit proof succesfully verified");
-
-    Ok((sum_be8, leaf1))
+    Ok(())
 }
 
 // 2 null -> 1 leaf
-pub fn verify_combine_proof(
-    proof: &[u8; 256],
-    // null1: [u8; 32],
-    // null2: [u8; 32],
-    // new_leaf: [u8; 32],
-    // root: [u8; 32],
-    public_inputs: &[u8],
-) -> Result<([u8; 32], [u8; 32], [u8; 32], [u8; 32])> {
-    // Build the fixed‐size array reference for the verifier
-    let n1: [u8; 32] = public_inputs[..32].try_into().expect("Failed converting");
-    let n2: [u8; 32] = public_inputs[32..64].try_into().expect("Failed converting");
-    let new_leaf: [u8; 32] = public_inputs[64..96].try_into().expect("Failed converting");
-    let root: [u8; 32] = public_inputs[96..128]
-        .try_into()
-        .expect("Failed converting");
-    let inputs_arr: &[[u8; 32]; 4] = &[n1, n2, new_leaf, root];
-
-    let _ = proof_verification(proof, &COMBINE2TO1_VERIFYINGKEY, inputs_arr);
+pub fn verify_combine_proof(proof: &[u8; 256], inputs: &CombineDoubleInputs) -> Result<()> {
+    proof_verification(proof, &COMBINE2TO1_VERIFYINGKEY, &inputs.to_field_elements())?;
 
     msg!("Combine proof successfully verified");
-
-    // Return the four public outputs
-    Ok((n1, n2, new_leaf, root))
-    // (*new_leaf, *root)
+    Ok(())
 }
 
-pub fn verify_one_null_two_leaves(
-    proof: &[u8; 256],
-    // null1: [u8; 32],
-    // null2: [u8; 32],
-    // new_leaf: [u8; 32],
-    // root: [u8; 32],
-    public_inputs: &[u8],
-) -> Result<([u8; 32], [u8; 32], [u8; 32], [u8; 32])> {
-    // Build the fixed‐size array reference for the verifier
-    let n1: [u8; 32] = public_inputs[..32].try_into().expect("Failed converting");
-    let leaf1: [u8; 32] = public_inputs[32..64].try_into().expect("Failed converting");
-    let leaf2: [u8; 32] = public_inputs[64..96].try_into().expect("Failed converting");
-    let root: [u8; 32] = public_inputs[96..128]
-        .try_into()
-        .expect("Failed converting");
-    let inputs_arr: &[[u8; 32]; 4] = &[n1, leaf1, leaf2, root];
+// nullifier into an indexed pool's tree
+#[cfg(feature = "dev-keys")]
+pub fn verify_nullifier_insertion(proof: &[u8; 256], inputs: &IndexedInsertInputs) -> Result<()> {
+    proof_verification(proof, &INDEXED_INSERT_VK, &inputs.to_field_elements())?;
 
-    let _ = proof_verification(proof, &COMBINE1TO2_VERIFYINGKEY, inputs_arr);
+    msg!("Nullifier insertion proof successfully verified");
+    Ok(())
+}
 
-    msg!("Combine proof successfully verified");
+// 1 null -> 2 leaves
+pub fn verify_one_null_two_leaves(proof: &[u8; 256], inputs: &CombineSingleInputs) -> Result<()> {
+    proof_verification(proof, &COMBINE1TO2_VERIFYINGKEY, &inputs.to_field_elements())?;
 
-    // Return the four public outputs
-    Ok((n1, leaf1, leaf2, root))
-    // (*new_leaf, *root)
+    msg!("Combine proof successfully verified");
+    Ok(())
 }
 
 pub fn verify_two_null_two_leaves(
@@ -420,7 +418,7 @@ pub fn verify_two_null_two_leaves(
         .expect("Failed converting");
     let inputs_arr: &[[u8; 32]; 5] = &[n1, n2, leaf1, leaf2, root];
 
-    let _ = proof_verification(proof, &COMBINE2TO2_VERIFYINGKEY, inputs_arr);
+    proof_verification(proof, &COMBINE2TO2_VERIFYINGKEY, inputs_arr)?;
 
     msg!("Combine proof successfully verified");
 
@@ -428,100 +426,69 @@ pub fn verify_two_null_two_leaves(
     Ok((n1, n2, leaf1, leaf2, root))
 }
 
-/// Unpacks & verifies a single‐leaf Merkle‐inclusion proof for withdrawal.
-/// Expects `public_inputs = secret_be || nullifier_hash_be || root_be`, each 32 bytes.
-pub fn verify_withdraw_proof(
-    proof: &[u8; 256],
-    public_inputs: &[u8],
-) -> Result<([u8; 8], [u8; 32], [u8; 32])> {
-    // let secret_be: [u8; 32] = Fr::from(8u64)
-    //     .0
-    //     .to_bytes_be()
-    //     .try_into()
-    //     .expect("Failed conversion");
-
-    // allocate a 32-byte buffer, zero-initialized
-    let mut val_be = [0u8; 32];
-    // copy your 8 bytes into the *right* end of the 32-byte buffer
-    let null: [u8; 32] = public_inputs[0..32].try_into().expect("Failed");
-    let asset_id: [u8; 32] = public_inputs[32..64].try_into().expect("Failed");
-    let val_be8: [u8; 8] = public_inputs[64..72].try_into().expect("Failed");
-    val_be[32 - 8..].copy_from_slice(&val_be8);
-    let root: [u8; 32] = public_inputs[72..104].try_into().expect("Failed");
-
+/// Verifies a single‐leaf Merkle‐inclusion proof for withdrawal.
+pub fn verify_withdraw_proof(proof: &[u8; 256], inputs: &WithdrawInputs) -> Result<()> {
     //For SOL this must be 0.
-    for i in asset_id {
-        if i != 0 {
-            return Err(ErrorCode::InvalidAssetId.into());
-        }
-    }
+    require!(inputs.asset_id == [0u8; 32], ErrorCode::InvalidAssetId);
 
-    let inputs_arr: &[[u8; 32]; 4] = &[val_be, asset_id, null, root];
-
-    let _ = proof_verification(proof, &WITHDRAW_VAR_VK, inputs_arr);
-
-    Ok((val_be8, null, root))
+    let inputs_arr: [[u8; 32]; 4] = inputs.to_field_elements();
+    proof_verification(proof, &WITHDRAW_VAR_VK, &inputs_arr)?;
+    Ok(())
 }
 
-pub fn verify_withdraw_and_add_proof(
-    proof: &[u8; 256],
-    public_inputs: &[u8],
-) -> Result<([u8; 8], [u8; 32], [u8; 32], [u8; 32])> {
-    let mut val_be = [0u8; 32];
-    let val_be8: [u8; 8] = public_inputs[96..104].try_into().expect("Failed");
-    // copy your 8 bytes into the *right* end of the 32-byte buffer
-    val_be[32 - 8..].copy_from_slice(&val_be8);
-    let null: [u8; 32] = public_inputs[0..32].try_into().expect("Failed");
-    let asset_id: [u8; 32] = public_inputs[32..64].try_into().expect("Failed");
-    let root = public_inputs[64..96].try_into().expect("Failed");
-    let new_leaf: [u8; 32] = public_inputs[104..136].try_into().expect("Failed");
-    let inputs_arr: &[[u8; 32]; 5] = &[val_be, asset_id, null, new_leaf, root];
+pub fn verify_withdraw_and_add_proof(proof: &[u8; 256], inputs: &WithdrawInputs) -> Result<()> {
     //For SOL this must be 0.
-    for i in asset_id {
-        if i != 0 {
-            return Err(ErrorCode::InvalidAssetId.into());
-        }
-    }
-    msg!(" asset_id, val_be8, null, root, new_leaf {:?}\n, {:?}\n, {:?}\n, {:?}\n, {:?}\n", asset_id, val_be8, null, root, new_leaf);
-    msg!("inpust arr: {:?}", inputs_arr);
-    let _ = proof_verification(proof, &WITHDRAW_AND_ADD_VERIFYINGKEY, inputs_arr);
+    require!(inputs.asset_id == [0u8; 32], ErrorCode::InvalidAssetId);
+
+    let inputs_arr: [[u8; 32]; 5] = inputs.to_field_elements();
+    proof_verification(proof, &WITHDRAW_AND_ADD_VERIFYINGKEY, &inputs_arr)?;
     msg!("Verification for withdraw and add leaf succeded");
-    Ok((val_be8, null, root, new_leaf))
+    Ok(())
 }
 
-pub fn verify_withdraw_on_behalf(
-    proof: &[u8; 256],
-    public_inputs: &[u8],
-) -> Result<([u8; 8], [u8; 32], [u8; 32], [u8; 32])> {
-    // Validate input length: nullifier(32) + amount(8) + root(32) + withdrawer_pubkey(32) = 104 bytes
-    if public_inputs.len() != 104 {
-        msg!("Invalid public inputs length: {} (expected 104)", public_inputs.len());
-        return Err(ErrorCode::InvalidArgument.into());
-    }
-
-    // Extract components based on JavaScript packing: [nullifier||amount||root||withdrawer_pubkey]
-    let null: [u8; 32] = public_inputs[0..32].try_into().expect("Failed to extract nullifier");
-    let amount_be8: [u8; 8] = public_inputs[32..40].try_into().expect("Failed to extract amount");
-    let root: [u8; 32] = public_inputs[40..72].try_into().expect("Failed to extract root");
-    let withdrawer_bytes: [u8; 32] = public_inputs[72..104].try_into().expect("Failed to extract withdrawer");
-
-    msg!("Amount bytes (8): {:?}", amount_be8);
-    let amount_u64 = u64::from_be_bytes(amount_be8);
-    msg!("Amount as u64: {}", amount_u64);
-
-    // Allocate a 32-byte buffer, zero-initialized for the amount
-    let mut amount_be = [0u8; 32];
-    // copy your 8 bytes into the *right* end of the 32-byte buffer
-    amount_be[32 - 8..].copy_from_slice(&amount_be8);
+/// Fails closed, the circuit has no key from a ceremony yet
+#[cfg(not(feature = "dev-keys"))]
+pub fn verify_nullifier_insertion(
+    _proof: &[u8; 256],
+    _inputs: &IndexedInsertInputs,
+) -> Result<()> {
+    err!(ErrorCode::CircuitNotDeployed)
+}
 
-    let inputs_arr: &[[u8; 32]; 4] = &[amount_be, null, withdrawer_bytes, root];
+/// Withdraw proof that also opens the note in an association set
+#[cfg(feature = "dev-keys")]
+pub fn verify_withdraw_association_proof(
+    proof: &[u8; 256],
+    inputs: &AssociationWithdrawInputs,
+) -> Result<()> {
+    //For SOL this must be 0.
+    require!(inputs.asset_id == [0u8; 32], ErrorCode::InvalidAssetId);
+
+    proof_verification(
+        proof,
+        &WITHDRAW_ASSOCIATION_VK,
+        &inputs.to_field_elements(),
+    )?;
+    msg!("Association withdraw proof successfully verified");
+    Ok(())
+}
 
-    let _ = proof_verification(proof, &WITHDRAW_ON_BEHALF_VK, inputs_arr);
+/// Fails closed, the circuit has no key from a ceremony yet
+#[cfg(not(feature = "dev-keys"))]
+pub fn verify_withdraw_association_proof(
+    _proof: &[u8; 256],
+    _inputs: &AssociationWithdrawInputs,
+) -> Result<()> {
+    err!(ErrorCode::CircuitNotDeployed)
+}
 
-    Ok((amount_be8, null, withdrawer_bytes, root))
+pub fn verify_withdraw_on_behalf(proof: &[u8; 256], inputs: &OnBehalfInputs) -> Result<()> {
+    proof_verification(proof, &WITHDRAW_ON_BEHALF_VK, &inputs.to_field_elements())?;
+    Ok(())
 }
 
-fn proof_verification<const N: usize>(
+/// Verifies a 256 byte proof as laid out by clients: A (not negated) || B || C, all big-endian
+pub fn proof_verification<const N: usize>(
     proof: &[u8; 256],
     verifying_key: &Groth16Verifyingkey,
     public_inputs: &[[u8; 32]; N],
@@ -542,8 +509,7 @@ fn proof_verification<const N: usize>(
     let mut v = Groth16Verifier::new(&proof_a, &proof_b, &proof_c, public_inputs, verifying_key)
         .map_err(|_| ErrorCode::InvalidProof)?;
 
-    let ok = v.verify().map_err(|_| ErrorCode::InvalidProof)?;
-    require!(ok, ErrorCode::InvalidProof);
+    v.verify().map_err(|_| ErrorCode::InvalidProof)?;
     Ok(())
 }
 
@@ -553,49 +519,17 @@ pub fn enforce_sub_batch_memo(
     batch_number: u64,
     expected_leaves: &[[u8; 32]],
 ) -> Result<()> {
-    // Load the first instruction (must be Memo)
-    let mut found = false;
-
-    // let mut memo_ix: Option<Instruction> = None;
-    let mut memoix = None;
-    for i in 0..=2 {
-        if let Ok(memo_ix) = instructions::load_instruction_at_checked(i, sysvar_account) {
-            msg!(
-                "Checking instruction at index {}: {:?}",
-                i,
-                memo_ix.program_id
-            );
-            if memo_ix.program_id == MEMO_PROGRAM_ID {
-                found = true;
-                memoix = Some(memo_ix);
-                msg!("Found memo instruction at index {}", i);
-                break;
-            }
-        }
-    }
-    let memo = memoix.unwrap();
-    require!(found, ErrorCode::MissingMemoInstruction);
-    // Decode base64 payload
-    let memo_str = std::str::from_utf8(&memo.data).map_err(|_| ErrorCode::InvalidMemoUtf8)?;
-    msg!("Translating from utf8 {}", memo_str);
-    let memo_bytes = general_purpose::STANDARD
-        .decode(memo_str)
-        .map_err(|_| ErrorCode::InvalidMemoBase64)?;
-    msg!("Getting memo bytes {:?}", memo_bytes);
+    let memo_ix = find_memo_instruction(sysvar_account, SUB_BATCH_MEMO_LEN)?;
+    let memo = SubBatchMemo::decode(&memo_ix.data)?;
 
-    // Check batch number (big-endian u64)
-    let user_batch = u64::from_be_bytes(memo_bytes[0..8].try_into().unwrap());
     require!(
-        user_batch == batch_number,
+        memo.batch_number == batch_number,
         ErrorCode::InvalidUserBatchNumber
     );
-
-    // Verify each leaf
-    for (i, leaf) in expected_leaves.iter().enumerate() {
-        let start = 8 + i * 32;
-        let slice: [u8; 32] = memo_bytes[start..start + 32].try_into().unwrap();
-        require!(&slice == leaf, ErrorCode::InvalidUserLeaves);
-    }
+    require!(
+        memo.leaves.as_slice() == expected_leaves,
+        ErrorCode::InvalidUserLeaves
+    );
     Ok(())
 }
 
@@ -605,29 +539,14 @@ pub fn enforce_small_tree_memo(
     closed_batch: u64,
     expected_root: [u8; 32],
 ) -> Result<()> {
-    let memo_ix = instructions::load_instruction_at_checked(0, ix_sysvar)?;
+    let memo_ix = find_memo_instruction(ix_sysvar, SMALL_TREE_MEMO_LEN)?;
+    let memo = SmallTreeMemo::decode(&memo_ix.data)?;
 
     require!(
-        memo_ix.program_id == MEMO_PROGRAM_ID,
-        ErrorCode::MissingMemoInstruction
+        memo.closed_batch == closed_batch,
+        ErrorCode::InvalidUserBatchNumber
     );
-
-    let memo_bytes = {
-        let memo_str =
-            core::str::from_utf8(&memo_ix.data).map_err(|_| ErrorCode::InvalidMemoUtf8)?;
-        general_purpose::STANDARD
-            .decode(memo_str)
-            .map_err(|_| ErrorCode::InvalidMemoBase64)?
-    };
-
-    // require!(memo_bytes.len() == 40, ErrorCode::InvalidMemoLength); // 8 + 32
-
-    let bn = u64::from_be_bytes(memo_bytes[0..8].try_into().unwrap());
-    require!(bn == closed_batch, ErrorCode::InvalidUserBatchNumber);
-
-    let root_slice: [u8; 32] = memo_bytes[8..40].try_into().unwrap();
-    require!(root_slice == expected_root, ErrorCode::InvalidSmallTreeRoot);
-
+    require!(memo.root == expected_root, ErrorCode::InvalidSmallTreeRoot);
     Ok(())
 }
 
@@ -465,4 +465,139 @@ pub const WITHDRAW_ON_BEHALF_VK: Groth16Verifyingkey =  Groth16Verifyingkey {
 			29,224,196,113,209,69,56,231,128,4,124,26,199,174,235,186,195,247,228,135,234,34,94,166,11,79,107,58,198,190,1,146,
 		],
 	]
-};
\ No newline at end of file
+};
+
+/// Development key of the native `indexed_insert` circuit (`prover/src/native/indexed.rs`),
+/// from `NativeProver::dev_setup`'s public seed. Anyone can forge proofs for it, so it
+/// only exists with the `dev-keys` feature until a ceremony key replaces it.
+#[cfg(feature = "dev-keys")]
+pub const INDEXED_INSERT_VK: Groth16Verifyingkey = Groth16Verifyingkey {
+	nr_pubinputs: 7,
+
+	vk_alpha_g1: [
+		23,65,163,201,162,167,204,102,39,169,88,146,67,175,72,225,112,125,108,211,127,249,151,33,133,59,5,180,248,32,237,37,
+		19,134,92,33,0,90,131,19,28,79,110,98,191,159,119,148,208,229,133,2,10,51,179,234,10,175,183,43,247,157,112,252,
+	],
+
+	vk_beta_g2: [
+		30,108,72,69,96,152,49,92,253,19,120,223,60,223,91,91,235,156,151,171,150,144,184,213,29,251,78,17,250,53,45,68,
+		43,126,222,170,65,153,130,173,195,88,136,233,152,234,205,63,164,28,207,230,38,86,247,46,18,245,61,40,83,140,251,81,
+		40,28,105,103,218,235,150,11,101,48,248,212,9,210,200,94,248,178,131,98,156,70,24,213,207,200,89,130,38,27,150,186,
+		24,30,16,49,171,6,183,72,59,57,108,110,255,65,7,139,69,224,75,27,111,222,33,0,226,167,92,58,149,190,240,19,
+	],
+
+	vk_gamme_g2: [
+		23,176,188,149,70,39,161,138,153,216,149,169,1,59,137,135,112,3,55,74,170,22,253,14,71,223,84,158,16,50,234,63,
+		45,214,254,79,58,106,67,244,54,251,60,92,209,255,131,150,125,78,198,98,145,55,248,239,40,117,135,39,57,59,118,181,
+		0,185,29,201,197,40,112,176,212,1,187,74,198,144,219,144,250,82,101,197,157,94,173,234,217,151,39,91,217,227,118,93,
+		47,230,11,110,87,169,44,213,23,71,170,81,43,31,14,3,115,210,117,251,238,108,239,94,5,40,240,112,174,235,148,220,
+	],
+
+	vk_delta_g2: [
+		45,157,97,255,209,120,49,121,234,44,102,239,72,25,227,151,58,116,104,119,177,37,149,82,76,42,100,70,149,110,68,113,
+		20,118,208,195,232,235,243,39,226,67,203,11,243,169,243,182,80,195,153,73,254,189,107,166,0,79,126,23,120,202,166,114,
+		5,232,183,39,91,89,251,120,155,184,112,87,106,51,163,116,242,169,195,49,95,66,179,189,216,66,67,184,128,113,35,113,
+		31,17,191,251,45,171,148,218,36,118,111,174,14,153,241,84,87,205,142,125,127,106,141,251,227,251,38,201,167,230,194,221,
+	],
+
+	vk_ic: &[
+		[
+			15,101,72,212,212,116,42,189,162,56,192,240,213,17,196,78,91,132,83,139,211,173,129,247,196,189,82,220,29,129,135,63,
+			26,134,168,132,128,234,151,243,102,204,102,30,169,73,186,84,129,210,84,64,92,81,119,84,192,9,240,185,108,52,47,80,
+		],
+		[
+			30,52,149,241,188,50,125,227,160,125,53,166,120,165,46,168,135,8,125,92,113,125,196,76,255,122,105,106,226,36,87,90,
+			5,20,30,120,90,101,241,126,95,254,226,90,95,65,83,208,230,64,68,190,88,212,183,18,250,112,164,57,189,233,212,217,
+		],
+		[
+			31,222,199,48,101,49,99,59,164,198,27,217,145,219,149,61,211,235,38,191,15,112,139,88,22,92,92,206,143,170,40,149,
+			42,142,226,170,146,254,66,154,215,249,173,177,241,213,187,163,241,31,251,108,17,0,12,15,10,16,131,80,193,142,109,19,
+		],
+		[
+			44,62,189,37,217,0,139,23,166,5,122,70,107,236,207,216,102,179,126,185,239,49,13,186,72,212,149,41,55,42,243,57,
+			17,35,57,196,89,211,80,133,73,115,153,167,111,1,249,60,120,160,165,173,25,37,236,127,132,184,217,73,1,222,162,56,
+		],
+		[
+			7,213,159,181,68,106,202,6,13,141,125,79,129,159,53,17,130,87,179,6,127,45,101,165,232,155,135,11,44,101,220,133,
+			31,55,192,147,209,60,53,68,211,47,177,185,17,57,24,252,231,7,192,52,254,227,243,56,92,59,145,94,211,1,32,13,
+		],
+		[
+			22,202,84,134,211,6,240,246,121,52,41,9,96,128,72,74,134,221,121,143,164,177,146,48,82,169,120,52,122,186,213,139,
+			21,124,115,156,61,177,161,207,222,174,9,16,159,78,168,221,50,40,254,96,99,26,189,208,225,107,78,86,220,87,229,164,
+		],
+		[
+			43,20,233,208,144,16,192,188,71,210,77,252,199,103,37,235,27,28,243,160,119,176,68,55,236,225,147,200,244,213,186,112,
+			34,228,145,60,202,161,210,230,138,123,117,217,146,94,205,37,137,135,40,134,152,152,142,82,123,215,243,41,178,124,29,143,
+		],
+	]
+};
+
+/// Development key of the native `withdraw_association` circuit
+/// (`prover/src/native/association.rs`), from `NativeProver::dev_setup`'s public seed.
+/// Anyone can forge proofs for it, so it only exists with the `dev-keys` feature until a
+/// ceremony key replaces it.
+#[cfg(feature = "dev-keys")]
+pub const WITHDRAW_ASSOCIATION_VK: Groth16Verifyingkey = Groth16Verifyingkey {
+	nr_pubinputs: 8,
+
+	vk_alpha_g1: [
+		23,65,163,201,162,167,204,102,39,169,88,146,67,175,72,225,112,125,108,211,127,249,151,33,133,59,5,180,248,32,237,37,
+		19,134,92,33,0,90,131,19,28,79,110,98,191,159,119,148,208,229,133,2,10,51,179,234,10,175,183,43,247,157,112,252,
+	],
+
+	vk_beta_g2: [
+		30,108,72,69,96,152,49,92,253,19,120,223,60,223,91,91,235,156,151,171,150,144,184,213,29,251,78,17,250,53,45,68,
+		43,126,222,170,65,153,130,173,195,88,136,233,152,234,205,63,164,28,207,230,38,86,247,46,18,245,61,40,83,140,251,81,
+		40,28,105,103,218,235,150,11,101,48,248,212,9,210,200,94,248,178,131,98,156,70,24,213,207,200,89,130,38,27,150,186,
+		24,30,16,49,171,6,183,72,59,57,108,110,255,65,7,139,69,224,75,27,111,222,33,0,226,167,92,58,149,190,240,19,
+	],
+
+	vk_gamme_g2: [
+		23,176,188,149,70,39,161,138,153,216,149,169,1,59,137,135,112,3,55,74,170,22,253,14,71,223,84,158,16,50,234,63,
+		45,214,254,79,58,106,67,244,54,251,60,92,209,255,131,150,125,78,198,98,145,55,248,239,40,117,135,39,57,59,118,181,
+		0,185,29,201,197,40,112,176,212,1,187,74,198,144,219,144,250,82,101,197,157,94,173,234,217,151,39,91,217,227,118,93,
+		47,230,11,110,87,169,44,213,23,71,170,81,43,31,14,3,115,210,117,251,238,108,239,94,5,40,240,112,174,235,148,220,
+	],
+
+	vk_delta_g2: [
+		45,157,97,255,209,120,49,121,234,44,102,239,72,25,227,151,58,116,104,119,177,37,149,82,76,42,100,70,149,110,68,113,
+		20,118,208,195,232,235,243,39,226,67,203,11,243,169,243,182,80,195,153,73,254,189,107,166,0,79,126,23,120,202,166,114,
+		5,232,183,39,91,89,251,120,155,184,112,87,106,51,163,116,242,169,195,49,95,66,179,189,216,66,67,184,128,113,35,113,
+		31,17,191,251,45,171,148,218,36,118,111,174,14,153,241,84,87,205,142,125,127,106,141,251,227,251,38,201,167,230,194,221,
+	],
+
+	vk_ic: &[
+		[
+			34,29,195,214,51,177,71,68,199,183,65,53,249,87,93,43,206,235,58,239,35,244,235,62,122,225,194,0,105,76,184,139,
+			7,222,14,3,85,23,11,172,161,85,230,223,121,104,129,133,128,203,132,69,255,89,140,9,65,19,47,13,71,197,33,45,
+		],
+		[
+			35,98,122,3,103,11,194,24,119,98,5,120,106,214,176,115,43,74,255,55,245,37,244,75,32,13,121,148,42,213,114,137,
+			43,53,173,85,94,35,105,33,210,161,244,113,144,24,105,14,133,245,195,33,54,69,14,55,28,76,91,252,241,135,114,114,
+		],
+		[
+			14,25,46,49,97,228,69,8,234,59,104,36,135,75,122,141,17,250,233,226,163,228,58,80,68,172,163,73,140,120,16,251,
+			48,43,123,198,168,138,137,203,38,71,182,33,35,250,180,18,66,233,227,247,23,160,112,31,141,237,82,20,134,4,158,52,
+		],
+		[
+			15,6,203,225,123,145,233,10,138,130,252,76,103,49,93,163,57,177,243,155,117,161,186,196,78,45,125,182,11,71,0,212,
+			23,133,244,178,176,169,94,3,218,83,76,69,184,77,127,122,207,185,162,221,57,75,246,163,124,101,95,52,102,90,105,33,
+		],
+		[
+			45,102,186,178,193,64,116,115,238,91,97,244,125,33,35,140,48,238,221,55,87,146,185,197,119,72,237,14,55,161,53,13,
+			31,27,249,123,108,144,46,31,23,12,65,50,58,116,94,126,30,120,8,166,245,135,116,116,139,201,191,104,17,115,165,77,
+		],
+		[
+			0,244,81,37,214,95,128,110,43,5,238,128,187,181,34,119,76,252,229,127,236,51,161,174,49,204,197,155,81,173,151,22,
+			47,144,42,89,112,1,155,139,227,148,238,62,25,233,58,233,137,116,14,35,226,91,128,228,124,241,150,152,222,247,200,122,
+		],
+		[
+			12,48,94,190,58,85,171,186,177,255,84,76,110,47,71,125,75,252,108,153,86,88,0,240,192,40,200,85,122,161,220,215,
+			47,116,209,18,84,213,180,98,246,118,76,233,180,127,222,144,144,73,86,45,2,245,103,153,201,184,97,97,190,187,17,99,
+		],
+		[
+			0,126,215,81,84,195,16,19,70,244,193,24,140,11,245,160,123,141,195,244,157,143,26,54,141,192,92,178,133,86,221,174,
+			30,254,43,27,191,227,124,89,41,97,246,121,151,112,92,165,254,123,108,23,241,221,42,120,229,110,125,9,136,115,34,110,
+		],
+	]
+};
@@ -0,0 +1,239 @@
+//! Association sets: the pool authority approves curators, curators publish roots, and a
+//! withdrawal is only checked against roots still in the registry's history. The
+//! `withdraw_association` circuit is native, its proofs run without circuit artifacts.
+mod common;
+
+use anchor_lang::error::Error;
+use common::circuits::{dev_prover, withdraw_association, Note, Spend};
+use common::tree::ShadowTree;
+use common::*;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_system_interface::instruction as system_instruction;
+use solnado::association::{AssociationRegistry, ASSOCIATION_ROOT_HISTORY, MAX_CURATORS};
+use solnado::error::ErrorCode;
+use solnado::inputs::{amount_to_field, pubkey_to_limbs, AssociationWithdrawInputs, PublicInputs};
+use solnado::shard::POOL_FEE;
+use solnado_prover::native::WithdrawAssociation;
+use solnado_prover::InstructionInputs;
+
+fn root(i: usize) -> [u8; 32] {
+    let mut root = [0u8; 32];
+    root[24..].copy_from_slice(&(i as u64 + 1).to_be_bytes());
+    root
+}
+
+#[test]
+fn history_keeps_the_latest_roots() {
+    let mut registry = AssociationRegistry::new();
+    assert!(!registry.knows(&[0u8; 32]));
+
+    for i in 0..ASSOCIATION_ROOT_HISTORY + 8 {
+        registry.publish(root(i));
+    }
+    let expected: Vec<_> = (8..ASSOCIATION_ROOT_HISTORY + 8).map(root).collect();
+    assert_eq!(registry.roots(), expected);
+    assert!(!registry.knows(&root(7)));
+    assert!(registry.knows(&root(8)));
+    assert!(registry.knows(&root(ASSOCIATION_ROOT_HISTORY + 7)));
+}
+
+#[test]
+fn curator_list_is_bounded() {
+    let mut registry = AssociationRegistry::new();
+    let curators: Vec<_> = (0..MAX_CURATORS).map(|_| Keypair::new().pubkey()).collect();
+    for c in &curators {
+        registry.set_curator(*c, true).unwrap();
+    }
+    // approving twice doesn't take a slot
+    registry.set_curator(curators[0], true).unwrap();
+    assert_eq!(
+        registry
+            .set_curator(Keypair::new().pubkey(), true)
+            .unwrap_err(),
+        Error::from(ErrorCode::TooManyCurators)
+    );
+
+    registry.set_curator(curators[2], false).unwrap();
+    assert!(!registry.is_curator(&curators[2]));
+    assert_eq!(registry.curators().len(), MAX_CURATORS - 1);
+    assert!(curators
+        .iter()
+        .filter(|c| **c != curators[2])
+        .all(|c| registry.is_curator(c)));
+    registry.set_curator(Keypair::new().pubkey(), true).unwrap();
+}
+
+#[test]
+fn association_inputs_keep_circuit_order() {
+    let inputs = AssociationWithdrawInputs {
+        nullifier: [1u8; 32],
+        asset_id: [0u8; 32],
+        amount: 7,
+        root: [3u8; 32],
+        association_root: [4u8; 32],
+        recipient: Keypair::new().pubkey(),
+    };
+    let [hi, lo] = pubkey_to_limbs(&inputs.recipient);
+    assert_eq!(
+        inputs.to_field_elements(),
+        [
+            amount_to_field(7),
+            [0u8; 32],
+            [1u8; 32],
+            [3u8; 32],
+            [4u8; 32],
+            hi,
+            lo
+        ]
+    );
+    // the limbs are the key's halves, below any modulus
+    assert_eq!(hi[..16], [0u8; 16]);
+    assert_eq!(lo[..16], [0u8; 16]);
+    assert_eq!([&hi[16..], &lo[16..]].concat(), inputs.recipient.to_bytes());
+}
+
+#[tokio::test]
+async fn only_approved_curators_publish() {
+    let mut h = Harness::start_with_pool().await;
+    let stranger = Keypair::new();
+    let fund =
+        system_instruction::transfer(&h.ctx.payer.pubkey(), &stranger.pubkey(), 1_000_000_000);
+    h.send(&[fund], &[]).await.unwrap();
+    assert_program_error(
+        h.initialize_association_registry(Some(&stranger)).await,
+        ErrorCode::UnauthorizedAction,
+    );
+    h.initialize_association_registry(None).await.unwrap();
+
+    let curator = Keypair::new();
+    assert_program_error(
+        h.set_association_curator(curator.pubkey(), true, Some(&stranger))
+            .await,
+        ErrorCode::UnauthorizedAction,
+    );
+    assert_program_error(
+        h.publish_association_root(root(0), &curator).await,
+        ErrorCode::UnauthorizedCurator,
+    );
+
+    h.set_association_curator(curator.pubkey(), true, None)
+        .await
+        .unwrap();
+    h.publish_association_root(root(0), &curator).await.unwrap();
+    assert_eq!(h.association_registry().await.roots(), vec![root(0)]);
+
+    h.set_association_curator(curator.pubkey(), false, None)
+        .await
+        .unwrap();
+    assert_program_error(
+        h.publish_association_root(root(1), &curator).await,
+        ErrorCode::UnauthorizedCurator,
+    );
+    // revoking a curator keeps the roots it published
+    assert!(h.association_registry().await.knows(&root(0)));
+}
+
+#[tokio::test]
+async fn withdrawal_needs_a_published_root() {
+    let mut h = Harness::start_with_pool().await;
+    h.initialize_association_registry(None).await.unwrap();
+    let curator = Keypair::new();
+    h.set_association_curator(curator.pubkey(), true, None)
+        .await
+        .unwrap();
+    h.publish_association_root(root(0), &curator).await.unwrap();
+
+    let mut inputs = AssociationWithdrawInputs {
+        nullifier: [1u8; 32],
+        asset_id: [0u8; 32],
+        amount: 1_000_000,
+        root: h.spend_root().await,
+        association_root: root(1),
+        recipient: Keypair::new().pubkey(),
+    };
+    // the proof pays whoever signs as the user
+    let ix = h.association_withdraw_ix([0u8; 256], inputs).await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidWithdrawerKey);
+
+    inputs.recipient = h.user.pubkey();
+    let ix = h.association_withdraw_ix([0u8; 256], inputs).await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::UnknownAssociationRoot);
+
+    inputs.association_root = root(0);
+    inputs.root = [9u8; 32];
+    let ix = h.association_withdraw_ix([0u8; 256], inputs).await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidPublicInputRoot);
+
+    // both roots check out, the proof doesn't
+    inputs.root = h.spend_root().await;
+    let ix = h.association_withdraw_ix([0u8; 256], inputs).await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidProof);
+}
+
+#[tokio::test]
+async fn withdraws_against_a_published_association_root() {
+    let mut h = Harness::start_with_pool().await;
+    h.initialize_association_registry(None).await.unwrap();
+    let curator = Keypair::new();
+    h.set_association_curator(curator.pubkey(), true, None)
+        .await
+        .unwrap();
+
+    let other = Note::random(1_000_000_000);
+    let note = Note::random(2_000_000_000);
+    h.seed_leaf(other.leaf(), other.amount).await;
+    h.seed_leaf(note.leaf(), note.amount).await;
+    // the curator only vouches for the second deposit
+    let mut set = ShadowTree::new();
+    set.insert(note.leaf());
+    h.publish_association_root(set.root(), &curator)
+        .await
+        .unwrap();
+
+    let (siblings, bits) = h.tree.path(1);
+    let spend = Spend {
+        note: &note,
+        siblings,
+        bits,
+    };
+    let user = h.user.pubkey();
+    let witness = withdraw_association(&spend, set.path(0), &user);
+    let proof = dev_prover::<WithdrawAssociation>()
+        .prove(witness, &mut rand::thread_rng())
+        .unwrap();
+    let InstructionInputs::Association(inputs) = proof.instruction_inputs().unwrap() else {
+        panic!("association circuit must produce association inputs");
+    };
+    assert_eq!(inputs.root, h.spend_root().await);
+    assert_eq!(inputs.association_root, set.root());
+    assert_eq!(inputs.nullifier, note.nullifier_hash());
+    assert_eq!(inputs.recipient, user);
+
+    // someone replaying the proof can't redirect it to themselves
+    let thief = Keypair::new();
+    let mut ix = h
+        .association_withdraw_ix(
+            proof.proof,
+            AssociationWithdrawInputs {
+                recipient: thief.pubkey(),
+                ..inputs
+            },
+        )
+        .await;
+    let meta = ix.accounts.iter_mut().find(|m| m.pubkey == user).unwrap();
+    meta.pubkey = thief.pubkey();
+    assert_program_error(h.send(&[ix], &[&thief]).await, ErrorCode::InvalidProof);
+
+    let before = h.lamports(&user).await;
+    let reserve_before = h.lamports(&rent_reserve()).await;
+    let ix = h.association_withdraw_ix(proof.proof, inputs).await;
+    h.send(&[ix], &[]).await.unwrap();
+    assert_eq!(h.lamports(&user).await - before, note.amount - POOL_FEE);
+    assert_eq!(h.lamports(&rent_reserve()).await - reserve_before, POOL_FEE);
+    assert_eq!(h.pool().await.ledger.withdrawn, note.amount - POOL_FEE);
+    h.audit_pool().await.unwrap();
+
+    // the note is spent
+    let ix = h.association_withdraw_ix(proof.proof, inputs).await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::NullifierAlreadyUsed);
+}
@@ -0,0 +1,194 @@
+//! Witnesses for the circom circuits. Signal names and note hashing must match the
+//! sources the artifacts in `SOLNADO_CIRCUITS` were compiled from, keep them here only.
+//! The prover rejects a name or array length its circuit's `.sym` file doesn't have.
+//! The native circuits take typed witnesses instead, built at the end, and prove with
+//! their development keys.
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use num_bigint::{BigInt, Sign};
+use rand::RngCore;
+use solana_poseidon::{hashv, Endianness, Parameters};
+use solana_sdk::pubkey::Pubkey;
+use solnado::inputs::amount_to_field;
+use solnado_prover::native::{
+    IndexedInsert, MerklePath, NativeCircuit, NativeProver, WithdrawAssociation,
+};
+use solnado_prover::Circuit;
+use solnado_sdk::Insertion;
+
+pub type Signals = HashMap<String, Vec<BigInt>>;
+
+fn field(bytes: &[u8; 32]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, bytes)
+}
+
+fn poseidon(inputs: &[&[u8; 32]]) -> [u8; 32] {
+    let inputs: Vec<&[u8]> = inputs.iter().map(|i| i.as_slice()).collect();
+    hashv(Parameters::Bn254X5, Endianness::BigEndian, &inputs)
+        .unwrap()
+        .to_bytes()
+}
+
+/// A leaf preimage: leaf = H(amount, nullifier, asset_id)
+#[derive(Clone, Copy, Debug)]
+pub struct Note {
+    pub amount: u64,
+    pub nullifier: [u8; 32],
+    pub asset_id: [u8; 32],
+}
+
+impl Note {
+    pub fn random(amount: u64) -> Self {
+        let mut nullifier = [0u8; 32];
+        // 31 random bytes always fit in the scalar field
+        rand::thread_rng().fill_bytes(&mut nullifier[1..]);
+        Note {
+            amount,
+            nullifier,
+            asset_id: [0u8; 32],
+        }
+    }
+
+    pub fn leaf(&self) -> [u8; 32] {
+        poseidon(&[
+            &amount_to_field(self.amount),
+            &self.nullifier,
+            &self.asset_id,
+        ])
+    }
+
+    /// The public nullifier the program stores in the shards
+    pub fn nullifier_hash(&self) -> [u8; 32] {
+        poseidon(&[&self.nullifier])
+    }
+
+    fn insert_private(&self, signals: &mut Signals, suffix: &str) {
+        let amount = amount_to_field(self.amount);
+        signals.insert(format!("amount{}", suffix), vec![field(&amount)]);
+        signals.insert(format!("nullifier{}", suffix), vec![field(&self.nullifier)]);
+        signals.insert(format!("assetId{}", suffix), vec![field(&self.asset_id)]);
+    }
+}
+
+/// A note already in the tree, with its merkle path
+pub struct Spend<'a> {
+    pub note: &'a Note,
+    pub siblings: Vec<[u8; 32]>,
+    pub bits: Vec<u8>,
+}
+
+impl Spend<'_> {
+    fn insert_private(&self, signals: &mut Signals, suffix: &str) {
+        self.note.insert_private(signals, suffix);
+        signals.insert(
+            format!("pathElements{}", suffix),
+            self.siblings.iter().map(field).collect(),
+        );
+        signals.insert(
+            format!("pathIndices{}", suffix),
+            self.bits.iter().map(|b| BigInt::from(*b)).collect(),
+        );
+    }
+}
+
+fn public(signals: &mut Signals, name: &str, value: &[u8; 32]) {
+    signals.insert(name.to_string(), vec![field(value)]);
+}
+
+pub fn deposit1(note: &Note) -> Signals {
+    let mut s = Signals::new();
+    note.insert_private(&mut s, "");
+    public(&mut s, "sum", &amount_to_field(note.amount));
+    public(&mut s, "leaf", &note.leaf());
+    s
+}
+
+pub fn deposit2(note1: &Note, note2: &Note) -> Signals {
+    let mut s = Signals::new();
+    note1.insert_private(&mut s, "1");
+    note2.insert_private(&mut s, "2");
+    public(&mut s, "sum", &amount_to_field(note1.amount + note2.amount));
+    public(&mut s, "leaf1", &note1.leaf());
+    public(&mut s, "leaf2", &note2.leaf());
+    s
+}
+
+pub fn withdraw(spend: &Spend, root: &[u8; 32]) -> Signals {
+    let mut s = Signals::new();
+    spend.insert_private(&mut s, "");
+    public(&mut s, "nullifierHash", &spend.note.nullifier_hash());
+    public(&mut s, "root", root);
+    s
+}
+
+pub fn withdraw_on_behalf(spend: &Spend, root: &[u8; 32], withdrawer: &Pubkey) -> Signals {
+    let mut s = withdraw(spend, root);
+    public(&mut s, "withdrawer", &withdrawer.to_bytes());
+    s
+}
+
+pub fn combine1to2(spend: &Spend, out1: &Note, out2: &Note, root: &[u8; 32]) -> Signals {
+    let mut s = withdraw(spend, root);
+    out1.insert_private(&mut s, "Out1");
+    out2.insert_private(&mut s, "Out2");
+    public(&mut s, "leaf1", &out1.leaf());
+    public(&mut s, "leaf2", &out2.leaf());
+    s
+}
+
+pub fn combine2to1(spend1: &Spend, spend2: &Spend, out: &Note, root: &[u8; 32]) -> Signals {
+    let mut s = Signals::new();
+    spend1.insert_private(&mut s, "1");
+    spend2.insert_private(&mut s, "2");
+    public(&mut s, "nullifierHash1", &spend1.note.nullifier_hash());
+    public(&mut s, "nullifierHash2", &spend2.note.nullifier_hash());
+    out.insert_private(&mut s, "Out");
+    public(&mut s, "newLeaf", &out.leaf());
+    public(&mut s, "root", root);
+    s
+}
+
+/// Development keys of a native circuit, set up once per test binary
+pub fn dev_prover<C: NativeCircuit>() -> &'static NativeProver {
+    static ASSOCIATION: OnceLock<NativeProver> = OnceLock::new();
+    static INSERTION: OnceLock<NativeProver> = OnceLock::new();
+    let cell = match C::CIRCUIT {
+        Circuit::WithdrawAssociation => &ASSOCIATION,
+        Circuit::IndexedInsert => &INSERTION,
+        other => panic!("{other:?} isn't native"),
+    };
+    cell.get_or_init(|| NativeProver::dev_setup::<C>().unwrap())
+}
+
+/// `spend` paid to `recipient`, its note also at the end of `association`, a path of the
+/// association tree
+pub fn withdraw_association(
+    spend: &Spend,
+    (siblings, bits): (Vec<[u8; 32]>, Vec<u8>),
+    recipient: &Pubkey,
+) -> WithdrawAssociation {
+    WithdrawAssociation {
+        amount: spend.note.amount,
+        nullifier: spend.note.nullifier,
+        asset_id: spend.note.asset_id,
+        path: MerklePath {
+            siblings: spend.siblings.clone(),
+            bits: spend.bits.clone(),
+        },
+        association_path: MerklePath { siblings, bits },
+        recipient: *recipient,
+    }
+}
+
+pub fn indexed_insert(insertion: &Insertion) -> IndexedInsert {
+    IndexedInsert {
+        nullifier: insertion.inputs.nullifier,
+        next_index: insertion.inputs.next_index,
+        low_index: insertion.inputs.low_index,
+        low_value: insertion.low_leaf.value,
+        low_next_index: insertion.low_leaf.next_index,
+        low_next_value: insertion.low_leaf.next_value,
+        low_path: insertion.low_path.clone(),
+    }
+}
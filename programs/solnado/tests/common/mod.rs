@@ -0,0 +1,1093 @@
+//! In-process harness: boots the program natively under solana-program-test, the memo
+//! program comes with the default SPL programs.
+#![allow(dead_code)]
+
+pub mod circuits;
+pub mod stubs;
+pub mod tree;
+
+use std::path::PathBuf;
+
+use anchor_lang::{AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas};
+use solana_loader_v3_interface::state::UpgradeableLoaderState;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    message::Message,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signature, Signer},
+    sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use solana_sdk_ids::bpf_loader_upgradeable;
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use solnado::association::{derive_association_registry_pda_key, AssociationRegistry};
+use solnado::denylist::{derive_denylist_pda_key, Denylist};
+use solnado::directory::{directory_entries, ShardPrefix};
+use solnado::error::ErrorCode;
+use solnado::inbox::EncryptedNote;
+use solnado::indexed::{derive_nullifier_tree_pda_key, NullifierTree};
+use solnado::inputs::*;
+use solnado::ledger::PoolLedger;
+use solnado::limits::{DepositEpoch, DepositLimits};
+use solnado::memo::{SmallTreeMemo, SubBatchMemo};
+use solnado::migrate::POOL_VERSION;
+use solnado::nullifier::{derive_nullifier_pda_key, NullifierStoreKind};
+use solnado::registry::{
+    derive_pool_entry_pda_key, derive_pool_registry_pda_key, PoolEntry, PoolRegistry,
+};
+use solnado::reserve::derive_rent_reserve_pda_key;
+use solnado::shard::{
+    derive_shard_pda_key, prefix_bit, BitShard, PREFIX_BYTES, SHARD_SPACE,
+    SHARD_SPLITTING_THRESHOLD,
+};
+use solnado::state::MerkleMountainRange;
+use solnado::sunset::PoolStatus;
+use solnado::utils::{default_batch_nodes, default_leaves, get_root, MEMO_PROGRAM_ID};
+use solnado::{DEFAULT_LEAF, LEAVES_LENGTH, TARGET_DEPTH_LARGE, TARGET_DEPTH_LARGE_ARRAY};
+use solnado_prover::{Circuit, InstructionInputs, Provers, SerializedProof};
+use solnado_sdk::memo::{memo_ix, required_memos};
+use solnado_sdk::shard::{directory_address, resolve_shard, ShardAccounts};
+use solnado_sdk::stealth::StealthKeypair;
+
+use self::tree::ShadowTree;
+
+pub const POOL_ID: [u8; 16] = *b"harness_pool\0\0\0\0";
+pub const USER_FUNDS: u64 = 100_000_000_000;
+
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // anchor's entry wants accounts living as long as their data borrows
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    solnado::entry(program_id, accounts, data)
+}
+
+pub fn pool_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"variable_pool", &POOL_ID], &solnado::ID).0
+}
+
+pub fn leaves_indexer() -> Pubkey {
+    Pubkey::find_program_address(&[b"leaves_indexer", &POOL_ID], &solnado::ID).0
+}
+
+pub fn subtree_indexer() -> Pubkey {
+    Pubkey::find_program_address(&[b"subtree_indexer", &POOL_ID], &solnado::ID).0
+}
+
+pub fn nullifier_pda(nullifier: &[u8; 32]) -> Pubkey {
+    derive_nullifier_pda_key(POOL_ID, nullifier).0
+}
+
+/// Pool state as `initialize_variable_pool` leaves it, with no creator or caps
+pub fn empty_pool() -> MerkleMountainRange {
+    let mut pool = MerkleMountainRange {
+        merkle_root_batch: get_root(&default_leaves()),
+        batch_leaves: default_leaves(),
+        identifier: [0u8; 16],
+        min_deposit_amount: 0,
+        whole_tree_root: DEFAULT_LEAF,
+        last_small_tree_root: DEFAULT_LEAF,
+        batch_number: 0,
+        peaks: [DEFAULT_LEAF; TARGET_DEPTH_LARGE_ARRAY],
+        depth: [0; TARGET_DEPTH_LARGE_ARRAY],
+        number_of_peaks: 0,
+        max_leaves: 1 << TARGET_DEPTH_LARGE,
+        nullifier_store: NullifierStoreKind::Sharded,
+        shard_split_threshold: 0,
+        ledger: PoolLedger::default(),
+        authority: Pubkey::default(),
+        deposit_limits: DepositLimits::default(),
+        deposit_epoch: DepositEpoch::default(),
+        batch_nodes: default_batch_nodes(),
+        deep_root: DEFAULT_LEAF,
+        version: POOL_VERSION,
+        status: PoolStatus::Active,
+        sunset_at: 0,
+    };
+    pool.refresh_deep_root();
+    pool
+}
+
+/// `bits` (one 0/1 per entry) packed MSB first, as the program stores prefixes
+pub fn pack_bits(bits: &[u8]) -> [u8; PREFIX_BYTES] {
+    let mut prefix = [0u8; PREFIX_BYTES];
+    for (i, bit) in bits.iter().enumerate() {
+        prefix[i / 8] |= bit << (7 - i % 8);
+    }
+    prefix
+}
+
+/// Shard for the prefix `bits`, one 0/1 per entry
+pub fn shard_pda(bits: &[u8]) -> Pubkey {
+    derive_shard_pda_key(POOL_ID, &pack_bits(bits), bits.len() as u8).0
+}
+
+pub fn shard_directory() -> Pubkey {
+    directory_address(POOL_ID)
+}
+
+pub fn rent_reserve() -> Pubkey {
+    derive_rent_reserve_pda_key(POOL_ID).0
+}
+
+/// The program's upgrade state, as the upgradeable loader keeps it
+pub fn program_data() -> Pubkey {
+    Pubkey::find_program_address(&[solnado::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+pub fn migrate_pool_ix(admin: Option<Pubkey>, authority: Option<Pubkey>) -> Instruction {
+    Instruction {
+        program_id: solnado::ID,
+        accounts: solnado::accounts::MigratePool {
+            pool: pool_pda(),
+            rent_reserve: rent_reserve(),
+            admin,
+            program_data: admin.map(|_| program_data()),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: solnado::instruction::MigratePool {
+            identifier: POOL_ID,
+            authority,
+        }
+        .data(),
+    }
+}
+
+pub fn association_registry() -> Pubkey {
+    derive_association_registry_pda_key(POOL_ID).0
+}
+
+pub fn denylist() -> Pubkey {
+    derive_denylist_pda_key(POOL_ID).0
+}
+
+pub fn pool_registry() -> Pubkey {
+    derive_pool_registry_pda_key().0
+}
+
+pub fn pool_entry(identifier: [u8; 16]) -> Pubkey {
+    derive_pool_entry_pda_key(identifier).0
+}
+
+/// Memo the program expects when a sub batch fills up: batch number (BE) || leaves
+pub fn sub_batch_memo(batch_number: u64, leaves: &[[u8; 32]]) -> Instruction {
+    raw_memo(
+        SubBatchMemo {
+            batch_number,
+            leaves: leaves.try_into().expect("a sub batch is 8 leaves"),
+        }
+        .encode(),
+    )
+}
+
+/// Memo the program expects on the first insertion after a small tree closed
+pub fn small_tree_memo(closed_batch: u64, root: &[u8; 32]) -> Instruction {
+    raw_memo(
+        SmallTreeMemo {
+            closed_batch,
+            root: *root,
+        }
+        .encode(),
+    )
+}
+
+/// Memo instruction carrying `data` as is, for malformed memos
+pub fn raw_memo(data: Vec<u8>) -> Instruction {
+    Instruction {
+        program_id: MEMO_PROGRAM_ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+pub fn custom_error(code: ErrorCode) -> u32 {
+    code.into()
+}
+
+/// Asserts the transaction failed with the given program error
+pub fn assert_program_error(res: Result<(), BanksClientError>, code: ErrorCode) {
+    match res {
+        Err(BanksClientError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(c),
+        ))) => assert_eq!(c, custom_error(code), "expected {:?}", code),
+        other => panic!("expected {:?}, got {:?}", code, other),
+    }
+}
+
+/// Artifacts directory for the real proof tests, which are ignored unless asked for
+pub fn circuits_dir() -> PathBuf {
+    std::env::var_os("SOLNADO_CIRCUITS")
+        .map(PathBuf::from)
+        .expect("SOLNADO_CIRCUITS must point to the circuit artifacts")
+}
+
+pub struct Harness {
+    pub ctx: ProgramTestContext,
+    pub user: Keypair,
+    /// Nullifier store the pool is created with
+    pub store: NullifierStoreKind,
+    /// Every leaf the pool accepted, in order
+    pub tree: ShadowTree,
+}
+
+impl Harness {
+    pub async fn start() -> Self {
+        let program = ProgramTest::new("solnado", solnado::ID, processor!(process_instruction));
+        let ctx = program.start_with_context().await;
+        stubs::capture_log_data();
+
+        let user = Keypair::new();
+        let fund = system_instruction::transfer(&ctx.payer.pubkey(), &user.pubkey(), USER_FUNDS);
+        let mut harness = Harness {
+            ctx,
+            user,
+            store: NullifierStoreKind::Sharded,
+            tree: ShadowTree::new(),
+        };
+        harness.send(&[fund], &[]).await.unwrap();
+        harness
+    }
+
+    /// Pool and both depth one shards
+    pub async fn start_with_pool() -> Self {
+        Self::start_with_store(NullifierStoreKind::Sharded).await
+    }
+
+    /// Pool recording nullifiers in `store`, with its shards if sharded
+    pub async fn start_with_store(store: NullifierStoreKind) -> Self {
+        let mut h = Self::start().await;
+        h.store = store;
+        h.initialize_pool().await.unwrap();
+        if store == NullifierStoreKind::Sharded {
+            h.initialize_shards(SHARD_SPLITTING_THRESHOLD as u16)
+                .await
+                .unwrap();
+        }
+        h
+    }
+
+    /// Sharded pool whose shards split past `split_threshold` nullifiers
+    pub async fn start_with_split_threshold(split_threshold: u16) -> Self {
+        let mut h = Self::start().await;
+        h.initialize_pool().await.unwrap();
+        h.initialize_shards(split_threshold).await.unwrap();
+        h
+    }
+
+    /// Signed by the context payer (fee payer), the user when an instruction needs it and
+    /// `extra`
+    async fn transaction(&mut self, ixs: &[Instruction], extra: &[&Keypair]) -> Transaction {
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+
+        let mut signers: Vec<&Keypair> = vec![&self.ctx.payer];
+        if ixs
+            .iter()
+            .flat_map(|ix| ix.accounts.iter())
+            .any(|m| m.pubkey == self.user.pubkey() && m.is_signer)
+        {
+            signers.push(&self.user);
+        }
+        signers.extend_from_slice(extra);
+
+        Transaction::new_signed_with_payer(ixs, Some(&self.ctx.payer.pubkey()), &signers, blockhash)
+    }
+
+    /// Signs with the context payer (fee payer), the user and `extra`
+    pub async fn send(
+        &mut self,
+        ixs: &[Instruction],
+        extra: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let tx = self.transaction(ixs, extra).await;
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// `send`, returning the transaction's log lines
+    pub async fn send_with_logs(
+        &mut self,
+        ixs: &[Instruction],
+        extra: &[&Keypair],
+    ) -> Result<Vec<String>, BanksClientError> {
+        let (result, logs) = self.send_for_logs(ixs, extra).await?;
+        result?;
+        Ok(logs)
+    }
+
+    /// `send`, returning the transaction's outcome and log lines, failed or not
+    pub async fn send_for_logs(
+        &mut self,
+        ixs: &[Instruction],
+        extra: &[&Keypair],
+    ) -> Result<(Result<(), TransactionError>, Vec<String>), BanksClientError> {
+        let tx = self.transaction(ixs, extra).await;
+        let res = self
+            .ctx
+            .banks_client
+            .process_transaction_with_metadata(tx)
+            .await?;
+        let logs = res
+            .metadata
+            .map(|m| {
+                m.log_messages
+                    .into_iter()
+                    .map(stubs::program_data)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok((res.result, logs))
+    }
+
+    /// Paid for and signed by a one-time stealth address alone
+    pub async fn send_as_stealth(
+        &mut self,
+        ixs: &[Instruction],
+        keypair: &StealthKeypair,
+    ) -> Result<(), BanksClientError> {
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let message = Message::new_with_blockhash(ixs, Some(&keypair.pubkey()), &blockhash);
+        let signature = Signature::from(keypair.sign(&message.serialize()));
+        let tx = Transaction {
+            signatures: vec![signature],
+            message,
+        };
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn account(&mut self, key: &Pubkey) -> Option<Account> {
+        self.ctx.banks_client.get_account(*key).await.unwrap()
+    }
+
+    pub async fn lamports(&mut self, key: &Pubkey) -> u64 {
+        self.account(key).await.map(|a| a.lamports).unwrap_or(0)
+    }
+
+    pub async fn pool(&mut self) -> MerkleMountainRange {
+        let account = self.account(&pool_pda()).await.expect("pool exists");
+        MerkleMountainRange::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn shard(&mut self, bits: &[u8]) -> Option<BitShard> {
+        let account = self.account(&shard_pda(bits)).await?;
+        let body = account.data.get(8..SHARD_SPACE)?;
+        Some(bytemuck::pod_read_unaligned(body))
+    }
+
+    /// Overwrites the pool account, to jump to states that take too many transactions
+    pub async fn set_pool(&mut self, pool: &MerkleMountainRange) {
+        let key = pool_pda();
+        let mut account = self.account(&key).await.expect("pool exists");
+        let mut data = Vec::with_capacity(account.data.len());
+        pool.try_serialize(&mut data).unwrap();
+        account.data[..data.len()].copy_from_slice(&data);
+        self.ctx
+            .set_account(&key, &AccountSharedData::from(account));
+    }
+
+    /// Root a spend proof must commit to, the in-flight batch folded into the peaks and
+    /// deepened, as the pool caches it
+    pub async fn spend_root(&mut self) -> [u8; 32] {
+        self.pool().await.deep_root
+    }
+
+    pub async fn initialize_pool(&mut self) -> Result<(), BanksClientError> {
+        self.initialize_named_pool(POOL_ID, "harness_pool", false, None)
+            .await
+    }
+
+    /// Pool `identifier` in `self.store` mode, created by `creator` (the user when None).
+    /// Creates the pool registry first if needed.
+    pub async fn initialize_named_pool(
+        &mut self,
+        identifier: [u8; 16],
+        name: &str,
+        namespaced: bool,
+        creator: Option<&Keypair>,
+    ) -> Result<(), BanksClientError> {
+        if self.account(&pool_registry()).await.is_none() {
+            self.initialize_pool_registry().await?;
+        }
+        let pda = |seed: &[u8]| Pubkey::find_program_address(&[seed, &identifier], &solnado::ID).0;
+        let authority = creator.map_or(self.user.pubkey(), |k| k.pubkey());
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::InitializeVariablePool {
+                pool: pda(b"variable_pool"),
+                leaves_indexer: pda(b"leaves_indexer"),
+                subtree_indexer: pda(b"subtree_indexer"),
+                rent_reserve: pda(b"rent_reserve"),
+                pool_registry: pool_registry(),
+                pool_entry: pool_entry(identifier),
+                authority,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::InitializeVariablePool {
+                identifier,
+                nullifier_store: self.store,
+                name: name.to_string(),
+                namespaced,
+            }
+            .data(),
+        };
+        self.send(&[ix], creator.as_slice()).await
+    }
+
+    pub async fn initialize_pool_registry(&mut self) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::InitializePoolRegistry {
+                pool_registry: pool_registry(),
+                payer: self.ctx.payer.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::InitializePoolRegistry {}.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    pub async fn pool_registry(&mut self) -> Option<PoolRegistry> {
+        let account = self.account(&pool_registry()).await?;
+        Some(PoolRegistry::try_deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    pub async fn pool_entry(&mut self, identifier: [u8; 16]) -> Option<PoolEntry> {
+        let account = self.account(&pool_entry(identifier)).await?;
+        Some(PoolEntry::try_deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    pub async fn migrate_pool(&mut self) -> Result<(), BanksClientError> {
+        let ix = migrate_pool_ix(None, None);
+        self.send(&[ix], &[]).await
+    }
+
+    /// Migration signed by `admin`, handing the pool `authority`
+    pub async fn migrate_legacy_pool(
+        &mut self,
+        admin: &Keypair,
+        authority: Option<Pubkey>,
+    ) -> Result<(), BanksClientError> {
+        let ix = migrate_pool_ix(Some(admin.pubkey()), authority);
+        self.send(&[ix], &[admin]).await
+    }
+
+    /// Makes `upgrade_authority` the program's upgrade authority. The program runs
+    /// natively, only its program data account is written.
+    pub fn set_upgrade_authority(&mut self, upgrade_authority: Pubkey) {
+        let state = UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(upgrade_authority),
+        };
+        let account = AccountSharedData::new_data(
+            Rent::default().minimum_balance(UpgradeableLoaderState::size_of_programdata_metadata()),
+            &state,
+            &bpf_loader_upgradeable::ID,
+        )
+        .unwrap();
+        self.ctx.set_account(&program_data(), &account);
+    }
+
+    pub fn sunset_pool_ix(&self, authority: Pubkey) -> Instruction {
+        Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::SunsetPool {
+                pool: pool_pda(),
+                authority,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::SunsetPool {}.data(),
+        }
+    }
+
+    /// Signed by `authority`, the user when None
+    pub async fn sunset_pool(
+        &mut self,
+        authority: Option<&Keypair>,
+    ) -> Result<(), BanksClientError> {
+        let signer = authority.map_or(self.user.pubkey(), |k| k.pubkey());
+        let ix = self.sunset_pool_ix(signer);
+        self.send(&[ix], authority.as_slice()).await
+    }
+
+    /// Signed by the user, closing the empty `reclaim` accounts
+    pub fn close_pool_ix(&self, reclaim: &[Pubkey]) -> Instruction {
+        let mut accounts = solnado::accounts::ClosePool {
+            pool: pool_pda(),
+            authority: self.user.pubkey(),
+        }
+        .to_account_metas(None);
+        accounts.extend(reclaim.iter().map(|key| AccountMeta::new(*key, false)));
+        Instruction {
+            program_id: solnado::ID,
+            accounts,
+            data: solnado::instruction::ClosePool {}.data(),
+        }
+    }
+
+    pub async fn close_pool(&mut self, reclaim: &[Pubkey]) -> Result<(), BanksClientError> {
+        let ix = self.close_pool_ix(reclaim);
+        self.send(&[ix], &[]).await
+    }
+
+    /// Moves the clock's unix timestamp `seconds` ahead
+    pub async fn advance_clock(&mut self, seconds: i64) {
+        let mut clock: Clock = self.ctx.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    /// Signed by `authority`, the user when None
+    pub async fn set_deposit_limits(
+        &mut self,
+        limits: DepositLimits,
+        authority: Option<&Keypair>,
+    ) -> Result<(), BanksClientError> {
+        let signer = authority.map_or(self.user.pubkey(), |k| k.pubkey());
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::SetDepositLimits {
+                pool: pool_pda(),
+                authority: signer,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::SetDepositLimits { limits }.data(),
+        };
+        self.send(&[ix], authority.as_slice()).await
+    }
+
+    pub fn audit_pool_ix(&self) -> Instruction {
+        Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::AuditPool {
+                pool: pool_pda(),
+                rent_reserve: rent_reserve(),
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::AuditPool {}.data(),
+        }
+    }
+
+    pub async fn audit_pool(&mut self) -> Result<(), BanksClientError> {
+        let ix = self.audit_pool_ix();
+        self.send(&[ix], &[]).await
+    }
+
+    pub async fn fund_rent_reserve(&mut self, amount: u64) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::FundRentReserve {
+                pool: pool_pda(),
+                rent_reserve: rent_reserve(),
+                funder: self.user.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::FundRentReserve { amount }.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    pub async fn initialize_nullifier_tree(&mut self) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::InitializeNullifierTree {
+                pool: pool_pda(),
+                nullifier_tree: derive_nullifier_tree_pda_key(POOL_ID).0,
+                authority: self.user.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::InitializeNullifierTree {}.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    pub async fn nullifier_tree(&mut self) -> NullifierTree {
+        let account = self
+            .account(&derive_nullifier_tree_pda_key(POOL_ID).0)
+            .await
+            .expect("nullifier tree exists");
+        NullifierTree::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// `nullifier_insertion_proof`, to send ahead of an indexed pool's spend
+    pub fn insertion_proof_ix(&self, proof: [u8; 256], inputs: IndexedInsertInputs) -> Instruction {
+        Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::CarryInsertionProof {}.to_account_metas(None),
+            data: solnado::instruction::NullifierInsertionProof { proof, inputs }.data(),
+        }
+    }
+
+    pub async fn association_registry(&mut self) -> AssociationRegistry {
+        let account = self
+            .account(&association_registry())
+            .await
+            .expect("association registry exists");
+        AssociationRegistry::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// Signed by `authority`, the user when None
+    pub async fn initialize_association_registry(
+        &mut self,
+        authority: Option<&Keypair>,
+    ) -> Result<(), BanksClientError> {
+        let signer = authority.map_or(self.user.pubkey(), |k| k.pubkey());
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::InitializeAssociationRegistry {
+                pool: pool_pda(),
+                association_registry: association_registry(),
+                authority: signer,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::InitializeAssociationRegistry {}.data(),
+        };
+        self.send(&[ix], authority.as_slice()).await
+    }
+
+    pub async fn denylist(&mut self) -> Option<Denylist> {
+        let account = self.account(&denylist()).await?;
+        Some(Denylist::try_deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    /// Signed by `authority`, the user when None
+    pub async fn initialize_denylist(
+        &mut self,
+        authority: Option<&Keypair>,
+    ) -> Result<(), BanksClientError> {
+        let signer = authority.map_or(self.user.pubkey(), |k| k.pubkey());
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::InitializeDenylist {
+                pool: pool_pda(),
+                denylist: denylist(),
+                authority: signer,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::InitializeDenylist {}.data(),
+        };
+        self.send(&[ix], authority.as_slice()).await
+    }
+
+    /// Signed by `authority`, the user when None
+    pub async fn update_denylist(
+        &mut self,
+        add: Vec<Pubkey>,
+        remove: Vec<Pubkey>,
+        authority: Option<&Keypair>,
+    ) -> Result<(), BanksClientError> {
+        let signer = authority.map_or(self.user.pubkey(), |k| k.pubkey());
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::UpdateDenylist {
+                pool: pool_pda(),
+                denylist: denylist(),
+                authority: signer,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::UpdateDenylist { add, remove }.data(),
+        };
+        self.send(&[ix], authority.as_slice()).await
+    }
+
+    /// Signed by `authority`, the user when None
+    pub async fn set_association_curator(
+        &mut self,
+        curator: Pubkey,
+        approved: bool,
+        authority: Option<&Keypair>,
+    ) -> Result<(), BanksClientError> {
+        let signer = authority.map_or(self.user.pubkey(), |k| k.pubkey());
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::SetAssociationCurator {
+                pool: pool_pda(),
+                association_registry: association_registry(),
+                authority: signer,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::SetAssociationCurator { curator, approved }.data(),
+        };
+        self.send(&[ix], authority.as_slice()).await
+    }
+
+    pub async fn publish_association_root(
+        &mut self,
+        root: [u8; 32],
+        curator: &Keypair,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::PublishAssociationRoot {
+                pool: pool_pda(),
+                association_registry: association_registry(),
+                curator: curator.pubkey(),
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::PublishAssociationRoot { root }.data(),
+        };
+        self.send(&[ix], &[curator]).await
+    }
+
+    pub async fn initialize_shards(
+        &mut self,
+        split_threshold: u16,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::InitializeNullifierShards {
+                pool: pool_pda(),
+                shard0: shard_pda(&[0]),
+                shard1: shard_pda(&[1]),
+                shard_directory: shard_directory(),
+                authority: self.user.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::InitializeShards { split_threshold }.data(),
+        };
+        self.send(&[ix], &[]).await
+    }
+
+    fn with_indexers(mut metas: Vec<AccountMeta>) -> Vec<AccountMeta> {
+        metas.push(AccountMeta::new_readonly(leaves_indexer(), false));
+        metas.push(AccountMeta::new_readonly(subtree_indexer(), false));
+        metas
+    }
+
+    pub fn deposit_ix(&self, proof: [u8; 256], inputs: DepositInputs) -> Instruction {
+        self.deposit_ix_with_notes(proof, inputs, vec![])
+    }
+
+    /// Deposit publishing `notes` next to its leaves
+    pub fn deposit_ix_with_notes(
+        &self,
+        proof: [u8; 256],
+        inputs: DepositInputs,
+        notes: Vec<Option<EncryptedNote>>,
+    ) -> Instruction {
+        Instruction {
+            program_id: solnado::ID,
+            accounts: Self::with_indexers(
+                solnado::accounts::DepositVariable {
+                    pool: pool_pda(),
+                    depositor: self.user.pubkey(),
+                    denylist: denylist(),
+                    instruction_account: sysvar::instructions::ID,
+                    system_program: system_program::ID,
+                }
+                .to_account_metas(None),
+            ),
+            data: solnado::instruction::DepositVariable {
+                proof,
+                inputs,
+                notes,
+            }
+            .data(),
+        }
+    }
+
+    /// Record and split children the pool's store needs for `nullifier`
+    pub async fn nullifier_accounts(&mut self, nullifier: &[u8; 32]) -> (Pubkey, Pubkey, Pubkey) {
+        match self.store {
+            NullifierStoreKind::Sharded => {
+                let accounts = self.resolve_shard(nullifier).await;
+                (accounts.shard, accounts.child0, accounts.child1)
+            }
+            NullifierStoreKind::PerNullifier => {
+                let pda = nullifier_pda(nullifier);
+                (pda, pda, pda)
+            }
+            NullifierStoreKind::Indexed => {
+                let tree = derive_nullifier_tree_pda_key(POOL_ID).0;
+                (tree, tree, tree)
+            }
+        }
+    }
+
+    /// Shard accounts of `nullifier` from the pool's directory, as a client finds them
+    pub async fn resolve_shard(&mut self, nullifier: &[u8; 32]) -> ShardAccounts {
+        let directory = self
+            .account(&shard_directory())
+            .await
+            .expect("shard directory created");
+        resolve_shard(POOL_ID, &directory.data, nullifier).unwrap()
+    }
+
+    /// Live shard prefixes listed in the directory
+    pub async fn directory(&mut self) -> Vec<ShardPrefix> {
+        let directory = self
+            .account(&shard_directory())
+            .await
+            .expect("shard directory created");
+        directory_entries(&directory.data).unwrap().to_vec()
+    }
+
+    pub async fn withdraw_ix(
+        &mut self,
+        mode: u8,
+        proof: [u8; 256],
+        inputs: WithdrawInputs,
+    ) -> Instruction {
+        let (record, child0, child1) = self.nullifier_accounts(&inputs.nullifier).await;
+        Instruction {
+            program_id: solnado::ID,
+            accounts: Self::with_indexers(
+                solnado::accounts::WithdrawVariableShard {
+                    pool: pool_pda(),
+                    nullifier_shard: record,
+                    user: self.user.pubkey(),
+                    instruction_account: sysvar::instructions::ID,
+                    system_program: system_program::ID,
+                    dummy0_account: child0,
+                    dummy1_account: child1,
+                    shard_directory: shard_directory(),
+                    rent_reserve: rent_reserve(),
+                }
+                .to_account_metas(None),
+            ),
+            data: solnado::instruction::WithdrawVariableShard {
+                mode,
+                proof,
+                inputs,
+                notes: vec![],
+            }
+            .data(),
+        }
+    }
+
+    pub async fn association_withdraw_ix(
+        &mut self,
+        proof: [u8; 256],
+        inputs: AssociationWithdrawInputs,
+    ) -> Instruction {
+        let (record, child0, child1) = self.nullifier_accounts(&inputs.nullifier).await;
+        Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::WithdrawWithAssociation {
+                pool: pool_pda(),
+                association_registry: association_registry(),
+                nullifier_shard: record,
+                user: self.user.pubkey(),
+                instruction_account: sysvar::instructions::ID,
+                system_program: system_program::ID,
+                dummy0_account: child0,
+                dummy1_account: child1,
+                shard_directory: shard_directory(),
+                rent_reserve: rent_reserve(),
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::WithdrawWithAssociation { proof, inputs }.data(),
+        }
+    }
+
+    pub async fn on_behalf_ix(
+        &mut self,
+        payer: &Pubkey,
+        proof: [u8; 256],
+        inputs: OnBehalfInputs,
+        stealth_ephemeral: Option<[u8; 32]>,
+    ) -> Instruction {
+        let (record, child0, child1) = self.nullifier_accounts(&inputs.nullifier).await;
+        Instruction {
+            program_id: solnado::ID,
+            accounts: solnado::accounts::WithdrawOnBehalfShard {
+                pool: pool_pda(),
+                nullifier_shard: record,
+                withdrawer: inputs.withdrawer,
+                payer: *payer,
+                instruction_account: sysvar::instructions::ID,
+                system_program: system_program::ID,
+                dummy0_account: child0,
+                dummy1_account: child1,
+                shard_directory: shard_directory(),
+                rent_reserve: rent_reserve(),
+            }
+            .to_account_metas(None),
+            data: solnado::instruction::WithdrawOnBehalfShard {
+                proof,
+                inputs,
+                stealth_ephemeral,
+            }
+            .data(),
+        }
+    }
+
+    pub async fn combine_single_ix(
+        &mut self,
+        proof: [u8; 256],
+        inputs: CombineSingleInputs,
+    ) -> Instruction {
+        let (record, child0, child1) = self.nullifier_accounts(&inputs.nullifier).await;
+        Instruction {
+            program_id: solnado::ID,
+            accounts: Self::with_indexers(
+                solnado::accounts::CombineDepositShardSingle {
+                    pool: pool_pda(),
+                    user: self.user.pubkey(),
+                    instruction_account: sysvar::instructions::ID,
+                    system_program: system_program::ID,
+                    nullifier_shard: record,
+                    dummy0_account: child0,
+                    dummy1_account: child1,
+                    shard_directory: shard_directory(),
+                    rent_reserve: rent_reserve(),
+                }
+                .to_account_metas(None),
+            ),
+            data: solnado::instruction::CombineDepositShardSingle {
+                proof,
+                inputs,
+                notes: vec![],
+            }
+            .data(),
+        }
+    }
+
+    pub async fn combine_double_ix(
+        &mut self,
+        proof: [u8; 256],
+        inputs: CombineDoubleInputs,
+    ) -> Instruction {
+        let (record1, child10, child11) = self.nullifier_accounts(&inputs.nullifier1).await;
+        let (record2, child20, child21) = self.nullifier_accounts(&inputs.nullifier2).await;
+        Instruction {
+            program_id: solnado::ID,
+            accounts: Self::with_indexers(
+                solnado::accounts::CombineDepositShardDouble {
+                    pool: pool_pda(),
+                    user: self.user.pubkey(),
+                    instruction_account: sysvar::instructions::ID,
+                    system_program: system_program::ID,
+                    nullifier_shard1: record1,
+                    dummy10_account: child10,
+                    dummy11_account: child11,
+                    nullifier_shard2: record2,
+                    dummy20_account: child20,
+                    dummy21_account: child21,
+                    shard_directory: shard_directory(),
+                    rent_reserve: rent_reserve(),
+                }
+                .to_account_metas(None),
+            ),
+            data: solnado::instruction::CombineDepositShardDouble {
+                same_shard: (record1 == record2) as u8,
+                proof,
+                inputs,
+                notes: vec![],
+            }
+            .data(),
+        }
+    }
+
+    /// Sends `ix`, prefixed with the memo its insertion of `leaves` requires, and mirrors
+    /// the accepted leaves in the shadow tree
+    pub async fn insert_leaves(
+        &mut self,
+        ix: Instruction,
+        leaves: &[[u8; 32]],
+    ) -> Result<(), BanksClientError> {
+        let pool = self.pool().await;
+        let mut ixs: Vec<Instruction> = required_memos(&pool, leaves).iter().map(memo_ix).collect();
+        ixs.push(ix);
+        self.send(&ixs, &[]).await?;
+        for leaf in leaves {
+            self.tree.insert(*leaf);
+        }
+        Ok(())
+    }
+}
+
+/// Shard a nullifier belongs to among the depth one shards
+pub fn shard_of(nullifier: &[u8; 32]) -> Vec<u8> {
+    vec![prefix_bit(nullifier, 0)]
+}
+
+/// Proof and typed inputs for `circuit`, from the artifacts under `SOLNADO_CIRCUITS`
+pub fn prove(
+    provers: &mut Provers,
+    circuit: Circuit,
+    signals: circuits::Signals,
+) -> (SerializedProof, InstructionInputs) {
+    let proof = provers
+        .prove(circuit, signals, &mut rand::thread_rng())
+        .unwrap_or_else(|e| panic!("proving {:?}: {}", circuit, e));
+    let inputs = proof.instruction_inputs().unwrap();
+    (proof, inputs)
+}
+
+impl Harness {
+    /// Jumps to the state right after `BATCHES_PER_SMALL_TREE` batches of `leaf` closed
+    /// the first small tree
+    pub async fn fill_first_small_tree(&mut self, leaf: [u8; 32]) {
+        let mut pool = self.pool().await;
+        let mut root = get_root(&[leaf; 16]);
+        let mut depth = 4;
+        while depth < 16 {
+            root = solana_poseidon::hashv(
+                solana_poseidon::Parameters::Bn254X5,
+                solana_poseidon::Endianness::BigEndian,
+                &[&root, &root],
+            )
+            .unwrap()
+            .to_bytes();
+            depth += 1;
+        }
+        pool.batch_number = solnado::BATCHES_PER_SMALL_TREE;
+        pool.peaks[0] = root;
+        pool.depth[0] = depth;
+        pool.number_of_peaks = 1;
+        pool.whole_tree_root = root;
+        pool.last_small_tree_root = root;
+        pool.refresh_deep_root();
+        self.set_pool(&pool).await;
+
+        self.tree.leaves = vec![leaf; 16 * solnado::BATCHES_PER_SMALL_TREE as usize];
+    }
+
+    /// Jumps to the state right after a deposit of `amount` whose note is `leaf`, for
+    /// spends that don't need a deposit proof. The leaf filling a batch rolls it into the
+    /// peaks, as the deposit would.
+    pub async fn seed_leaf(&mut self, leaf: [u8; 32], amount: u64) {
+        let mut pool = self.pool().await;
+        let index = self.tree.leaves.len() % LEAVES_LENGTH;
+        assert_eq!(
+            pool.batch_number as usize,
+            self.tree.leaves.len() / LEAVES_LENGTH,
+            "seeded pools only"
+        );
+        pool.merkle_root_batch = solnado::utils::insert_into_batch(
+            &mut pool.batch_leaves,
+            &mut pool.batch_nodes,
+            index,
+            leaf,
+        );
+        if index + 1 == LEAVES_LENGTH {
+            // roll_over_batch without its events, which need a running program
+            pool.update_peaks(pool.merkle_root_batch);
+            pool.batch_number += 1;
+            pool.whole_tree_root = pool.compute_root_from_peaks();
+            pool.reset_batch();
+        }
+        pool.refresh_deep_root();
+        pool.ledger.deposit(amount).unwrap();
+        self.set_pool(&pool).await;
+        let fund = system_instruction::transfer(&self.ctx.payer.pubkey(), &pool_pda(), amount);
+        self.send(&[fund], &[]).await.unwrap();
+
+        self.tree.insert(leaf);
+    }
+}
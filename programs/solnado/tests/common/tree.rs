@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use solana_poseidon::{hashv, Endianness, Parameters};
+use solnado::utils::get_default_root_depth;
+use solnado::{DEFAULT_LEAF, TARGET_DEPTH_LARGE};
+
+fn hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hashv(Parameters::Bn254X5, Endianness::BigEndian, &[left, right])
+        .unwrap()
+        .to_bytes()
+}
+
+fn default_node(level: usize) -> [u8; 32] {
+    if level == 0 {
+        DEFAULT_LEAF
+    } else {
+        get_default_root_depth(level)
+    }
+}
+
+/// Plain sparse merkle tree of depth `TARGET_DEPTH_LARGE` holding every leaf the pool
+/// has accepted, in insertion order. Reference for the pool roots and source of the
+/// paths the circuits take.
+#[derive(Clone, Debug, Default)]
+pub struct ShadowTree {
+    /// Level the stored nodes sit at, 0 unless built from batch roots
+    base: usize,
+    pub leaves: Vec<[u8; 32]>,
+}
+
+impl ShadowTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Same tree, starting from the roots of the 16 leaf batches instead of the leaves
+    pub fn from_batch_roots(roots: Vec<[u8; 32]>) -> Self {
+        ShadowTree {
+            base: 4,
+            leaves: roots,
+        }
+    }
+
+    pub fn insert(&mut self, leaf: [u8; 32]) -> usize {
+        self.leaves.push(leaf);
+        self.leaves.len() - 1
+    }
+
+    /// Non default nodes of every level from `base` up
+    fn levels(&self) -> Vec<Vec<[u8; 32]>> {
+        // filled states repeat the same leaf, don't rehash identical pairs
+        let mut cache = HashMap::new();
+        let mut levels = vec![self.leaves.clone()];
+        for level in self.base..TARGET_DEPTH_LARGE {
+            let nodes = &levels[level - self.base];
+            let parents = nodes
+                .chunks(2)
+                .map(|pair| {
+                    let right = pair.get(1).copied().unwrap_or_else(|| default_node(level));
+                    *cache
+                        .entry((pair[0], right))
+                        .or_insert_with(|| hash(&pair[0], &right))
+                })
+                .collect();
+            levels.push(parents);
+        }
+        levels
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels()[TARGET_DEPTH_LARGE - self.base]
+            .first()
+            .copied()
+            .unwrap_or_else(|| default_node(TARGET_DEPTH_LARGE))
+    }
+
+    /// Siblings from the leaf up and the matching left/right bits of `index`
+    pub fn path(&self, index: usize) -> (Vec<[u8; 32]>, Vec<u8>) {
+        assert_eq!(self.base, 0, "paths start from the leaves");
+        assert!(index < self.leaves.len(), "leaf {} not in tree", index);
+        let levels = self.levels();
+        let mut siblings = Vec::with_capacity(TARGET_DEPTH_LARGE);
+        let mut bits = Vec::with_capacity(TARGET_DEPTH_LARGE);
+        let mut i = index;
+        for (level, nodes) in levels.iter().take(TARGET_DEPTH_LARGE).enumerate() {
+            let sibling = nodes
+                .get(i ^ 1)
+                .copied()
+                .unwrap_or_else(|| default_node(level));
+            siblings.push(sibling);
+            bits.push((i & 1) as u8);
+            i >>= 1;
+        }
+        (siblings, bits)
+    }
+}
@@ -0,0 +1,115 @@
+//! Depositor screening: the pool authority keeps the denylist, listed depositors are
+//! turned away with a `DepositDenied` event before any lamports move.
+mod common;
+
+use anchor_lang::error::Error;
+use anchor_lang::Event;
+use base64::{engine::general_purpose, Engine as _};
+use common::*;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solnado::denylist::{Denylist, MAX_DENIED};
+use solnado::error::ErrorCode;
+use solnado::events::DepositDenied;
+use solnado::inputs::DepositInputs;
+use solnado::DEFAULT_LEAF;
+
+fn dummy_deposit(h: &Harness) -> solana_sdk::instruction::Instruction {
+    h.deposit_ix(
+        [0u8; 256],
+        DepositInputs {
+            amount: 10_000_000,
+            leaf1: [7u8; 32],
+            leaf2: DEFAULT_LEAF,
+        },
+    )
+}
+
+#[test]
+fn list_stays_sorted_and_bounded() {
+    let mut list = Denylist::default();
+    let keys: Vec<Pubkey> = (0..MAX_DENIED).map(|_| Pubkey::new_unique()).collect();
+    list.update(&keys.iter().rev().copied().collect::<Vec<_>>(), &[])
+        .unwrap();
+    // listing twice doesn't take a slot
+    list.update(&keys[..4], &[]).unwrap();
+    assert_eq!(list.addresses, keys);
+    assert_eq!(
+        list.update(&[Pubkey::new_unique()], &[]).unwrap_err(),
+        Error::from(ErrorCode::DenylistFull)
+    );
+
+    // removals go first, freeing room for the additions
+    let newcomer = Pubkey::new_unique();
+    list.update(&[newcomer], &[keys[3], Pubkey::new_unique()])
+        .unwrap();
+    assert!(list.contains(&newcomer));
+    assert!(!list.contains(&keys[3]));
+    assert!(list.addresses.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[tokio::test]
+async fn only_the_authority_updates_the_list() {
+    let mut h = Harness::start_with_pool().await;
+    h.initialize_denylist(None).await.unwrap();
+    let stranger = Keypair::new();
+    assert_program_error(
+        h.update_denylist(vec![stranger.pubkey()], vec![], Some(&stranger))
+            .await,
+        ErrorCode::UnauthorizedAction,
+    );
+
+    let flagged = Pubkey::new_unique();
+    h.update_denylist(vec![flagged], vec![], None)
+        .await
+        .unwrap();
+    assert_eq!(h.denylist().await.unwrap().addresses, vec![flagged]);
+    h.update_denylist(vec![], vec![flagged], None)
+        .await
+        .unwrap();
+    assert!(h.denylist().await.unwrap().addresses.is_empty());
+}
+
+#[tokio::test]
+async fn listed_depositor_is_turned_away_with_an_event() {
+    let mut h = Harness::start_with_pool().await;
+    h.initialize_denylist(None).await.unwrap();
+    let depositor = h.user.pubkey();
+    h.update_denylist(vec![depositor], vec![], None)
+        .await
+        .unwrap();
+    let before = h.lamports(&pool_pda()).await;
+
+    let ix = dummy_deposit(&h);
+    let (result, logs) = h.send_for_logs(&[ix], &[]).await.unwrap();
+    assert_program_error(result.map_err(Into::into), ErrorCode::DepositorDenied);
+    let event = DepositDenied {
+        pool: POOL_ID,
+        depositor,
+    };
+    let line = format!(
+        "Program data: {}",
+        general_purpose::STANDARD.encode(event.data())
+    );
+    assert!(logs.contains(&line), "no DepositDenied event in {:?}", logs);
+    assert_eq!(h.lamports(&pool_pda()).await, before);
+}
+
+#[tokio::test]
+async fn unlisted_depositors_pass_the_screen() {
+    let mut h = Harness::start_with_pool().await;
+    // no denylist yet, the dummy proof is the first thing to fail
+    let ix = dummy_deposit(&h);
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidProof);
+
+    h.initialize_denylist(None).await.unwrap();
+    let depositor = h.user.pubkey();
+    h.update_denylist(vec![Pubkey::new_unique(), depositor], vec![], None)
+        .await
+        .unwrap();
+    h.update_denylist(vec![], vec![depositor], None)
+        .await
+        .unwrap();
+    let ix = dummy_deposit(&h);
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidProof);
+}
@@ -0,0 +1,41 @@
+//! Encrypted notes: at most one per output leaf, each within the size the clients' format
+//! needs.
+use anchor_lang::error::Error;
+use solnado::error::ErrorCode;
+use solnado::inbox::{publish_notes, EncryptedNote, MAX_NOTE_CIPHERTEXT};
+
+fn note(len: usize) -> Option<EncryptedNote> {
+    Some(EncryptedNote {
+        ephemeral_key: [1u8; 32],
+        ciphertext: vec![2u8; len],
+    })
+}
+
+#[test]
+fn notes_are_optional_per_leaf() {
+    let leaves = [[3u8; 32], [4u8; 32]];
+    publish_notes(&leaves, &[]).unwrap();
+    publish_notes(&leaves, &[None, note(MAX_NOTE_CIPHERTEXT)]).unwrap();
+    publish_notes(&leaves[..1], &[note(90)]).unwrap();
+}
+
+#[test]
+fn notes_beyond_the_leaves_are_rejected() {
+    assert_eq!(
+        publish_notes(&[[3u8; 32]], &[note(90), None]).unwrap_err(),
+        Error::from(ErrorCode::TooManyNotes)
+    );
+    // withdrawals without change have no leaf to attach a note to
+    assert_eq!(
+        publish_notes(&[], &[note(90)]).unwrap_err(),
+        Error::from(ErrorCode::TooManyNotes)
+    );
+}
+
+#[test]
+fn oversized_note_is_rejected() {
+    assert_eq!(
+        publish_notes(&[[3u8; 32]], &[note(MAX_NOTE_CIPHERTEXT + 1)]).unwrap_err(),
+        Error::from(ErrorCode::NoteTooLarge)
+    );
+}
@@ -0,0 +1,129 @@
+//! Indexed pools: every spend carries an `indexed_insert` proof putting its nullifier in
+//! the pool's nullifier tree, of which the program keeps the root and frontier only. The
+//! insertion and association withdrawal circuits are native, these spends prove for real.
+mod common;
+
+use common::circuits::{dev_prover, indexed_insert, withdraw_association, Note, Spend};
+use common::tree::ShadowTree;
+use common::*;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::{Keypair, Signer};
+use solnado::error::ErrorCode;
+use solnado::nullifier::NullifierStoreKind;
+use solnado::shard::POOL_FEE;
+use solnado_prover::native::{IndexedInsert, WithdrawAssociation};
+use solnado_prover::InstructionInputs;
+use solnado_sdk::{IndexedTree, InsertError};
+
+/// Indexed pool holding `notes`, all in a published association set
+async fn pool_with(notes: &[Note]) -> (Harness, ShadowTree) {
+    let mut h = Harness::start_with_store(NullifierStoreKind::Indexed).await;
+    h.initialize_nullifier_tree().await.unwrap();
+    h.initialize_association_registry(None).await.unwrap();
+    let curator = Keypair::new();
+    h.set_association_curator(curator.pubkey(), true, None)
+        .await
+        .unwrap();
+
+    let mut set = ShadowTree::new();
+    for note in notes {
+        h.seed_leaf(note.leaf(), note.amount).await;
+        set.insert(note.leaf());
+    }
+    h.publish_association_root(set.root(), &curator)
+        .await
+        .unwrap();
+    (h, set)
+}
+
+/// Insertion proof and withdrawal of the note at `index`, as one transaction
+async fn spend_ixs(
+    h: &mut Harness,
+    set: &ShadowTree,
+    nullifiers: &mut IndexedTree,
+    note: &Note,
+    index: usize,
+) -> Vec<Instruction> {
+    let rng = &mut rand::thread_rng();
+    let insertion = nullifiers.insert(&note.nullifier_hash()).unwrap();
+    let carried = dev_prover::<IndexedInsert>()
+        .prove(indexed_insert(&insertion), rng)
+        .unwrap();
+    assert_eq!(
+        carried.instruction_inputs().unwrap(),
+        InstructionInputs::Insertion(insertion.inputs)
+    );
+
+    let (siblings, bits) = h.tree.path(index);
+    let spend = Spend {
+        note,
+        siblings,
+        bits,
+    };
+    let proof = dev_prover::<WithdrawAssociation>()
+        .prove(
+            withdraw_association(&spend, set.path(index), &h.user.pubkey()),
+            rng,
+        )
+        .unwrap();
+    let InstructionInputs::Association(inputs) = proof.instruction_inputs().unwrap() else {
+        panic!("association circuit must produce association inputs");
+    };
+    vec![
+        h.insertion_proof_ix(carried.proof, insertion.inputs),
+        h.association_withdraw_ix(proof.proof, inputs).await,
+    ]
+}
+
+#[tokio::test]
+async fn spends_insert_their_nullifiers() {
+    let notes: Vec<_> = (1..=3).map(|i| Note::random(i * 1_000_000_000)).collect();
+    let (mut h, set) = pool_with(&notes).await;
+    let mut nullifiers = IndexedTree::new();
+    assert_eq!(h.nullifier_tree().await.root, nullifiers.root());
+
+    let user = h.user.pubkey();
+    let mut first = vec![];
+    for (index, note) in notes.iter().enumerate() {
+        let ixs = spend_ixs(&mut h, &set, &mut nullifiers, note, index).await;
+        let before = h.lamports(&user).await;
+        h.send(&ixs, &[]).await.unwrap();
+        assert_eq!(h.lamports(&user).await - before, note.amount - POOL_FEE);
+
+        let tree = h.nullifier_tree().await;
+        assert_eq!(tree.root, nullifiers.root());
+        assert_eq!(tree.next_index, nullifiers.leaves().len() as u64);
+        if first.is_empty() {
+            first = ixs;
+        }
+    }
+    h.audit_pool().await.unwrap();
+
+    // spent: the client finds the nullifier in the tree, and the first proof is stale
+    assert_eq!(
+        nullifiers.clone().insert(&notes[0].nullifier_hash()),
+        Err(InsertError::AlreadySpent)
+    );
+    assert_program_error(h.send(&first, &[]).await, ErrorCode::StaleNullifierTree);
+}
+
+#[tokio::test]
+async fn spend_needs_its_insertion_proof() {
+    let notes = [Note::random(1_000_000_000), Note::random(1_000_000_000)];
+    let (mut h, set) = pool_with(&notes).await;
+    let mut nullifiers = IndexedTree::new();
+
+    let ixs = spend_ixs(&mut h, &set, &mut nullifiers, &notes[0], 0).await;
+    assert_program_error(
+        h.send(&ixs[1..], &[]).await,
+        ErrorCode::MissingInsertionProof,
+    );
+
+    // the insertion of another nullifier doesn't do
+    let other = spend_ixs(&mut h, &set, &mut nullifiers, &notes[1], 1).await;
+    assert_program_error(
+        h.send(&[other[0].clone(), ixs[1].clone()], &[]).await,
+        ErrorCode::MissingInsertionProof,
+    );
+    h.send(&ixs, &[]).await.unwrap();
+}
@@ -0,0 +1,112 @@
+//! Deposit caps: each deposit is checked against the pool's caps, epoch windows follow
+//! the clock.
+mod common;
+
+use anchor_lang::error::Error;
+use common::empty_pool;
+use solnado::error::ErrorCode;
+use solnado::limits::{admit_deposit, DepositEpoch, DepositLimits};
+use solnado::state::MerkleMountainRange;
+
+const NOW: i64 = 1_760_000_000;
+
+fn capped(limits: DepositLimits) -> MerkleMountainRange {
+    let mut pool = empty_pool();
+    pool.deposit_limits = limits;
+    pool
+}
+
+fn assert_error(res: Result<(), Error>, code: ErrorCode) {
+    assert_eq!(res.unwrap_err(), Error::from(code));
+}
+
+#[test]
+fn zero_caps_admit_anything() {
+    let mut pool = empty_pool();
+    admit_deposit(&mut pool, u64::MAX, NOW).unwrap();
+    admit_deposit(&mut pool, u64::MAX, NOW).unwrap();
+    assert_eq!(pool.deposit_epoch, DepositEpoch::default());
+}
+
+#[test]
+fn per_deposit_cap_is_inclusive() {
+    let mut pool = capped(DepositLimits {
+        max_deposit: 1_000,
+        ..Default::default()
+    });
+    admit_deposit(&mut pool, 1_000, NOW).unwrap();
+    assert_error(
+        admit_deposit(&mut pool, 1_001, NOW),
+        ErrorCode::DepositTooLarge,
+    );
+}
+
+#[test]
+fn tvl_cap_counts_outstanding_notes() {
+    let mut pool = capped(DepositLimits {
+        max_tvl: 10_000,
+        ..Default::default()
+    });
+    pool.ledger.deposit(12_000).unwrap();
+    pool.ledger.withdraw(3_000, 1_000).unwrap();
+    // 8_000 outstanding
+    admit_deposit(&mut pool, 2_000, NOW).unwrap();
+    assert_error(
+        admit_deposit(&mut pool, 2_001, NOW),
+        ErrorCode::TvlCapReached,
+    );
+    assert_error(
+        admit_deposit(&mut pool, u64::MAX, NOW),
+        ErrorCode::TvlCapReached,
+    );
+}
+
+#[test]
+fn epoch_cap_resets_with_the_window() {
+    let day = 86_400;
+    let mut pool = capped(DepositLimits {
+        max_per_epoch: 5_000,
+        epoch_seconds: day as u64,
+        ..Default::default()
+    });
+    let opened = NOW - NOW % day;
+
+    admit_deposit(&mut pool, 3_000, NOW).unwrap();
+    admit_deposit(&mut pool, 2_000, opened + day - 1).unwrap();
+    assert_eq!(
+        pool.deposit_epoch,
+        DepositEpoch {
+            start: opened,
+            deposited: 5_000
+        }
+    );
+    assert_error(
+        admit_deposit(&mut pool, 1, opened + 1),
+        ErrorCode::EpochDepositCapReached,
+    );
+    // a rejected deposit isn't counted
+    assert_eq!(pool.deposit_epoch.deposited, 5_000);
+
+    admit_deposit(&mut pool, 5_000, opened + day).unwrap();
+    assert_eq!(pool.deposit_epoch.start, opened + day);
+    assert_error(
+        admit_deposit(&mut pool, 5_001, opened + 3 * day),
+        ErrorCode::EpochDepositCapReached,
+    );
+}
+
+#[test]
+fn epoch_cap_needs_a_window() {
+    let limits = DepositLimits {
+        max_per_epoch: 5_000,
+        ..Default::default()
+    };
+    assert_error(limits.validate(), ErrorCode::InvalidDepositLimits);
+    assert!(DepositLimits::default().validate().is_ok());
+    assert!(DepositLimits {
+        epoch_seconds: 60,
+        ..limits
+    }
+    .validate()
+    .is_ok());
+}
@@ -0,0 +1,181 @@
+//! Memo codec: every malformed memo decodes to an error, never a panic, and each
+//! deposit check picks its own kind among the transaction's memos.
+use anchor_lang::prelude::AccountInfo;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::solana_program::sysvar::instructions::{self, BorrowedInstruction};
+use base64::{engine::general_purpose, Engine as _};
+use solnado::error::ErrorCode;
+use solnado::memo::*;
+use solnado::utils::{enforce_small_tree_memo, enforce_sub_batch_memo, MEMO_PROGRAM_ID};
+
+fn encoded(payload: &[u8]) -> Vec<u8> {
+    general_purpose::STANDARD.encode(payload).into_bytes()
+}
+
+#[test]
+fn sub_batch_roundtrip() {
+    let mut leaves = [[0u8; 32]; 8];
+    for (i, leaf) in leaves.iter_mut().enumerate() {
+        *leaf = [i as u8 + 1; 32];
+    }
+    let memo = SubBatchMemo {
+        batch_number: 0x0102_0304_0506_0708,
+        leaves,
+    };
+    let data = memo.encode();
+    assert_eq!(SubBatchMemo::decode(&data), Ok(memo));
+    assert_eq!(PoolMemo::decode(&data), Ok(PoolMemo::SubBatch(memo)));
+
+    let payload = general_purpose::STANDARD.decode(&data).unwrap();
+    assert_eq!(payload.len(), SUB_BATCH_MEMO_LEN);
+    assert_eq!(payload[..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn small_tree_roundtrip() {
+    let memo = SmallTreeMemo {
+        closed_batch: 4095,
+        root: [7u8; 32],
+    };
+    let data = memo.encode();
+    assert_eq!(SmallTreeMemo::decode(&data), Ok(memo));
+    assert_eq!(PoolMemo::decode(&data), Ok(PoolMemo::SmallTree(memo)));
+}
+
+#[test]
+fn wrong_lengths_are_errors() {
+    for len in [0, 1, 7, 8, 39, 41, 263, 265, 520] {
+        let data = encoded(&vec![1u8; len]);
+        assert_eq!(
+            SmallTreeMemo::decode(&data),
+            Err(MemoError::InvalidLength {
+                expected: SMALL_TREE_MEMO_LEN,
+                got: len,
+            })
+        );
+        assert_eq!(
+            SubBatchMemo::decode(&data),
+            Err(MemoError::InvalidLength {
+                expected: SUB_BATCH_MEMO_LEN,
+                got: len,
+            })
+        );
+        assert!(PoolMemo::decode(&data).is_err());
+    }
+    // the length check is exact, the wrong kind is rejected too
+    let small = SmallTreeMemo {
+        closed_batch: 1,
+        root: [1u8; 32],
+    };
+    assert!(SubBatchMemo::decode(&small.encode()).is_err());
+}
+
+#[test]
+fn bad_encodings_are_errors() {
+    assert_eq!(
+        SmallTreeMemo::decode(&[0xff, 0xfe, 0x41]),
+        Err(MemoError::NotUtf8)
+    );
+    assert_eq!(
+        SmallTreeMemo::decode(b"not base64!"),
+        Err(MemoError::NotBase64)
+    );
+    // url safe alphabet is not accepted
+    let mut data = encoded(&[0xfb; SMALL_TREE_MEMO_LEN]);
+    data.iter_mut()
+        .filter(|c| **c == b'+')
+        .for_each(|c| *c = b'-');
+    assert_eq!(SmallTreeMemo::decode(&data), Err(MemoError::NotBase64));
+}
+
+#[test]
+fn errors_map_to_program_codes() {
+    assert_eq!(
+        ErrorCode::from(MemoError::NotUtf8) as u32,
+        ErrorCode::InvalidMemoUtf8 as u32
+    );
+    assert_eq!(
+        ErrorCode::from(MemoError::NotBase64) as u32,
+        ErrorCode::InvalidMemoBase64 as u32
+    );
+    assert_eq!(
+        ErrorCode::from(MemoError::InvalidLength {
+            expected: 40,
+            got: 0
+        }) as u32,
+        ErrorCode::InvalidMemoLength as u32
+    );
+}
+
+/// Runs `check` against an instructions sysvar holding `ixs`
+fn with_instructions_sysvar(ixs: &[Instruction], check: impl FnOnce(&AccountInfo)) {
+    let borrowed: Vec<BorrowedInstruction> = ixs
+        .iter()
+        .map(|ix| BorrowedInstruction {
+            program_id: &ix.program_id,
+            accounts: vec![],
+            data: &ix.data,
+        })
+        .collect();
+    let mut data = instructions::construct_instructions_data(&borrowed);
+    let mut lamports = 0;
+    let owner = sysvar::ID;
+    let account = AccountInfo::new(
+        &instructions::ID,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    check(&account);
+}
+
+fn memo(data: Vec<u8>) -> Instruction {
+    Instruction {
+        program_id: MEMO_PROGRAM_ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+#[test]
+fn each_check_finds_its_memo_kind() {
+    let sub_batch = SubBatchMemo {
+        batch_number: 4095,
+        leaves: [[2u8; 32]; 8],
+    };
+    let small_tree = SmallTreeMemo {
+        closed_batch: 4095,
+        root: [3u8; 32],
+    };
+    // a deposit closing the last batch of a small tree and opening the next one
+    let ixs = [memo(sub_batch.encode()), memo(small_tree.encode())];
+    with_instructions_sysvar(&ixs, |sysvar| {
+        enforce_sub_batch_memo(sysvar, 4095, &sub_batch.leaves).unwrap();
+        enforce_small_tree_memo(sysvar, 4095, small_tree.root).unwrap();
+    });
+    let ixs = [memo(small_tree.encode()), memo(sub_batch.encode())];
+    with_instructions_sysvar(&ixs, |sysvar| {
+        enforce_sub_batch_memo(sysvar, 4095, &sub_batch.leaves).unwrap();
+        enforce_small_tree_memo(sysvar, 4095, small_tree.root).unwrap();
+    });
+
+    // without a memo of the right length, the first one's error
+    let ixs = [memo(encoded(&[1u8; 12])), memo(sub_batch.encode())];
+    with_instructions_sysvar(&ixs, |sysvar| {
+        assert_eq!(
+            enforce_small_tree_memo(sysvar, 4095, small_tree.root).unwrap_err(),
+            ErrorCode::InvalidMemoLength.into()
+        );
+    });
+    with_instructions_sysvar(&[], |sysvar| {
+        assert_eq!(
+            enforce_sub_batch_memo(sysvar, 4095, &sub_batch.leaves).unwrap_err(),
+            ErrorCode::MissingMemoInstruction.into()
+        );
+    });
+}
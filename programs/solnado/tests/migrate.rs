@@ -0,0 +1,322 @@
+//! Pool layout versions: `migrate_pool` upgrades pools written with an older layout in
+//! place, growing them at the rent reserve's expense when the layout outgrew the account.
+mod common;
+
+use anchor_lang::AccountSerialize;
+use common::tree::ShadowTree;
+use common::*;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_system_interface::instruction as system_instruction;
+use solnado::error::ErrorCode;
+use solnado::ledger::PoolLedger;
+use solnado::migrate::POOL_VERSION;
+use solnado::reserve::RENT_RESERVE_SPACE;
+use solnado::state::MerkleMountainRange;
+use solnado::sunset::PoolStatus;
+use solnado::utils::{batch_nodes, get_default_root_depth, get_root, BatchNodes};
+
+const AMOUNT: u64 = 10_000_000;
+/// Version 1 layout, before the lifecycle fields
+const V1_LEN: usize = MerkleMountainRange::SIZE - 1 - 8;
+/// Layout before the version byte
+const V0_LEN: usize = V1_LEN - 1;
+/// What `initialize_variable_pool` allocated before the version byte: the hand-summed
+/// size, over by 32 bytes, plus 100 bytes of slack
+const LEGACY_SPACE: usize = 8 + V0_LEN + 32 + 100;
+/// Same, before the batch cache and deep root
+const PRE_CACHE_SPACE: usize = LEGACY_SPACE - 14 * 32 - 32;
+/// Layout of the pools deployed before this series, up to `max_leaves`: no store,
+/// ledger, authority or caches
+const BASELINE_LEN: usize = 32 + 16 * 32 + 16 + 8 + 32 + 32 + 8 + 26 * 32 + 26 + 1 + 8;
+/// What those pools allocated, their hand-summed `MAX_SIZE`
+const BASELINE_SPACE: usize = 8 + 1639;
+
+/// A pool holding a few notes, with its caches and deep root up to date. Written field by
+/// field, `insert_leaf` emits events and only runs inside a transaction once a bank started.
+async fn pool_with_notes(h: &mut Harness) -> MerkleMountainRange {
+    let mut pool = h.pool().await;
+    for i in 0..5u8 {
+        let leaf = [i + 1; 32];
+        pool.batch_leaves[i as usize] = leaf;
+        h.tree.insert(leaf);
+    }
+    pool.batch_nodes = batch_nodes(&pool.batch_leaves);
+    pool.merkle_root_batch = get_root(&pool.batch_leaves);
+    pool.refresh_deep_root();
+    pool.ledger = PoolLedger {
+        deposited: 5 * AMOUNT,
+        ..PoolLedger::default()
+    };
+    pool
+}
+
+/// Replaces the pool account with the first `layout_len` bytes of `pool`, an older layout,
+/// zero padded to `space`. The pool holds its rent and the notes.
+async fn write_legacy_pool(
+    h: &mut Harness,
+    pool: &MerkleMountainRange,
+    layout_len: usize,
+    space: usize,
+) {
+    let mut data = Vec::new();
+    pool.try_serialize(&mut data).unwrap();
+    data.truncate(8 + layout_len);
+    data.resize(space, 0);
+    let mut account = h.account(&pool_pda()).await.expect("pool exists");
+    account.lamports = Rent::default().minimum_balance(space) + pool.ledger.outstanding();
+    account.data = data;
+    h.ctx
+        .set_account(&pool_pda(), &AccountSharedData::from(account));
+}
+
+/// Whole tree root as pools bagged their peaks before the version byte: adjacent pairs,
+/// an odd one out lifted by a default node
+fn pairwise_bagging(pool: &MerkleMountainRange) -> [u8; 32] {
+    let mut nodes: Vec<([u8; 32], u8)> = (0..pool.number_of_peaks as usize)
+        .map(|i| (pool.peaks[i], pool.depth[i]))
+        .collect();
+    while nodes.len() > 1 {
+        nodes = nodes
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => MerkleMountainRange::merge_nodes(*a, *b),
+                [(node, depth)] => MerkleMountainRange::merge_nodes(
+                    (*node, *depth),
+                    (get_default_root_depth(*depth as usize), *depth),
+                ),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    nodes[0].0
+}
+
+#[test]
+fn size_matches_the_serialized_layout() {
+    let mut data = Vec::new();
+    empty_pool().try_serialize(&mut data).unwrap();
+    assert_eq!(data.len(), 8 + MerkleMountainRange::SIZE);
+}
+
+#[tokio::test]
+async fn new_pools_need_no_migration() {
+    let mut h = Harness::start_with_pool().await;
+    assert_eq!(h.pool().await.version, POOL_VERSION);
+    let account = h.account(&pool_pda()).await.unwrap();
+    assert_eq!(account.data.len(), 8 + MerkleMountainRange::SIZE);
+    assert_program_error(h.migrate_pool().await, ErrorCode::PoolUpToDate);
+}
+
+#[tokio::test]
+async fn unversioned_pool_migrates_in_place() {
+    let mut h = Harness::start_with_pool().await;
+    let expected = pool_with_notes(&mut h).await;
+    // the version byte lands in the old slack, reading as 0
+    write_legacy_pool(&mut h, &expected, V0_LEN, LEGACY_SPACE).await;
+    assert_eq!(h.pool().await.version, 0);
+    let (pool_before, reserve_before) = (
+        h.lamports(&pool_pda()).await,
+        h.lamports(&rent_reserve()).await,
+    );
+
+    h.migrate_pool().await.unwrap();
+
+    let pool = h.pool().await;
+    assert_eq!(pool.version, POOL_VERSION);
+    assert_eq!(pool.batch_leaves, expected.batch_leaves);
+    assert_eq!(pool.batch_nodes, expected.batch_nodes);
+    assert_eq!(pool.deep_root, h.tree.root());
+    assert_eq!(pool.ledger, expected.ledger);
+    // already large enough, nothing moved
+    let account = h.account(&pool_pda()).await.unwrap();
+    assert_eq!(account.data.len(), LEGACY_SPACE);
+    assert_eq!(account.lamports, pool_before);
+    assert_eq!(h.lamports(&rent_reserve()).await, reserve_before);
+    h.audit_pool().await.unwrap();
+    assert_program_error(h.migrate_pool().await, ErrorCode::PoolUpToDate);
+}
+
+#[tokio::test]
+async fn unversioned_pool_roots_are_rebagged() {
+    let mut h = Harness::start_with_pool().await;
+    let mut pool = h.pool().await;
+    // seven closed batches, three peaks
+    let roots: Vec<[u8; 32]> = (1..=7u8).map(|i| [i; 32]).collect();
+    for root in &roots {
+        pool.update_peaks(*root);
+        pool.batch_number += 1;
+    }
+    assert_eq!(pool.number_of_peaks, 3);
+    pool.whole_tree_root = pairwise_bagging(&pool);
+    assert_ne!(pool.whole_tree_root, pool.compute_root_from_peaks());
+    pool.deep_root = [0u8; 32];
+    write_legacy_pool(&mut h, &pool, V0_LEN, LEGACY_SPACE).await;
+
+    h.migrate_pool().await.unwrap();
+
+    let migrated = h.pool().await;
+    assert_eq!(migrated.peaks, pool.peaks);
+    assert_eq!(migrated.whole_tree_root, pool.compute_root_from_peaks());
+    assert_eq!(
+        migrated.deep_root,
+        ShadowTree::from_batch_roots(roots).root()
+    );
+}
+
+#[tokio::test]
+async fn pool_without_batch_cache_grows_and_rebuilds_it() {
+    let mut h = Harness::start_with_pool().await;
+    let expected = pool_with_notes(&mut h).await;
+    let layout_len = V0_LEN - std::mem::size_of::<BatchNodes>() - 32;
+    write_legacy_pool(&mut h, &expected, layout_len, PRE_CACHE_SPACE).await;
+    // too short for the current layout until migrated
+    h.audit_pool().await.unwrap_err();
+
+    // the reserve pays for the growth
+    assert_program_error(h.migrate_pool().await, ErrorCode::RentReserveShort);
+    // fund_rent_reserve reads the pool as well, a plain transfer tops the reserve up
+    let top_up = system_instruction::transfer(&h.ctx.payer.pubkey(), &rent_reserve(), AMOUNT);
+    h.send(&[top_up], &[]).await.unwrap();
+    let (pool_before, reserve_before) = (
+        h.lamports(&pool_pda()).await,
+        h.lamports(&rent_reserve()).await,
+    );
+    h.migrate_pool().await.unwrap();
+
+    let space = 8 + MerkleMountainRange::SIZE;
+    let rent =
+        Rent::default().minimum_balance(space) - Rent::default().minimum_balance(PRE_CACHE_SPACE);
+    let account = h.account(&pool_pda()).await.unwrap();
+    assert_eq!(account.data.len(), space);
+    assert_eq!(account.lamports, pool_before + rent);
+    assert_eq!(h.lamports(&rent_reserve()).await, reserve_before - rent);
+
+    let pool = h.pool().await;
+    assert_eq!(pool.version, POOL_VERSION);
+    assert_eq!(pool.batch_leaves, expected.batch_leaves);
+    assert_eq!(pool.batch_nodes, expected.batch_nodes);
+    assert_eq!(pool.deep_root, h.tree.root());
+    assert_eq!(pool.ledger.rent_spent, rent);
+    h.audit_pool().await.unwrap();
+}
+
+#[tokio::test]
+async fn version_one_pool_gains_the_lifecycle_fields() {
+    let mut h = Harness::start_with_pool().await;
+    let mut expected = pool_with_notes(&mut h).await;
+    expected.version = 1;
+    let old_space = 8 + V1_LEN;
+    write_legacy_pool(&mut h, &expected, V1_LEN, old_space).await;
+    h.audit_pool().await.unwrap_err();
+
+    let top_up = system_instruction::transfer(&h.ctx.payer.pubkey(), &rent_reserve(), AMOUNT);
+    h.send(&[top_up], &[]).await.unwrap();
+    let pool_before = h.lamports(&pool_pda()).await;
+    h.migrate_pool().await.unwrap();
+
+    let space = 8 + MerkleMountainRange::SIZE;
+    let rent = Rent::default().minimum_balance(space) - Rent::default().minimum_balance(old_space);
+    assert_eq!(h.lamports(&pool_pda()).await, pool_before + rent);
+    let pool = h.pool().await;
+    assert_eq!(pool.version, POOL_VERSION);
+    assert_eq!(pool.status, PoolStatus::Active);
+    assert_eq!(pool.sunset_at, 0);
+    assert_eq!(pool.deep_root, expected.deep_root);
+    assert_eq!(pool.ledger.rent_spent, rent);
+    h.audit_pool().await.unwrap();
+}
+
+#[tokio::test]
+async fn baseline_pool_gets_an_authority_ledger_and_reserve() {
+    let mut h = Harness::start_with_pool().await;
+    let expected = pool_with_notes(&mut h).await;
+    write_legacy_pool(&mut h, &expected, BASELINE_LEN, BASELINE_SPACE).await;
+    // those pools had no rent reserve either
+    h.ctx
+        .set_account(&rent_reserve(), &AccountSharedData::default());
+    let notes = 5 * AMOUNT;
+
+    // nothing pays for the growth, nor may anyone name the authority
+    assert_program_error(h.migrate_pool().await, ErrorCode::InvalidRentReserve);
+    let (admin, impostor, authority) = (Keypair::new(), Keypair::new(), Keypair::new());
+    for key in [admin.pubkey(), impostor.pubkey()] {
+        let fund = system_instruction::transfer(&h.ctx.payer.pubkey(), &key, 1_000_000_000);
+        h.send(&[fund], &[]).await.unwrap();
+    }
+    h.set_upgrade_authority(admin.pubkey());
+    assert_program_error(
+        h.migrate_legacy_pool(&impostor, Some(impostor.pubkey()))
+            .await,
+        ErrorCode::UnauthorizedAction,
+    );
+    assert_program_error(
+        h.migrate_legacy_pool(&admin, None).await,
+        ErrorCode::MissingPoolAuthority,
+    );
+
+    let admin_before = h.lamports(&admin.pubkey()).await;
+    h.migrate_legacy_pool(&admin, Some(authority.pubkey()))
+        .await
+        .unwrap();
+
+    let pool = h.pool().await;
+    assert_eq!(pool.version, POOL_VERSION);
+    assert_eq!(pool.authority, authority.pubkey());
+    assert_eq!(pool.batch_leaves, expected.batch_leaves);
+    assert_eq!(pool.deep_root, h.tree.root());
+    // the ledger starts from the notes the pool holds, the admin paid the rent
+    assert_eq!(
+        pool.ledger,
+        PoolLedger {
+            deposited: notes,
+            ..PoolLedger::default()
+        }
+    );
+    let space = 8 + MerkleMountainRange::SIZE;
+    let account = h.account(&pool_pda()).await.unwrap();
+    assert_eq!(account.data.len(), space);
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(space) + notes
+    );
+    let reserve = h.account(&rent_reserve()).await.unwrap();
+    assert_eq!(reserve.owner, solnado::ID);
+    assert_eq!(reserve.data.len(), RENT_RESERVE_SPACE);
+    let rent = Rent::default().minimum_balance(space)
+        - Rent::default().minimum_balance(BASELINE_SPACE)
+        + reserve.lamports;
+    assert!(h.lamports(&admin.pubkey()).await <= admin_before - rent);
+    h.audit_pool().await.unwrap();
+
+    // the new authority runs the pool
+    let sunset = h.sunset_pool_ix(authority.pubkey());
+    h.send(&[sunset], &[&authority]).await.unwrap();
+    assert_program_error(h.migrate_pool().await, ErrorCode::PoolUpToDate);
+}
+
+#[tokio::test]
+async fn pool_with_an_authority_keeps_it() {
+    let mut h = Harness::start_with_pool().await;
+    let before = h.pool().await;
+    let mut expected = pool_with_notes(&mut h).await;
+    expected.version = 1;
+    write_legacy_pool(&mut h, &expected, V1_LEN, 8 + V1_LEN).await;
+    let top_up = system_instruction::transfer(&h.ctx.payer.pubkey(), &rent_reserve(), AMOUNT);
+    h.send(&[top_up], &[]).await.unwrap();
+
+    // the upgrade authority can't replace a pool's authority
+    let admin = Keypair::new();
+    let fund = system_instruction::transfer(&h.ctx.payer.pubkey(), &admin.pubkey(), AMOUNT);
+    h.send(&[fund], &[]).await.unwrap();
+    h.set_upgrade_authority(admin.pubkey());
+    assert_program_error(
+        h.migrate_legacy_pool(&admin, Some(admin.pubkey())).await,
+        ErrorCode::InvalidArgument,
+    );
+    h.migrate_pool().await.unwrap();
+    let pool = h.pool().await;
+    assert_eq!(pool.authority, before.authority);
+    assert_eq!(pool.ledger.deposited, expected.ledger.deposited);
+}
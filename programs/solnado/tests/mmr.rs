@@ -0,0 +1,193 @@
+//! The mountain range roots must be those of a plain depth `TARGET_DEPTH_LARGE` sparse
+//! tree holding the same batches, whatever the number of batches.
+mod common;
+
+use common::empty_pool;
+use common::tree::ShadowTree;
+use proptest::prelude::*;
+use solana_poseidon::{hashv, Endianness, Parameters};
+use solnado::state::MerkleMountainRange;
+use solnado::utils::{default_batch_nodes, default_leaves, get_root};
+use solnado::{DEFAULT_LEAF, LEAVES_LENGTH, TARGET_DEPTH_LARGE, TARGET_DEPTH_LARGE_ARRAY};
+
+fn hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hashv(Parameters::Bn254X5, Endianness::BigEndian, &[left, right])
+        .unwrap()
+        .to_bytes()
+}
+
+/// Rolls a closed batch into the peaks, as the deposit instructions do
+fn push_batch(pool: &mut MerkleMountainRange, root: [u8; 32]) {
+    pool.update_peaks(root);
+    pool.batch_number += 1;
+    pool.whole_tree_root = pool.compute_root_from_peaks();
+}
+
+/// State after `count` batches all rooted at `root`, without replaying them
+fn skip_ahead(count: u64, root: [u8; 32]) -> MerkleMountainRange {
+    let mut pool = empty_pool();
+    let mut full = vec![root];
+    for _ in 1..TARGET_DEPTH_LARGE_ARRAY {
+        let last = *full.last().unwrap();
+        full.push(hash(&last, &last));
+    }
+    for bit in (0..TARGET_DEPTH_LARGE_ARRAY).rev() {
+        if count & (1 << bit) != 0 {
+            let i = pool.number_of_peaks as usize;
+            pool.peaks[i] = full[bit];
+            pool.depth[i] = bit as u8 + 4;
+            pool.number_of_peaks += 1;
+        }
+    }
+    pool.batch_number = count;
+    pool.whole_tree_root = pool.compute_root_from_peaks();
+    pool
+}
+
+/// Root the spending instructions check: closed batches plus the in-flight one
+fn spend_root(pool: &MerkleMountainRange, in_flight: [u8; 32]) -> [u8; 32] {
+    pool.deepen_temp(pool.update_peaks_temp(in_flight), TARGET_DEPTH_LARGE)
+}
+
+fn assert_peaks_cleared(pool: &MerkleMountainRange) {
+    for i in pool.number_of_peaks as usize..TARGET_DEPTH_LARGE_ARRAY {
+        assert_eq!(pool.peaks[i], DEFAULT_LEAF, "stale peak in slot {}", i);
+        assert_eq!(pool.depth[i], 0, "stale depth in slot {}", i);
+    }
+}
+
+fn batch_root() -> impl Strategy<Value = [u8; 32]> {
+    // leading zero byte keeps it a canonical field element
+    any::<[u8; 31]>().prop_map(|tail| {
+        let mut root = [0u8; 32];
+        root[1..].copy_from_slice(&tail);
+        root
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(24))]
+
+    #[test]
+    fn random_batches_match_naive_tree(roots in prop::collection::vec(batch_root(), 1..48)) {
+        let mut pool = empty_pool();
+        for (i, root) in roots.iter().enumerate() {
+            prop_assert_eq!(
+                spend_root(&pool, *root),
+                ShadowTree::from_batch_roots(roots[..=i].to_vec()).root()
+            );
+            push_batch(&mut pool, *root);
+        }
+        prop_assert_eq!(pool.number_of_peaks as u32, roots.len().count_ones());
+        prop_assert_eq!(pool.get_deep_root(), ShadowTree::from_batch_roots(roots).root());
+        assert_peaks_cleared(&pool);
+    }
+
+    #[test]
+    fn large_ranges_match_naive_tree(
+        prefix in (1u64 << 16)..(1u64 << 18),
+        filler in batch_root(),
+        tail in prop::collection::vec(batch_root(), 0..24),
+    ) {
+        let mut pool = skip_ahead(prefix, filler);
+        let mut roots = vec![filler; prefix as usize];
+        for root in &tail {
+            push_batch(&mut pool, *root);
+            roots.push(*root);
+        }
+        let in_flight = [1u8; 32];
+        prop_assert_eq!(pool.number_of_peaks as u32, roots.len().count_ones());
+        prop_assert_eq!(pool.get_deep_root(), ShadowTree::from_batch_roots(roots.clone()).root());
+        assert_peaks_cleared(&pool);
+
+        roots.push(in_flight);
+        prop_assert_eq!(spend_root(&pool, in_flight), ShadowTree::from_batch_roots(roots).root());
+    }
+}
+
+#[test]
+fn seventeen_peaks_collapse_into_one() {
+    let filler = [2u8; 32];
+    let count = (1u64 << 17) - 1;
+    let mut pool = skip_ahead(count, filler);
+    assert_eq!(pool.number_of_peaks, 17);
+
+    let mut roots = vec![filler; count as usize];
+    assert_eq!(
+        pool.get_deep_root(),
+        ShadowTree::from_batch_roots(roots.clone()).root()
+    );
+
+    push_batch(&mut pool, filler);
+    roots.push(filler);
+    assert_eq!(pool.number_of_peaks, 1);
+    assert_eq!(pool.depth[0], 21);
+    assert_peaks_cleared(&pool);
+    assert_eq!(
+        pool.get_deep_root(),
+        ShadowTree::from_batch_roots(roots).root()
+    );
+}
+
+#[test]
+fn skip_ahead_matches_replayed_batches() {
+    let filler = [4u8; 32];
+    let mut pool = empty_pool();
+    for count in 1..=40u64 {
+        push_batch(&mut pool, filler);
+        let skipped = skip_ahead(count, filler);
+        assert_eq!(skipped.number_of_peaks, pool.number_of_peaks);
+        assert_eq!(skipped.peaks, pool.peaks);
+        assert_eq!(skipped.depth, pool.depth);
+    }
+}
+
+#[test]
+fn roll_over_batch_pushes_the_filled_batch() {
+    let mut pool = empty_pool();
+    let mut expected = empty_pool();
+    let leaves: [[u8; 32]; LEAVES_LENGTH] = std::array::from_fn(|i| [i as u8; 32]);
+    for (idx, leaf) in leaves.into_iter().enumerate() {
+        pool.insert_leaf(idx, leaf);
+    }
+    assert_eq!(pool.merkle_root_batch, get_root(&leaves));
+
+    pool.roll_over_batch();
+    push_batch(&mut expected, get_root(&leaves));
+    assert_eq!(pool.batch_number, 1);
+    assert_eq!(pool.peaks, expected.peaks);
+    assert_eq!(pool.whole_tree_root, expected.whole_tree_root);
+    // the next batch starts empty
+    assert_eq!(pool.batch_leaves, default_leaves());
+    assert_eq!(pool.batch_nodes, default_batch_nodes());
+    assert_eq!(pool.merkle_root_batch, get_root(&default_leaves()));
+}
+
+#[test]
+fn batch_root_follows_scattered_inserts() {
+    let mut pool = empty_pool();
+    assert_eq!(pool.merkle_root_batch, get_root(&default_leaves()));
+    // out of order and overwriting, the cached path must match a full rehash every time
+    for (step, idx) in [5, 0, 15, 6, 5, 9, 1, 14].into_iter().enumerate() {
+        pool.insert_leaf(idx, [step as u8 + 1; 32]);
+        assert_eq!(pool.merkle_root_batch, get_root(&pool.batch_leaves));
+    }
+}
+
+#[test]
+fn deep_root_tracks_inserts_across_a_rollover() {
+    let mut pool = empty_pool();
+    let mut tree = ShadowTree::new();
+    assert_eq!(pool.deep_root, tree.root());
+    for i in 1..=20u8 {
+        let leaf = [i; 32];
+        let idx = pool.find_first_match();
+        pool.insert_leaf(idx, leaf);
+        if idx + 1 == LEAVES_LENGTH {
+            pool.roll_over_batch();
+        }
+        pool.refresh_deep_root();
+        tree.insert(leaf);
+        assert_eq!(pool.deep_root, tree.root(), "after {} leaves", i);
+    }
+}
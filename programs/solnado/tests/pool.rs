@@ -0,0 +1,332 @@
+mod common;
+
+use common::*;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_system_interface::instruction as system_instruction;
+use solnado::error::ErrorCode;
+use solnado::events::PoolAudited;
+use solnado::inputs::{DepositInputs, OnBehalfInputs, WithdrawInputs, FIELD_MODULUS};
+use solnado::ledger::PoolLedger;
+use solnado::limits::DepositLimits;
+use solnado::nullifier::NullifierStoreKind;
+use solnado::reserve::RENT_RESERVE_SPACE;
+use solnado::shard::{MAX_SPLIT_THRESHOLD, SHARD_SPLITTING_THRESHOLD};
+use solnado::utils::{default_leaves, get_root};
+use solnado::DEFAULT_LEAF;
+use solnado_sdk::IndexedTree;
+
+#[tokio::test]
+async fn initialize_pool_starts_empty_tree() {
+    let mut h = Harness::start().await;
+    h.initialize_pool().await.unwrap();
+
+    let pool = h.pool().await;
+    assert_eq!(pool.identifier, POOL_ID);
+    assert_eq!(pool.batch_number, 0);
+    assert_eq!(pool.number_of_peaks, 0);
+    assert_eq!(pool.batch_leaves, default_leaves());
+    assert_eq!(pool.merkle_root_batch, get_root(&default_leaves()));
+    assert_eq!(pool.deep_root, h.tree.root());
+    assert_eq!(pool.max_leaves, 1 << 30);
+    assert_eq!(pool.nullifier_store, NullifierStoreKind::Sharded);
+
+    for indexer in [leaves_indexer(), subtree_indexer()] {
+        let account = h.account(&indexer).await.expect("indexer created");
+        assert_eq!(account.owner, solnado::ID);
+    }
+
+    assert_eq!(h.spend_root().await, h.tree.root());
+}
+
+#[tokio::test]
+async fn rent_reserve_starts_empty_and_takes_top_ups() {
+    let mut h = Harness::start().await;
+    h.initialize_pool().await.unwrap();
+
+    let reserve = h.account(&rent_reserve()).await.expect("reserve created");
+    assert_eq!(reserve.owner, solnado::ID);
+    let floor = Rent::default().minimum_balance(RENT_RESERVE_SPACE);
+    assert_eq!(reserve.lamports, floor);
+
+    let pool_before = h.lamports(&pool_pda()).await;
+    h.fund_rent_reserve(5_000_000).await.unwrap();
+    assert_eq!(h.lamports(&rent_reserve()).await, floor + 5_000_000);
+    assert_eq!(h.lamports(&pool_pda()).await, pool_before);
+}
+
+#[tokio::test]
+async fn audit_counts_donations_as_surplus() {
+    let mut h = Harness::start_with_pool().await;
+    assert_eq!(h.pool().await.ledger, PoolLedger::default());
+    h.audit_pool().await.unwrap();
+
+    let rent_exempt = h.lamports(&pool_pda()).await;
+    let gift = system_instruction::transfer(&h.ctx.payer.pubkey(), &pool_pda(), 1_000);
+    h.send(&[gift], &[]).await.unwrap();
+    let ix = h.audit_pool_ix();
+    let logs = h.send_with_logs(&[ix], &[]).await.unwrap();
+    assert_eq!(
+        stubs::events::<PoolAudited>(&logs),
+        [PoolAudited {
+            pool: POOL_ID,
+            deposited: 0,
+            withdrawn: 0,
+            fees: 0,
+            rent_spent: 0,
+            lamports: rent_exempt + 1_000,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn audit_flags_pool_short_of_its_notes() {
+    let mut h = Harness::start_with_pool().await;
+    // a fresh pool holds its rent only
+    let rent_exempt = h.lamports(&pool_pda()).await;
+
+    // notes worth one lamport more than the pool holds past its rent
+    let mut pool = h.pool().await;
+    pool.ledger = PoolLedger {
+        deposited: 5_000_001,
+        withdrawn: 4_000_000,
+        fees: 1_000_000,
+        rent_spent: 0,
+    };
+    h.set_pool(&pool).await;
+    assert_eq!(pool.ledger.outstanding(), 1);
+    assert_program_error(h.audit_pool().await, ErrorCode::PoolInsolvent);
+
+    let gift = system_instruction::transfer(&h.ctx.payer.pubkey(), &pool_pda(), 1);
+    h.send(&[gift], &[]).await.unwrap();
+    assert_eq!(h.lamports(&pool_pda()).await, rent_exempt + 1);
+    h.audit_pool().await.unwrap();
+}
+
+#[tokio::test]
+async fn creator_sets_deposit_limits() {
+    let mut h = Harness::start_with_pool().await;
+    assert_eq!(h.pool().await.authority, h.user.pubkey());
+    assert_eq!(h.pool().await.deposit_limits, DepositLimits::default());
+
+    let limits = DepositLimits {
+        max_deposit: 1_000_000_000,
+        max_per_epoch: 5_000_000_000,
+        epoch_seconds: 86_400,
+        max_tvl: 100_000_000_000,
+    };
+    let stranger = Keypair::new();
+    assert_program_error(
+        h.set_deposit_limits(limits, Some(&stranger)).await,
+        ErrorCode::UnauthorizedAction,
+    );
+    assert_program_error(
+        h.set_deposit_limits(
+            DepositLimits {
+                epoch_seconds: 0,
+                ..limits
+            },
+            None,
+        )
+        .await,
+        ErrorCode::InvalidDepositLimits,
+    );
+    h.set_deposit_limits(limits, None).await.unwrap();
+    assert_eq!(h.pool().await.deposit_limits, limits);
+}
+
+#[tokio::test]
+async fn initialize_pool_twice_fails() {
+    let mut h = Harness::start().await;
+    h.initialize_pool().await.unwrap();
+    assert!(h.initialize_pool().await.is_err());
+}
+
+#[tokio::test]
+async fn initialize_shards_creates_depth_one_shards() {
+    let mut h = Harness::start_with_pool().await;
+
+    let shard0 = h.shard(&[0]).await.expect("shard0 created");
+    assert_eq!(shard0.prefix_len, 1);
+    assert_eq!(shard0.prefix[0], 0);
+    assert!(shard0.nullifiers().is_empty());
+
+    let shard1 = h.shard(&[1]).await.expect("shard1 created");
+    assert_eq!(shard1.prefix_len, 1);
+    assert_eq!(shard1.prefix[0], 0x80);
+    assert!(shard1.nullifiers().is_empty());
+
+    assert_eq!(h.directory().await, [shard0.key(), shard1.key()]);
+    let high = h.resolve_shard(&[0xff; 32]).await;
+    assert_eq!(high.shard, shard_pda(&[1]));
+    assert_eq!(
+        (high.child0, high.child1),
+        (shard_pda(&[1, 0]), shard_pda(&[1, 1]))
+    );
+}
+
+#[tokio::test]
+async fn initialize_shards_stores_split_threshold() {
+    let mut h = Harness::start().await;
+    h.initialize_pool().await.unwrap();
+    assert_eq!(
+        h.pool().await.split_threshold(),
+        SHARD_SPLITTING_THRESHOLD,
+        "unset threshold falls back to the default"
+    );
+
+    h.initialize_shards(8).await.unwrap();
+    let pool = h.pool().await;
+    assert_eq!(pool.shard_split_threshold, 8);
+    assert_eq!(pool.split_threshold(), 8);
+
+    assert!(h.initialize_shards(16).await.is_err());
+    assert_eq!(h.pool().await.shard_split_threshold, 8);
+}
+
+#[tokio::test]
+async fn initialize_shards_rejects_out_of_range_threshold() {
+    let mut h = Harness::start().await;
+    h.initialize_pool().await.unwrap();
+    for threshold in [0, MAX_SPLIT_THRESHOLD as u16 + 1] {
+        assert_program_error(
+            h.initialize_shards(threshold).await,
+            ErrorCode::InvalidSplitThreshold,
+        );
+    }
+    assert!(h.shard(&[0]).await.is_none());
+}
+
+#[tokio::test]
+async fn per_nullifier_pool_rejects_shards() {
+    let mut h = Harness::start_with_store(NullifierStoreKind::PerNullifier).await;
+    assert_program_error(h.initialize_shards(8).await, ErrorCode::InvalidMode);
+}
+
+#[tokio::test]
+async fn indexed_pool_starts_with_the_list_head() {
+    let mut h = Harness::start_with_store(NullifierStoreKind::Indexed).await;
+    assert_eq!(h.pool().await.nullifier_store, NullifierStoreKind::Indexed);
+    assert_program_error(h.initialize_shards(8).await, ErrorCode::InvalidMode);
+    h.initialize_nullifier_tree().await.unwrap();
+    assert_eq!(h.nullifier_tree().await.root, IndexedTree::new().root());
+}
+
+#[tokio::test]
+async fn sharded_pool_rejects_nullifier_tree() {
+    let mut h = Harness::start_with_pool().await;
+    assert_program_error(h.initialize_nullifier_tree().await, ErrorCode::InvalidMode);
+}
+
+#[tokio::test]
+async fn per_nullifier_pool_has_no_shards() {
+    let mut h = Harness::start_with_store(NullifierStoreKind::PerNullifier).await;
+    assert_eq!(
+        h.pool().await.nullifier_store,
+        NullifierStoreKind::PerNullifier
+    );
+    assert!(h.shard(&[0]).await.is_none());
+
+    // a rejected spend leaves the nullifier PDA unborn
+    let nullifier = [1u8; 32];
+    let root = h.spend_root().await;
+    let ix = h
+        .withdraw_ix(
+            0,
+            [0u8; 256],
+            WithdrawInputs {
+                nullifier,
+                asset_id: [0u8; 32],
+                amount: 10_000_000,
+                root,
+                new_leaf: DEFAULT_LEAF,
+            },
+        )
+        .await;
+    assert_eq!(ix.accounts[1].pubkey, nullifier_pda(&nullifier));
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidProof);
+    assert!(h.account(&nullifier_pda(&nullifier)).await.is_none());
+}
+
+#[tokio::test]
+async fn on_behalf_withdrawer_must_be_a_field_element() {
+    let mut h = Harness::start_with_pool().await;
+    let root = h.spend_root().await;
+    let payer = h.user.pubkey();
+    let inputs = OnBehalfInputs {
+        nullifier: [1u8; 32],
+        amount: 10_000_000,
+        root,
+        withdrawer: Pubkey::new_from_array(FIELD_MODULUS),
+    };
+
+    let ix = h.on_behalf_ix(&payer, [0u8; 256], inputs, None).await;
+    assert_program_error(
+        h.send(&[ix], &[]).await,
+        ErrorCode::WithdrawerNotAFieldElement,
+    );
+    let mut below = FIELD_MODULUS;
+    below[31] -= 1;
+    let inputs = OnBehalfInputs {
+        withdrawer: Pubkey::new_from_array(below),
+        ..inputs
+    };
+    let ix = h.on_behalf_ix(&payer, [0u8; 256], inputs, None).await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidProof);
+}
+
+#[tokio::test]
+async fn deposit_with_invalid_proof_is_rejected() {
+    let mut h = Harness::start_with_pool().await;
+    let before = h.lamports(&pool_pda()).await;
+
+    let ix = h.deposit_ix(
+        [0u8; 256],
+        DepositInputs {
+            amount: 10_000_000,
+            leaf1: [7u8; 32],
+            leaf2: DEFAULT_LEAF,
+        },
+    );
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidProof);
+
+    assert_eq!(h.lamports(&pool_pda()).await, before);
+    assert_eq!(h.pool().await.batch_leaves, default_leaves());
+}
+
+#[tokio::test]
+async fn withdraw_with_invalid_proof_is_rejected() {
+    let mut h = Harness::start_with_pool().await;
+    let root = h.spend_root().await;
+    let user_before = h.lamports(&h.user.pubkey()).await;
+
+    let ix = h
+        .withdraw_ix(
+            0,
+            [0u8; 256],
+            WithdrawInputs {
+                nullifier: [1u8; 32],
+                asset_id: [0u8; 32],
+                amount: 10_000_000,
+                root,
+                new_leaf: DEFAULT_LEAF,
+            },
+        )
+        .await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidProof);
+
+    assert_eq!(h.lamports(&h.user.pubkey()).await, user_before);
+    assert!(h.shard(&[0]).await.unwrap().nullifiers().is_empty());
+}
+
+#[tokio::test]
+async fn small_tree_state_matches_shadow_tree() {
+    let mut h = Harness::start_with_pool().await;
+    h.fill_first_small_tree([3u8; 32]).await;
+
+    let pool = h.pool().await;
+    assert_eq!(pool.batch_number, solnado::BATCHES_PER_SMALL_TREE);
+    assert_eq!(pool.last_small_tree_root, pool.peaks[0]);
+    assert_eq!(h.spend_root().await, h.tree.root());
+}
@@ -0,0 +1,686 @@
+//! Flows backed by real proofs of the circom circuits. They need the circuit artifacts
+//! (`<circuit>.wasm` and `<circuit>.zkey`) in the directory `SOLNADO_CIRCUITS` points to,
+//! so they're ignored by default: run them with `cargo test -- --ignored`.
+mod common;
+
+use common::circuits::{self, Note, Spend};
+use common::*;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_system_interface::instruction as system_instruction;
+use solnado::directory::{directory_space, ShardPrefix};
+use solnado::error::ErrorCode;
+use solnado::events::{FeeCollected, LeafInserted, NullifierSpent, Withdrawal};
+use solnado::inputs::FIELD_MODULUS;
+use solnado::ledger::PoolLedger;
+use solnado::limits::DepositLimits;
+use solnado::nullifier::NullifierStoreKind;
+use solnado::shard::{ON_BEHALF_FEE, POOL_FEE, SHARD_SPACE};
+use solnado::sunset::SUNSET_GRACE_SECONDS;
+use solnado::utils::get_root;
+use solnado_prover::{Circuit, InstructionInputs, Provers};
+use solnado_sdk::inbox::{self, encrypt_note, scan_notes};
+use solnado_sdk::keys::SpendingKey;
+use solnado_sdk::stealth::{claim_ix, derive_stealth_address, scan_withdrawals};
+
+const AMOUNT: u64 = 10_000_000;
+
+fn provers() -> Provers {
+    Provers::new(circuits_dir())
+}
+
+fn spend<'a>(h: &Harness, note: &'a Note, index: usize) -> Spend<'a> {
+    let (siblings, bits) = h.tree.path(index);
+    Spend {
+        note,
+        siblings,
+        bits,
+    }
+}
+
+/// Deposits `notes` (one or two) and returns their leaf indices
+async fn deposit(h: &mut Harness, provers: &mut Provers, notes: &[Note]) -> Vec<usize> {
+    let (circuit, signals) = match notes {
+        [n] => (Circuit::Deposit1, circuits::deposit1(n)),
+        [n1, n2] => (Circuit::Deposit2, circuits::deposit2(n1, n2)),
+        _ => panic!("one or two notes per deposit"),
+    };
+    let (proof, inputs) = prove(provers, circuit, signals);
+    let InstructionInputs::Deposit(inputs) = inputs else {
+        unreachable!()
+    };
+    let leaves: Vec<[u8; 32]> = notes.iter().map(Note::leaf).collect();
+    let first = h.tree.leaves.len();
+    let ix = h.deposit_ix(proof.proof, inputs);
+    h.insert_leaves(ix, &leaves).await.unwrap();
+    (first..first + notes.len()).collect()
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn deposits_cross_sub_batch_and_batch_boundaries() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    let pool_before = h.lamports(&pool_pda()).await;
+
+    // three two leaf deposits, the fourth fills slots 6 and 7
+    for _ in 0..3 {
+        deposit(
+            &mut h,
+            &mut provers,
+            &[Note::random(AMOUNT), Note::random(AMOUNT)],
+        )
+        .await;
+    }
+    let notes = [Note::random(AMOUNT), Note::random(AMOUNT)];
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::Deposit2,
+        circuits::deposit2(&notes[0], &notes[1]),
+    );
+    let InstructionInputs::Deposit(inputs) = inputs else {
+        unreachable!()
+    };
+    let ix = h.deposit_ix(proof.proof, inputs);
+
+    let mut sub_batch = h.pool().await.batch_leaves;
+    sub_batch[6] = notes[0].leaf();
+    sub_batch[7] = notes[1].leaf();
+    let wrong_batch = sub_batch_memo(1, &sub_batch[..8]);
+    assert_program_error(
+        h.send(&[wrong_batch, ix.clone()], &[]).await,
+        ErrorCode::InvalidUserBatchNumber,
+    );
+    h.insert_leaves(ix, &[notes[0].leaf(), notes[1].leaf()])
+        .await
+        .unwrap();
+    assert_eq!(h.pool().await.find_first_match(), 8);
+
+    // the second sub batch closes the batch and rolls it into the peaks
+    for _ in 0..4 {
+        deposit(
+            &mut h,
+            &mut provers,
+            &[Note::random(AMOUNT), Note::random(AMOUNT)],
+        )
+        .await;
+    }
+    let pool = h.pool().await;
+    let mut batch = [[0u8; 32]; 16];
+    batch.copy_from_slice(&h.tree.leaves[..16]);
+    assert_eq!(pool.batch_number, 1);
+    assert_eq!(pool.number_of_peaks, 1);
+    assert_eq!(pool.peaks[0], get_root(&batch));
+    assert_eq!(pool.whole_tree_root, get_root(&batch));
+    assert_eq!(pool.find_first_match(), 0);
+
+    // single leaf deposit into the next batch
+    deposit(&mut h, &mut provers, &[Note::random(AMOUNT)]).await;
+    assert_eq!(h.spend_root().await, h.tree.root());
+    assert_eq!(h.lamports(&pool_pda()).await, pool_before + 17 * AMOUNT);
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn small_tree_boundary_requires_root_memo() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    h.fill_first_small_tree([3u8; 32]).await;
+    let pool = h.pool().await;
+
+    let note = Note::random(AMOUNT);
+    let (proof, inputs) = prove(&mut provers, Circuit::Deposit1, circuits::deposit1(&note));
+    let InstructionInputs::Deposit(inputs) = inputs else {
+        unreachable!()
+    };
+    let ix = h.deposit_ix(proof.proof, inputs);
+
+    assert_program_error(
+        h.send(std::slice::from_ref(&ix), &[]).await,
+        ErrorCode::MissingMemoInstruction,
+    );
+    // a truncated memo is rejected, not a panic
+    let mut truncated = small_tree_memo(pool.batch_number - 1, &pool.last_small_tree_root);
+    truncated.data.truncate(12);
+    assert_program_error(
+        h.send(&[truncated, ix.clone()], &[]).await,
+        ErrorCode::InvalidMemoLength,
+    );
+    let wrong_root = small_tree_memo(pool.batch_number - 1, &[9u8; 32]);
+    assert_program_error(
+        h.send(&[wrong_root, ix.clone()], &[]).await,
+        ErrorCode::InvalidSmallTreeRoot,
+    );
+
+    h.insert_leaves(ix, &[note.leaf()]).await.unwrap();
+    assert_eq!(h.pool().await.batch_leaves[0], note.leaf());
+    assert_eq!(h.spend_root().await, h.tree.root());
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn deposits_respect_pool_caps() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    h.set_deposit_limits(
+        DepositLimits {
+            max_deposit: AMOUNT,
+            max_tvl: 2 * AMOUNT,
+            ..Default::default()
+        },
+        None,
+    )
+    .await
+    .unwrap();
+
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::Deposit1,
+        circuits::deposit1(&Note::random(AMOUNT + 1)),
+    );
+    let InstructionInputs::Deposit(inputs) = inputs else {
+        unreachable!()
+    };
+    let ix = h.deposit_ix(proof.proof, inputs);
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::DepositTooLarge);
+
+    deposit(&mut h, &mut provers, &[Note::random(AMOUNT)]).await;
+    deposit(&mut h, &mut provers, &[Note::random(AMOUNT)]).await;
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::Deposit1,
+        circuits::deposit1(&Note::random(1)),
+    );
+    let InstructionInputs::Deposit(inputs) = inputs else {
+        unreachable!()
+    };
+    let ix = h.deposit_ix(proof.proof, inputs);
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::TvlCapReached);
+    assert_eq!(h.pool().await.ledger.deposited, 2 * AMOUNT);
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn deposit_notes_reach_their_recipient() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    let recipient = SpendingKey::random(&mut rand::thread_rng());
+    let notes = [Note::random(AMOUNT), Note::random(2 * AMOUNT)];
+    let encrypted = notes
+        .iter()
+        .map(|n| {
+            let note = inbox::Note {
+                amount: n.amount,
+                nullifier: n.nullifier,
+                asset_id: n.asset_id,
+                memo: b"rent".to_vec(),
+            };
+            encrypt_note(&recipient.address(), &note, &mut rand::thread_rng()).ok()
+        })
+        .collect();
+
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::Deposit2,
+        circuits::deposit2(&notes[0], &notes[1]),
+    );
+    let InstructionInputs::Deposit(inputs) = inputs else {
+        unreachable!()
+    };
+    let ix = h.deposit_ix_with_notes(proof.proof, inputs, encrypted);
+    let logs = h.send_with_logs(&[ix], &[]).await.unwrap();
+
+    let incoming = scan_notes(&recipient, logs.iter().map(String::as_str));
+    assert_eq!(incoming.len(), 2);
+    for (found, note) in incoming.iter().zip(&notes) {
+        assert_eq!(found.leaf, note.leaf());
+        assert_eq!(found.note.nullifier, note.nullifier);
+        assert_eq!(found.note.amount, note.amount);
+        assert_eq!(found.note.memo, b"rent");
+    }
+    let stranger = SpendingKey::random(&mut rand::thread_rng());
+    assert!(scan_notes(&stranger, logs.iter().map(String::as_str)).is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn withdraw_spends_note_once() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    let note = Note::random(AMOUNT);
+    let index = deposit(&mut h, &mut provers, &[note]).await[0];
+
+    let root = h.spend_root().await;
+    assert_eq!(root, h.tree.root());
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::Withdraw,
+        circuits::withdraw(&spend(&h, &note, index), &root),
+    );
+    let InstructionInputs::Withdraw(inputs) = inputs else {
+        unreachable!()
+    };
+    let shard = shard_of(&inputs.nullifier);
+
+    let user = h.user.pubkey();
+    let (user_before, pool_before) = (h.lamports(&user).await, h.lamports(&pool_pda()).await);
+    let reserve_before = h.lamports(&rent_reserve()).await;
+    let ix = h.withdraw_ix(0, proof.proof, inputs).await;
+    h.send(std::slice::from_ref(&ix), &[]).await.unwrap();
+
+    assert_eq!(h.lamports(&user).await, user_before + AMOUNT - POOL_FEE);
+    // the whole note leaves the pool, its fee goes to the rent reserve
+    assert_eq!(h.lamports(&pool_pda()).await, pool_before - AMOUNT);
+    assert_eq!(h.lamports(&rent_reserve()).await, reserve_before + POOL_FEE);
+    let stored = h.shard(&shard).await.unwrap();
+    assert_eq!(stored.nullifiers(), &[inputs.nullifier]);
+    assert_eq!(stored.count, 1);
+    assert_eq!(
+        h.pool().await.ledger,
+        PoolLedger {
+            deposited: AMOUNT,
+            withdrawn: AMOUNT - POOL_FEE,
+            fees: POOL_FEE,
+            rent_spent: 0,
+        }
+    );
+    h.audit_pool().await.unwrap();
+
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::NullifierAlreadyUsed);
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn sunset_pool_pays_out_after_closing() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    let notes = [Note::random(AMOUNT), Note::random(AMOUNT)];
+    let indices = deposit(&mut h, &mut provers, &notes).await;
+    h.sunset_pool(None).await.unwrap();
+
+    let root = h.spend_root().await;
+    let mut withdrawals = Vec::new();
+    for (note, index) in notes.iter().zip(indices) {
+        let (proof, inputs) = prove(
+            &mut provers,
+            Circuit::Withdraw,
+            circuits::withdraw(&spend(&h, note, index), &root),
+        );
+        let InstructionInputs::Withdraw(inputs) = inputs else {
+            unreachable!()
+        };
+        withdrawals.push(h.withdraw_ix(0, proof.proof, inputs).await);
+    }
+
+    let user = h.user.pubkey();
+    let before = h.lamports(&user).await;
+    h.send(&withdrawals[..1], &[]).await.unwrap();
+    assert_eq!(h.lamports(&user).await, before + AMOUNT - POOL_FEE);
+
+    h.advance_clock(SUNSET_GRACE_SECONDS).await;
+    h.close_pool(&[]).await.unwrap();
+    let before = h.lamports(&user).await;
+    h.send(&withdrawals[1..], &[]).await.unwrap();
+    assert_eq!(h.lamports(&user).await, before + AMOUNT - POOL_FEE);
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn deposit_and_withdraw_emit_events() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    let note = Note::random(AMOUNT);
+    let (proof, inputs) = prove(&mut provers, Circuit::Deposit1, circuits::deposit1(&note));
+    let InstructionInputs::Deposit(inputs) = inputs else {
+        unreachable!()
+    };
+    let ix = h.deposit_ix(proof.proof, inputs);
+    let logs = h.send_with_logs(&[ix], &[]).await.unwrap();
+    h.tree.insert(note.leaf());
+    assert_eq!(
+        stubs::events::<LeafInserted>(&logs),
+        vec![LeafInserted {
+            pool: POOL_ID,
+            index: 0,
+            leaf: note.leaf(),
+        }]
+    );
+
+    let root = h.spend_root().await;
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::Withdraw,
+        circuits::withdraw(&spend(&h, &note, 0), &root),
+    );
+    let InstructionInputs::Withdraw(inputs) = inputs else {
+        unreachable!()
+    };
+    let shard = shard_pda(&shard_of(&inputs.nullifier));
+    let ix = h.withdraw_ix(0, proof.proof, inputs).await;
+    let logs = h.send_with_logs(&[ix], &[]).await.unwrap();
+    assert_eq!(
+        stubs::events::<NullifierSpent>(&logs),
+        vec![NullifierSpent {
+            pool: POOL_ID,
+            shard,
+            nullifier: inputs.nullifier,
+        }]
+    );
+    assert_eq!(
+        stubs::events::<Withdrawal>(&logs),
+        vec![Withdrawal {
+            pool: POOL_ID,
+            recipient: h.user.pubkey(),
+            amount: AMOUNT - POOL_FEE,
+            fees: POOL_FEE,
+        }]
+    );
+    assert_eq!(
+        stubs::events::<FeeCollected>(&logs),
+        vec![FeeCollected {
+            pool: POOL_ID,
+            payer: pool_pda(),
+            recipient: rent_reserve(),
+            amount: POOL_FEE,
+        }]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn full_shard_splits_and_updates_directory() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_split_threshold(1).await;
+    let notes = [Note::random(AMOUNT), Note::random(AMOUNT)];
+    let indices = deposit(&mut h, &mut provers, &notes).await;
+
+    let pool_before = h.lamports(&pool_pda()).await;
+
+    let mut nullifiers = Vec::new();
+    for (i, (note, index)) in notes.iter().zip(indices).enumerate() {
+        let root = h.spend_root().await;
+        let (proof, inputs) = prove(
+            &mut provers,
+            Circuit::Withdraw,
+            circuits::withdraw(&spend(&h, note, index), &root),
+        );
+        let InstructionInputs::Withdraw(inputs) = inputs else {
+            unreachable!()
+        };
+        nullifiers.push(inputs.nullifier);
+        let ix = h.withdraw_ix(0, proof.proof, inputs).await;
+        if i == 1 {
+            // the second spend splits, the fees so far don't cover a shard
+            assert_program_error(
+                h.send(std::slice::from_ref(&ix), &[]).await,
+                ErrorCode::RentReserveShort,
+            );
+            h.fund_rent_reserve(100_000_000).await.unwrap();
+        }
+        h.send(&[ix], &[]).await.unwrap();
+    }
+    // shard rent never came out of the notes
+    assert_eq!(h.lamports(&pool_pda()).await, pool_before - 2 * AMOUNT);
+    // two children for the parent, one more directory entry
+    let rent = Rent::default();
+    let directory_growth =
+        rent.minimum_balance(directory_space(3)) - rent.minimum_balance(directory_space(2));
+    assert_eq!(
+        h.pool().await.ledger.rent_spent,
+        rent.minimum_balance(SHARD_SPACE) + directory_growth
+    );
+    h.audit_pool().await.unwrap();
+
+    // field elements have their two top bits clear: [0] filled up and split on bit 1,
+    // both nullifiers sorting to [0, 0]
+    assert!(h.account(&shard_pda(&[0])).await.is_none());
+    let (low, mid, high) = (pack_bits(&[0, 0]), pack_bits(&[0, 1]), pack_bits(&[1]));
+    assert_eq!(
+        h.directory().await,
+        [
+            ShardPrefix::new(2, low),
+            ShardPrefix::new(2, mid),
+            ShardPrefix::new(1, high),
+        ]
+    );
+    for nullifier in &nullifiers {
+        let resolved = h.resolve_shard(nullifier).await;
+        assert_eq!(resolved.shard, shard_pda(&[0, 0]));
+        assert!(h
+            .shard(&[0, 0])
+            .await
+            .unwrap()
+            .nullifiers()
+            .contains(nullifier));
+    }
+    assert!(h.shard(&[0, 1]).await.unwrap().nullifiers().is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn per_nullifier_pool_spends_into_pdas() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_store(NullifierStoreKind::PerNullifier).await;
+    let note = Note::random(AMOUNT);
+    let index = deposit(&mut h, &mut provers, &[note]).await[0];
+
+    let root = h.spend_root().await;
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::Withdraw,
+        circuits::withdraw(&spend(&h, &note, index), &root),
+    );
+    let InstructionInputs::Withdraw(inputs) = inputs else {
+        unreachable!()
+    };
+    let record = nullifier_pda(&inputs.nullifier);
+    // someone funding the address first must not block the spend
+    let grief = system_instruction::transfer(&h.ctx.payer.pubkey(), &record, 1);
+    h.send(&[grief], &[]).await.unwrap();
+
+    let user = h.user.pubkey();
+    let user_before = h.lamports(&user).await;
+    let reserve_before = h.lamports(&rent_reserve()).await;
+    let ix = h.withdraw_ix(0, proof.proof, inputs).await;
+    h.send(std::slice::from_ref(&ix), &[]).await.unwrap();
+
+    // the spender pays the record's rent, the reserve only takes the fee
+    let rent = Rent::default().minimum_balance(1);
+    assert_eq!(
+        h.lamports(&user).await,
+        user_before + AMOUNT - POOL_FEE - (rent - 1)
+    );
+    assert_eq!(h.lamports(&rent_reserve()).await, reserve_before + POOL_FEE);
+    assert_eq!(h.pool().await.ledger.rent_spent, 0);
+    let account = h.account(&record).await.unwrap();
+    assert_eq!(account.owner, solnado::ID);
+    assert_eq!(account.lamports, rent);
+
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::NullifierAlreadyUsed);
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn withdraw_on_behalf_pays_withdrawer_and_relayer() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    let note = Note::random(AMOUNT);
+    let index = deposit(&mut h, &mut provers, &[note]).await[0];
+
+    let relayer = Keypair::new();
+    // the program only takes keys the circuit binds whole
+    let withdrawer = std::iter::repeat_with(|| Keypair::new().pubkey())
+        .find(|k| k.to_bytes() < FIELD_MODULUS)
+        .unwrap();
+    let fund =
+        system_instruction::transfer(&h.ctx.payer.pubkey(), &relayer.pubkey(), 1_000_000_000);
+    h.send(&[fund], &[]).await.unwrap();
+
+    let root = h.spend_root().await;
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::WithdrawOnBehalf,
+        circuits::withdraw_on_behalf(&spend(&h, &note, index), &root, &withdrawer),
+    );
+    let InstructionInputs::OnBehalf(inputs) = inputs else {
+        unreachable!()
+    };
+    assert_eq!(inputs.withdrawer, withdrawer);
+    let shard = shard_of(&inputs.nullifier);
+
+    let relayer_before = h.lamports(&relayer.pubkey()).await;
+    let ix = h
+        .on_behalf_ix(&relayer.pubkey(), proof.proof, inputs, None)
+        .await;
+    h.send(&[ix], &[&relayer]).await.unwrap();
+
+    assert_eq!(
+        h.lamports(&withdrawer).await,
+        AMOUNT - POOL_FEE - ON_BEHALF_FEE
+    );
+    assert_eq!(
+        h.lamports(&relayer.pubkey()).await,
+        relayer_before + ON_BEHALF_FEE
+    );
+    let ledger = h.pool().await.ledger;
+    assert_eq!(ledger.withdrawn, AMOUNT - POOL_FEE - ON_BEHALF_FEE);
+    assert_eq!(ledger.fees, POOL_FEE + ON_BEHALF_FEE);
+    assert_eq!(ledger.outstanding(), 0);
+    assert!(h
+        .shard(&shard)
+        .await
+        .unwrap()
+        .nullifiers()
+        .contains(&inputs.nullifier));
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn stealth_withdrawal_is_found_and_claimed() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    let note = Note::random(AMOUNT);
+    let index = deposit(&mut h, &mut provers, &[note]).await[0];
+
+    let recipient = SpendingKey::random(&mut rand::thread_rng());
+    let stealth =
+        derive_stealth_address(&recipient.stealth_meta_address(), &mut rand::thread_rng());
+    let relayer = Keypair::new();
+    let fund =
+        system_instruction::transfer(&h.ctx.payer.pubkey(), &relayer.pubkey(), 1_000_000_000);
+    h.send(&[fund], &[]).await.unwrap();
+
+    let root = h.spend_root().await;
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::WithdrawOnBehalf,
+        circuits::withdraw_on_behalf(&spend(&h, &note, index), &root, &stealth.address),
+    );
+    let InstructionInputs::OnBehalf(mut inputs) = inputs else {
+        unreachable!()
+    };
+    inputs.withdrawer = stealth.address;
+    let ix = h
+        .on_behalf_ix(
+            &relayer.pubkey(),
+            proof.proof,
+            inputs,
+            Some(stealth.ephemeral_key),
+        )
+        .await;
+    let logs = h.send_with_logs(&[ix], &[&relayer]).await.unwrap();
+
+    let found = scan_withdrawals(&recipient, logs.iter().map(String::as_str));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].pubkey(), stealth.address);
+    let net = AMOUNT - POOL_FEE - ON_BEHALF_FEE;
+    assert_eq!(h.lamports(&stealth.address).await, net);
+
+    // one signature pays the claim's fee
+    let wallet = Keypair::new().pubkey();
+    let claim = claim_ix(&found[0], &wallet, net - 5_000);
+    h.send_as_stealth(&[claim], &found[0]).await.unwrap();
+    assert_eq!(h.lamports(&wallet).await, net - 5_000);
+}
+
+#[tokio::test]
+#[ignore = "needs SOLNADO_CIRCUITS"]
+async fn combine_merges_and_splits_notes() {
+    let mut provers = provers();
+    let mut h = Harness::start_with_pool().await;
+    let (a, b) = (Note::random(AMOUNT), Note::random(2 * AMOUNT));
+    let indices = deposit(&mut h, &mut provers, &[a, b]).await;
+
+    // two notes into one
+    let merged = Note::random(3 * AMOUNT);
+    let root = h.spend_root().await;
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::Combine2To1,
+        circuits::combine2to1(
+            &spend(&h, &a, indices[0]),
+            &spend(&h, &b, indices[1]),
+            &merged,
+            &root,
+        ),
+    );
+    let InstructionInputs::CombineDouble(inputs) = inputs else {
+        unreachable!()
+    };
+    let (shard1, shard2) = (shard_of(&inputs.nullifier1), shard_of(&inputs.nullifier2));
+    let (pool_before, reserve_before) = (
+        h.lamports(&pool_pda()).await,
+        h.lamports(&rent_reserve()).await,
+    );
+    let ix = h.combine_double_ix(proof.proof, inputs).await;
+    h.insert_leaves(ix, &[merged.leaf()]).await.unwrap();
+
+    assert_eq!(h.lamports(&pool_pda()).await, pool_before);
+    assert_eq!(
+        h.lamports(&rent_reserve()).await,
+        reserve_before + 2 * POOL_FEE
+    );
+    assert!(h
+        .shard(&shard1)
+        .await
+        .unwrap()
+        .nullifiers()
+        .contains(&inputs.nullifier1));
+    assert!(h
+        .shard(&shard2)
+        .await
+        .unwrap()
+        .nullifiers()
+        .contains(&inputs.nullifier2));
+    assert_eq!(h.spend_root().await, h.tree.root());
+
+    // and back into two
+    let (c, d) = (Note::random(AMOUNT), Note::random(2 * AMOUNT));
+    let root = h.spend_root().await;
+    let (proof, inputs) = prove(
+        &mut provers,
+        Circuit::Combine1To2,
+        circuits::combine1to2(&spend(&h, &merged, 2), &c, &d, &root),
+    );
+    let InstructionInputs::CombineSingle(inputs) = inputs else {
+        unreachable!()
+    };
+    let shard = shard_of(&inputs.nullifier);
+    let ix = h.combine_single_ix(proof.proof, inputs).await;
+    h.insert_leaves(ix.clone(), &[c.leaf(), d.leaf()])
+        .await
+        .unwrap();
+
+    let pool = h.pool().await;
+    assert_eq!(pool.batch_leaves[3], c.leaf());
+    assert_eq!(pool.batch_leaves[4], d.leaf());
+    assert!(h
+        .shard(&shard)
+        .await
+        .unwrap()
+        .nullifiers()
+        .contains(&inputs.nullifier));
+    assert_eq!(h.spend_root().await, h.tree.root());
+
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::InvalidPublicInputRoot);
+}
@@ -0,0 +1,95 @@
+//! Pool registry: every new pool gets its own numbered entry, and namespaced identifiers
+//! belong to the creator they were derived for.
+mod common;
+
+use common::*;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_system_interface::instruction as system_instruction;
+use solnado::error::ErrorCode;
+use solnado::registry::{
+    namespaced_identifier, PoolEntry, PoolRegistry, MAX_POOL_NAME, NATIVE_MINT,
+};
+
+async fn funded_stranger(h: &mut Harness) -> Keypair {
+    let stranger = Keypair::new();
+    let fund =
+        system_instruction::transfer(&h.ctx.payer.pubkey(), &stranger.pubkey(), 1_000_000_000);
+    h.send(&[fund], &[]).await.unwrap();
+    stranger
+}
+
+#[tokio::test]
+async fn new_pools_are_listed_in_creation_order() {
+    let mut h = Harness::start_with_pool().await;
+    let stranger = funded_stranger(&mut h).await;
+    let other = *b"other_pool\0\0\0\0\0\0";
+    h.initialize_named_pool(other, "Other pool", false, Some(&stranger))
+        .await
+        .unwrap();
+
+    let first = h.pool_entry(POOL_ID).await.unwrap();
+    let second = h.pool_entry(other).await.unwrap();
+    assert_eq!(first.identifier, POOL_ID);
+    assert_eq!(first.creator, h.user.pubkey());
+    assert_eq!(first.name(), "harness_pool");
+    assert_eq!(first.asset, NATIVE_MINT);
+    assert_eq!(second.identifier, other);
+    assert_eq!(second.creator, stranger.pubkey());
+    assert_eq!(second.name(), "Other pool");
+    assert_eq!((first.index, second.index), (0, 1));
+    assert!(first.created_slot <= second.created_slot);
+
+    // the counter stays the same size however many pools there are
+    assert_eq!(h.pool_registry().await.unwrap().pool_count, 2);
+    let account = h.account(&pool_registry()).await.unwrap();
+    assert_eq!(account.data.len(), 8 + PoolRegistry::SIZE);
+    let account = h.account(&pool_entry(other)).await.unwrap();
+    assert_eq!(account.data.len(), 8 + PoolEntry::SIZE);
+}
+
+#[tokio::test]
+async fn namespaced_names_belong_to_their_creator() {
+    let mut h = Harness::start().await;
+    let stranger = funded_stranger(&mut h).await;
+    let mine = namespaced_identifier(&h.user.pubkey(), "main");
+
+    // the name's identifier under the user's key is out of reach for anyone else
+    assert_program_error(
+        h.initialize_named_pool(mine, "main", true, Some(&stranger))
+            .await,
+        ErrorCode::NamespaceMismatch,
+    );
+    assert_program_error(
+        h.initialize_named_pool(mine, "main", false, Some(&stranger))
+            .await,
+        ErrorCode::ReservedPoolIdentifier,
+    );
+    h.initialize_named_pool(mine, "main", true, None)
+        .await
+        .unwrap();
+
+    // the stranger gets their own "main"
+    let theirs = namespaced_identifier(&stranger.pubkey(), "main");
+    assert_ne!(theirs, mine);
+    h.initialize_named_pool(theirs, "main", true, Some(&stranger))
+        .await
+        .unwrap();
+    assert_eq!(h.pool_registry().await.unwrap().pool_count, 2);
+    assert_eq!(h.pool_entry(mine).await.unwrap().index, 0);
+    let entry = h.pool_entry(theirs).await.unwrap();
+    assert_eq!((entry.creator, entry.index), (stranger.pubkey(), 1));
+}
+
+#[tokio::test]
+async fn pool_names_are_bounded() {
+    let mut h = Harness::start().await;
+    let name = "n".repeat(MAX_POOL_NAME + 1);
+    assert_program_error(
+        h.initialize_named_pool(*b"long_name_pool\0\0", &name, false, None)
+            .await,
+        ErrorCode::PoolNameTooLong,
+    );
+    h.initialize_named_pool(*b"long_name_pool\0\0", &name[..MAX_POOL_NAME], false, None)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,147 @@
+//! In-place shard storage: the slot array stays sorted and bounded, and a split moves
+//! each nullifier to the child its next bit names. Splits through the program are in
+//! `spend.rs`.
+use anchor_lang::error::Error;
+use bytemuck::Zeroable;
+use solnado::error::ErrorCode;
+use solnado::shard::{
+    check_prefix, derive_shard_pda_key, leading_bits, prefix_bit, prefix_seed, BitShard,
+    PREFIX_BYTES, SHARD_CAPACITY, SHARD_SPACE,
+};
+
+fn empty_shard() -> Box<BitShard> {
+    Box::new(BitShard::zeroed())
+}
+
+fn nullifier(i: u32) -> [u8; 32] {
+    // spread the values so inserts land everywhere in the array
+    let mut n = [0u8; 32];
+    n[..4].copy_from_slice(&i.wrapping_mul(2_654_435_761).to_be_bytes());
+    n
+}
+
+/// One 0/1 per entry, packed MSB first
+fn pack(bits: &[u8]) -> [u8; PREFIX_BYTES] {
+    let mut prefix = [0u8; PREFIX_BYTES];
+    for (i, bit) in bits.iter().enumerate() {
+        prefix[i / 8] |= bit << (7 - i % 8);
+    }
+    prefix
+}
+
+fn assert_error(res: Result<(), Error>, code: ErrorCode) {
+    assert_eq!(res.unwrap_err(), Error::from(code));
+}
+
+#[test]
+fn layout_fits_a_cpi_allocation() {
+    const { assert!(SHARD_SPACE <= 10_240) };
+    assert_eq!(SHARD_SPACE, 8 + 16 + 32 * SHARD_CAPACITY);
+}
+
+#[test]
+fn inserts_keep_slots_sorted() {
+    let mut shard = empty_shard();
+    for i in 0..100 {
+        shard.insert(&nullifier(i)).unwrap();
+    }
+    assert_eq!(shard.count, 100);
+    assert!(shard.nullifiers().windows(2).all(|w| w[0] < w[1]));
+    assert!(shard.slots[100..].iter().all(|s| *s == [0u8; 32]));
+    for i in 0..100 {
+        assert!(shard.nullifiers().binary_search(&nullifier(i)).is_ok());
+    }
+}
+
+#[test]
+fn duplicate_is_rejected_and_shard_unchanged() {
+    let mut shard = empty_shard();
+    for i in 0..10 {
+        shard.insert(&nullifier(i)).unwrap();
+    }
+    let before = *shard;
+    assert_error(shard.insert(&nullifier(3)), ErrorCode::NullifierAlreadyUsed);
+    assert_eq!(shard.count, before.count);
+    assert_eq!(shard.slots, before.slots);
+}
+
+#[test]
+fn full_shard_rejects_new_nullifiers() {
+    let mut shard = empty_shard();
+    for i in 0..SHARD_CAPACITY as u32 {
+        shard.insert(&nullifier(i)).unwrap();
+    }
+    assert_error(
+        shard.insert(&nullifier(SHARD_CAPACITY as u32)),
+        ErrorCode::ShardFull,
+    );
+    // still a duplicate first
+    assert_error(shard.insert(&nullifier(0)), ErrorCode::NullifierAlreadyUsed);
+    assert_eq!(shard.count as usize, SHARD_CAPACITY);
+}
+
+#[test]
+fn reset_clears_slots() {
+    let mut shard = empty_shard();
+    shard.insert(&nullifier(1)).unwrap();
+    shard.reset(1, pack(&[1]));
+    assert_eq!(shard.count, 0);
+    assert_eq!(shard.prefix_len, 1);
+    assert_eq!(shard.prefix[0], 0x80);
+    assert!(shard.slots.iter().all(|s| *s == [0u8; 32]));
+}
+
+#[test]
+fn split_moves_uneven_halves() {
+    let mut parent = empty_shard();
+    parent.reset(1, pack(&[0]));
+    let mut low = [0u8; 32];
+    low[0] = 0x10;
+    parent.insert(&low).unwrap();
+    for i in 0..5u8 {
+        let mut high = [0u8; 32];
+        high[0] = 0x40 | i;
+        parent.insert(&high).unwrap();
+    }
+
+    let (mut child0, mut child1) = (empty_shard(), empty_shard());
+    parent.split_into(&mut child0, &mut child1);
+    assert_eq!((child0.count, child1.count), (1, 5));
+    assert_eq!(child0.prefix_len, 2);
+    assert_eq!(child1.prefix, pack(&[0, 1]));
+    assert_eq!(child0.nullifiers(), &[low]);
+    assert!(child1.nullifiers().windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn prefix_bits_are_read_msb_first() {
+    let mut n = [0u8; 32];
+    n[0] = 0b1010_0000;
+    n[1] = 0b0000_0001;
+    let bits: Vec<u8> = (0..16).map(|i| prefix_bit(&n, i)).collect();
+    assert_eq!(bits, [1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+    assert_eq!(leading_bits(&n, 3), pack(&[1, 0, 1]));
+    assert_eq!(leading_bits(&n, 16), pack(&bits));
+    assert_eq!(leading_bits(&[0xff; 32], 12)[..2], [0xff, 0xf0]);
+    assert_eq!(prefix_seed(&pack(&bits), 9).len(), 2);
+
+    assert!(check_prefix(&n, &pack(&[1, 0, 1]), 3).is_ok());
+    assert_error(
+        check_prefix(&n, &pack(&[1, 1]), 2),
+        ErrorCode::InvalidNullifierBits,
+    );
+}
+
+#[test]
+fn same_packed_bytes_at_different_depths_are_different_shards() {
+    let pool_id = [4u8; 16];
+    let zeros = [0u8; PREFIX_BYTES];
+    let keys: Vec<_> = [1u8, 2, 8, 9, 64]
+        .iter()
+        .map(|&len| derive_shard_pda_key(pool_id, &zeros, len).0)
+        .collect();
+    for (i, a) in keys.iter().enumerate() {
+        assert!(!keys[i + 1..].contains(a));
+    }
+}
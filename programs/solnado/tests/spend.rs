@@ -0,0 +1,257 @@
+//! The spend paths of `proofs.rs` proven for real on every run: withdrawals go through
+//! the native `withdraw_association` circuit, which needs no artifacts, so the shard
+//! writes, splits and directory updates and the paths of notes on batch boundaries are
+//! checked without `SOLNADO_CIRCUITS`. Splits are only driven through the program, over
+//! as many shards as a handful of real spends reach.
+mod common;
+
+use common::circuits::{dev_prover, withdraw_association, Note, Spend};
+use common::tree::ShadowTree;
+use common::*;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solnado::directory::{directory_space, ShardPrefix};
+use solnado::error::ErrorCode;
+use solnado::inputs::AssociationWithdrawInputs;
+use solnado::ledger::PoolLedger;
+use solnado::shard::{derive_shard_pda_key, BitShard, MAX_PREFIX_BITS, POOL_FEE, SHARD_SPACE};
+use solnado_prover::native::WithdrawAssociation;
+use solnado_prover::InstructionInputs;
+
+const AMOUNT: u64 = 10_000_000;
+
+/// Seeds `notes` into the pool and publishes an association set holding all of them, in
+/// the same order
+async fn seed(h: &mut Harness, notes: &[Note]) -> ShadowTree {
+    h.initialize_association_registry(None).await.unwrap();
+    let curator = Keypair::new();
+    h.set_association_curator(curator.pubkey(), true, None)
+        .await
+        .unwrap();
+    let mut set = ShadowTree::new();
+    for note in notes {
+        h.seed_leaf(note.leaf(), note.amount).await;
+        set.insert(note.leaf());
+    }
+    h.publish_association_root(set.root(), &curator)
+        .await
+        .unwrap();
+    set
+}
+
+/// Proof and inputs of the withdrawal of the note at `index` to the user
+async fn prove_withdrawal(
+    h: &mut Harness,
+    set: &ShadowTree,
+    note: &Note,
+    index: usize,
+) -> ([u8; 256], AssociationWithdrawInputs) {
+    let (siblings, bits) = h.tree.path(index);
+    let spend = Spend {
+        note,
+        siblings,
+        bits,
+    };
+    let proof = dev_prover::<WithdrawAssociation>()
+        .prove(
+            withdraw_association(&spend, set.path(index), &h.user.pubkey()),
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+    let InstructionInputs::Association(inputs) = proof.instruction_inputs().unwrap() else {
+        panic!("association circuit must produce association inputs");
+    };
+    assert_eq!(inputs.root, h.spend_root().await);
+    (proof.proof, inputs)
+}
+
+/// Withdrawal of the note at `index` to the user
+async fn withdraw(
+    h: &mut Harness,
+    set: &ShadowTree,
+    note: &Note,
+    index: usize,
+) -> (Instruction, AssociationWithdrawInputs) {
+    let (proof, inputs) = prove_withdrawal(h, set, note, index).await;
+    (h.association_withdraw_ix(proof, inputs).await, inputs)
+}
+
+/// Live shards of the directory with their accounts, checked against their entries
+async fn live_shards(h: &mut Harness, threshold: usize) -> Vec<(ShardPrefix, BitShard)> {
+    let mut shards = Vec::new();
+    for entry in h.directory().await {
+        let key = derive_shard_pda_key(POOL_ID, &entry.prefix, entry.len).0;
+        let account = h.account(&key).await.expect("listed shards exist");
+        let shard: BitShard = bytemuck::pod_read_unaligned(&account.data[8..SHARD_SPACE]);
+        assert_eq!((shard.prefix_len, shard.prefix), (entry.len, entry.prefix));
+        assert!(shard.nullifiers().windows(2).all(|w| w[0] < w[1]));
+        assert!(shard.nullifiers().iter().all(|n| entry.contains(n)));
+        if (entry.len as usize) < MAX_PREFIX_BITS {
+            assert!(shard.count as usize <= threshold + entry.len as usize);
+        }
+        shards.push((entry, shard));
+    }
+    shards
+}
+
+#[tokio::test]
+async fn withdraw_spends_note_once() {
+    let mut h = Harness::start_with_pool().await;
+    let note = Note::random(AMOUNT);
+    let set = seed(&mut h, &[note]).await;
+    let (ix, inputs) = withdraw(&mut h, &set, &note, 0).await;
+    let shard = shard_of(&inputs.nullifier);
+
+    let user = h.user.pubkey();
+    let (user_before, pool_before) = (h.lamports(&user).await, h.lamports(&pool_pda()).await);
+    let reserve_before = h.lamports(&rent_reserve()).await;
+    h.send(std::slice::from_ref(&ix), &[]).await.unwrap();
+
+    assert_eq!(h.lamports(&user).await, user_before + AMOUNT - POOL_FEE);
+    // the whole note leaves the pool, its fee goes to the rent reserve
+    assert_eq!(h.lamports(&pool_pda()).await, pool_before - AMOUNT);
+    assert_eq!(h.lamports(&rent_reserve()).await, reserve_before + POOL_FEE);
+    let stored = h.shard(&shard).await.unwrap();
+    assert_eq!(stored.nullifiers(), &[inputs.nullifier]);
+    assert_eq!(stored.count, 1);
+    assert_eq!(
+        h.pool().await.ledger,
+        PoolLedger {
+            deposited: AMOUNT,
+            withdrawn: AMOUNT - POOL_FEE,
+            fees: POOL_FEE,
+            rent_spent: 0,
+        }
+    );
+    h.audit_pool().await.unwrap();
+
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::NullifierAlreadyUsed);
+}
+
+#[tokio::test]
+async fn full_shard_splits_and_updates_directory() {
+    let mut h = Harness::start_with_split_threshold(1).await;
+    let notes = [Note::random(AMOUNT), Note::random(AMOUNT)];
+    let set = seed(&mut h, &notes).await;
+    let pool_before = h.lamports(&pool_pda()).await;
+
+    let mut nullifiers = Vec::new();
+    for (index, note) in notes.iter().enumerate() {
+        let (ix, inputs) = withdraw(&mut h, &set, note, index).await;
+        nullifiers.push(inputs.nullifier);
+        if index == 1 {
+            // the second spend splits, the fees so far don't cover a shard
+            assert_program_error(
+                h.send(std::slice::from_ref(&ix), &[]).await,
+                ErrorCode::RentReserveShort,
+            );
+            h.fund_rent_reserve(100_000_000).await.unwrap();
+        }
+        h.send(&[ix], &[]).await.unwrap();
+    }
+    // shard rent never came out of the notes
+    assert_eq!(h.lamports(&pool_pda()).await, pool_before - 2 * AMOUNT);
+    // two children for the parent, one more directory entry
+    let rent = Rent::default();
+    let directory_growth =
+        rent.minimum_balance(directory_space(3)) - rent.minimum_balance(directory_space(2));
+    assert_eq!(
+        h.pool().await.ledger.rent_spent,
+        rent.minimum_balance(SHARD_SPACE) + directory_growth
+    );
+    h.audit_pool().await.unwrap();
+
+    // field elements have their two top bits clear: [0] filled up and split on bit 1,
+    // both nullifiers sorting to [0, 0]
+    assert!(h.account(&shard_pda(&[0])).await.is_none());
+    let (low, mid, high) = (pack_bits(&[0, 0]), pack_bits(&[0, 1]), pack_bits(&[1]));
+    assert_eq!(
+        h.directory().await,
+        [
+            ShardPrefix::new(2, low),
+            ShardPrefix::new(2, mid),
+            ShardPrefix::new(1, high),
+        ]
+    );
+    for nullifier in &nullifiers {
+        let resolved = h.resolve_shard(nullifier).await;
+        assert_eq!(resolved.shard, shard_pda(&[0, 0]));
+        assert!(h
+            .shard(&[0, 0])
+            .await
+            .unwrap()
+            .nullifiers()
+            .contains(nullifier));
+    }
+    assert!(h.shard(&[0, 1]).await.unwrap().nullifiers().is_empty());
+}
+
+#[tokio::test]
+async fn splits_keep_every_nullifier_findable() {
+    let threshold = 1;
+    let mut h = Harness::start_with_split_threshold(threshold as u16).await;
+    let notes: Vec<_> = (0..6).map(|_| Note::random(AMOUNT)).collect();
+    let set = seed(&mut h, &notes).await;
+    h.fund_rent_reserve(1_000_000_000).await.unwrap();
+
+    let mut spent = Vec::new();
+    let mut first = None;
+    for (index, note) in notes.iter().enumerate() {
+        let (proof, inputs) = prove_withdrawal(&mut h, &set, note, index).await;
+        let ix = h.association_withdraw_ix(proof, inputs).await;
+        h.send(&[ix], &[]).await.unwrap();
+        spent.push(inputs.nullifier);
+        first.get_or_insert((proof, inputs));
+
+        let shards = live_shards(&mut h, threshold).await;
+        let total: usize = shards.iter().map(|(_, s)| s.count as usize).sum();
+        assert_eq!(total, spent.len());
+        for nullifier in &spent {
+            let resolved = h.resolve_shard(nullifier).await;
+            let (_, shard) = shards
+                .iter()
+                .find(|(entry, _)| {
+                    derive_shard_pda_key(POOL_ID, &entry.prefix, entry.len).0 == resolved.shard
+                })
+                .expect("resolves to a listed shard");
+            assert!(shard.nullifiers().binary_search(nullifier).is_ok());
+        }
+    }
+    // the second spend split [0] and the third [0, 0], both sure to be full
+    assert!(h.directory().await.len() >= 4);
+    h.audit_pool().await.unwrap();
+
+    // the spent shard moved, the nullifier is still found in its new one
+    let (proof, inputs) = first.unwrap();
+    let ix = h.association_withdraw_ix(proof, inputs).await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::NullifierAlreadyUsed);
+}
+
+#[tokio::test]
+async fn notes_on_batch_boundaries_are_spendable() {
+    let mut h = Harness::start_with_pool().await;
+    let notes: Vec<_> = (0..18).map(|_| Note::random(AMOUNT)).collect();
+    let set = seed(&mut h, &notes).await;
+    // the first batch rolled into the peaks, the second holds two leaves
+    let pool = h.pool().await;
+    assert_eq!(pool.batch_number, 1);
+    assert_eq!(pool.number_of_peaks, 1);
+    assert_eq!(pool.find_first_match(), 2);
+    assert_eq!(h.spend_root().await, h.tree.root());
+
+    let user = h.user.pubkey();
+    // both sides of the sub batch and batch boundaries
+    for index in [7, 8, 15, 16, 17] {
+        let (ix, _) = withdraw(&mut h, &set, &notes[index], index).await;
+        let before = h.lamports(&user).await;
+        h.send(&[ix], &[]).await.unwrap();
+        assert_eq!(
+            h.lamports(&user).await,
+            before + AMOUNT - POOL_FEE,
+            "{index}"
+        );
+    }
+    assert_eq!(h.pool().await.ledger.withdrawn, 5 * (AMOUNT - POOL_FEE));
+    h.audit_pool().await.unwrap();
+}
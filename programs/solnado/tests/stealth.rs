@@ -0,0 +1,38 @@
+//! One-time withdrawal addresses: the recipient's wallet claims their funds with a
+//! signature the runtime accepts.
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use solana_sdk::signature::Signer;
+use solana_system_interface::instruction as system_instruction;
+use solnado_sdk::keys::SpendingKey;
+use solnado_sdk::stealth::{claim_ix, derive_stealth_address, open_stealth_address};
+
+const FUNDS: u64 = 50_000_000;
+/// One signature
+const FEE: u64 = 5_000;
+
+#[tokio::test]
+async fn recipient_claims_a_stealth_address() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut h = Harness::start().await;
+    let recipient = SpendingKey::random(&mut rng);
+    let stealth = derive_stealth_address(&recipient.stealth_meta_address(), &mut rng);
+
+    // what withdraw_on_behalf_shard pays to the one-time address
+    let fund = system_instruction::transfer(&h.ctx.payer.pubkey(), &stealth.address, FUNDS);
+    h.send(&[fund], &[]).await.unwrap();
+
+    let keypair = open_stealth_address(&recipient, &stealth.address, &stealth.ephemeral_key)
+        .expect("derived for the recipient");
+    assert_eq!(keypair.pubkey(), stealth.address);
+    let wallet = Pubkey::new_unique();
+    let claim = claim_ix(&keypair, &wallet, FUNDS - FEE);
+    h.send_as_stealth(&[claim], &keypair).await.unwrap();
+
+    assert_eq!(h.lamports(&wallet).await, FUNDS - FEE);
+    assert_eq!(h.lamports(&stealth.address).await, 0);
+}
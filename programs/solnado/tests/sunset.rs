@@ -0,0 +1,208 @@
+//! Pool lifecycle: a sunset pool takes no deposits, and once the grace period is over
+//! its authority closes it and reclaims the rent of its empty indexers and shards. Notes
+//! can still be spent after that.
+mod common;
+
+use common::circuits::{dev_prover, withdraw_association, Note, Spend};
+use common::tree::ShadowTree;
+use common::*;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solnado::error::ErrorCode;
+use solnado::events::{PoolClosed, PoolSunset, RentReclaimed};
+use solnado::inputs::DepositInputs;
+use solnado::shard::{POOL_FEE, SHARD_SPACE};
+use solnado::sunset::{PoolStatus, SUNSET_GRACE_SECONDS};
+use solnado::DEFAULT_LEAF;
+use solnado_prover::native::WithdrawAssociation;
+use solnado_prover::InstructionInputs;
+
+fn dummy_deposit(h: &Harness) -> solana_sdk::instruction::Instruction {
+    h.deposit_ix(
+        [0u8; 256],
+        DepositInputs {
+            amount: 10_000_000,
+            leaf1: [7u8; 32],
+            leaf2: DEFAULT_LEAF,
+        },
+    )
+}
+
+/// Sunsets the pool and lets the whole grace period pass
+async fn sunset_and_wait(h: &mut Harness) {
+    h.sunset_pool(None).await.unwrap();
+    h.advance_clock(SUNSET_GRACE_SECONDS).await;
+}
+
+#[tokio::test]
+async fn sunset_stops_deposits() {
+    let mut h = Harness::start_with_pool().await;
+    assert_eq!(h.pool().await.status, PoolStatus::Active);
+    let stranger = Keypair::new();
+    assert_program_error(
+        h.sunset_pool(Some(&stranger)).await,
+        ErrorCode::UnauthorizedAction,
+    );
+
+    let ix = h.sunset_pool_ix(h.user.pubkey());
+    let logs = h.send_with_logs(&[ix], &[]).await.unwrap();
+    let pool = h.pool().await;
+    assert_eq!(pool.status, PoolStatus::Sunset);
+    assert!(pool.sunset_at > 0);
+    assert_eq!(
+        stubs::events::<PoolSunset>(&logs),
+        [PoolSunset {
+            pool: POOL_ID,
+            closes_after: pool.sunset_at + SUNSET_GRACE_SECONDS,
+        }]
+    );
+
+    let ix = dummy_deposit(&h);
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::DepositsClosed);
+    assert_program_error(h.sunset_pool(None).await, ErrorCode::PoolNotActive);
+}
+
+#[tokio::test]
+async fn close_waits_for_the_grace_period() {
+    let mut h = Harness::start_with_pool().await;
+    assert_program_error(h.close_pool(&[]).await, ErrorCode::PoolNotSunset);
+
+    h.sunset_pool(None).await.unwrap();
+    h.advance_clock(SUNSET_GRACE_SECONDS - 60).await;
+    assert_program_error(h.close_pool(&[]).await, ErrorCode::SunsetGracePending);
+
+    h.advance_clock(60).await;
+    h.close_pool(&[]).await.unwrap();
+    assert_eq!(h.pool().await.status, PoolStatus::Closed);
+    let ix = dummy_deposit(&h);
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::DepositsClosed);
+    assert_program_error(h.sunset_pool(None).await, ErrorCode::PoolNotActive);
+    // closing again only reclaims
+    h.close_pool(&[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn close_reclaims_empty_indexers_and_shards() {
+    let mut h = Harness::start_with_pool().await;
+    sunset_and_wait(&mut h).await;
+    let reclaimed = [leaves_indexer(), subtree_indexer(), shard_pda(&[0])];
+    let mut rent = 0;
+    for key in &reclaimed {
+        rent += h.lamports(key).await;
+    }
+    let user = h.user.pubkey();
+    let user_before = h.lamports(&user).await;
+
+    let ix = h.close_pool_ix(&reclaimed);
+    let logs = h.send_with_logs(&[ix], &[]).await.unwrap();
+
+    assert_eq!(h.lamports(&user).await, user_before + rent);
+    for key in &reclaimed {
+        assert!(h.account(key).await.is_none());
+    }
+    assert_eq!(
+        stubs::events::<PoolClosed>(&logs),
+        [PoolClosed { pool: POOL_ID }]
+    );
+    assert_eq!(stubs::events::<RentReclaimed>(&logs).len(), reclaimed.len());
+    assert_eq!(
+        stubs::events::<RentReclaimed>(&logs)[2].account,
+        shard_pda(&[0])
+    );
+}
+
+#[tokio::test]
+async fn reclaimed_accounts_are_gone_for_the_rest_of_the_transaction() {
+    let mut h = Harness::start_with_pool().await;
+    sunset_and_wait(&mut h).await;
+    // the second reclaim would pass its checks if the first one left the owner or the data
+    for key in [leaves_indexer(), shard_pda(&[0])] {
+        let ixs = [h.close_pool_ix(&[key]), h.close_pool_ix(&[key])];
+        assert_program_error(h.send(&ixs, &[]).await, ErrorCode::InvalidReclaimAccount);
+    }
+    h.close_pool(&[leaves_indexer(), shard_pda(&[0])])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn close_keeps_shards_holding_nullifiers_and_foreign_accounts() {
+    let mut h = Harness::start_with_pool().await;
+    sunset_and_wait(&mut h).await;
+    h.close_pool(&[]).await.unwrap();
+
+    // a spent nullifier in shard [1]
+    let shard = shard_pda(&[1]);
+    let mut account = h.account(&shard).await.unwrap();
+    account.data[8..12].copy_from_slice(&1u32.to_le_bytes());
+    h.ctx.set_account(&shard, &AccountSharedData::from(account));
+    assert_program_error(h.close_pool(&[shard]).await, ErrorCode::ShardNotEmpty);
+
+    // program accounts other than shards, and accounts of other programs
+    for key in [rent_reserve(), pool_pda(), Keypair::new().pubkey()] {
+        assert_program_error(h.close_pool(&[key]).await, ErrorCode::InvalidReclaimAccount);
+    }
+    assert!(h.account(&shard).await.is_some());
+}
+
+#[tokio::test]
+async fn closed_pool_pays_out_and_recreates_reclaimed_shards() {
+    let mut h = Harness::start_with_pool().await;
+    h.initialize_association_registry(None).await.unwrap();
+    let curator = Keypair::new();
+    h.set_association_curator(curator.pubkey(), true, None)
+        .await
+        .unwrap();
+    let note = Note::random(1_000_000_000);
+    h.seed_leaf(note.leaf(), note.amount).await;
+    let mut set = ShadowTree::new();
+    set.insert(note.leaf());
+    h.publish_association_root(set.root(), &curator)
+        .await
+        .unwrap();
+
+    // nothing was spent yet, both shards go
+    sunset_and_wait(&mut h).await;
+    let shards = [shard_pda(&[0]), shard_pda(&[1])];
+    h.close_pool(&shards).await.unwrap();
+    for key in &shards {
+        assert!(h.account(key).await.is_none());
+    }
+
+    let (siblings, bits) = h.tree.path(0);
+    let spend = Spend {
+        note: &note,
+        siblings,
+        bits,
+    };
+    let proof = dev_prover::<WithdrawAssociation>()
+        .prove(
+            withdraw_association(&spend, set.path(0), &h.user.pubkey()),
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+    let InstructionInputs::Association(inputs) = proof.instruction_inputs().unwrap() else {
+        panic!("association circuit must produce association inputs");
+    };
+
+    // the reserve pays for the shard the nullifier lands in, anyone can top it up
+    let ix = h.association_withdraw_ix(proof.proof, inputs).await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::RentReserveShort);
+    let shard_rent = Rent::default().minimum_balance(SHARD_SPACE);
+    h.fund_rent_reserve(shard_rent).await.unwrap();
+
+    let user = h.user.pubkey();
+    let before = h.lamports(&user).await;
+    let ix = h.association_withdraw_ix(proof.proof, inputs).await;
+    h.send(&[ix], &[]).await.unwrap();
+    assert_eq!(h.lamports(&user).await - before, note.amount - POOL_FEE);
+    let shard = shard_pda(&[inputs.nullifier[0] >> 7]);
+    let account = h.account(&shard).await.unwrap();
+    assert_eq!(account.data.len(), SHARD_SPACE);
+    assert_eq!(h.pool().await.ledger.rent_spent, shard_rent);
+    h.audit_pool().await.unwrap();
+
+    let ix = h.association_withdraw_ix(proof.proof, inputs).await;
+    assert_program_error(h.send(&[ix], &[]).await, ErrorCode::NullifierAlreadyUsed);
+}
@@ -0,0 +1,66 @@
+//! Prints the verifying key of a native circuit's development keys as a
+//! `verifying_key.rs` constant.
+//!
+//!     cargo run --release -p solnado-prover --example native_setup -- withdraw_association
+//!
+//! The keys come from a public seed, anyone can forge proofs for them: the program only
+//! holds them with its `dev-keys` feature.
+use solnado_prover::native::{IndexedInsert, NativeCircuit, NativeProver, WithdrawAssociation};
+use solnado_prover::{Circuit, VerifyingKeyBytes};
+
+fn setup<C: NativeCircuit>() -> NativeProver {
+    NativeProver::dev_setup::<C>().expect("key generation")
+}
+
+fn bytes(out: &mut String, name: &str, points: &[&[u8]]) {
+    out.push_str(&format!("\t{}: [\n", name));
+    for point in points {
+        for half in point.chunks(32) {
+            let line: Vec<String> = half.iter().map(|b| b.to_string()).collect();
+            out.push_str(&format!("\t\t{},\n", line.join(",")));
+        }
+    }
+    out.push_str("\t],\n\n");
+}
+
+fn constant(name: &str, vk: &VerifyingKeyBytes) -> String {
+    let mut out = format!(
+        "pub const {}: Groth16Verifyingkey = Groth16Verifyingkey {{\n\tnr_pubinputs: {},\n\n",
+        name,
+        vk.nr_pubinputs()
+    );
+    bytes(&mut out, "vk_alpha_g1", &[&vk.alpha_g1]);
+    bytes(&mut out, "vk_beta_g2", &[&vk.beta_g2]);
+    bytes(&mut out, "vk_gamme_g2", &[&vk.gamma_g2]);
+    bytes(&mut out, "vk_delta_g2", &[&vk.delta_g2]);
+    out.push_str("\tvk_ic: &[\n");
+    for ic in &vk.ic {
+        out.push_str("\t\t[\n");
+        for half in ic.chunks(32) {
+            let line: Vec<String> = half.iter().map(|b| b.to_string()).collect();
+            out.push_str(&format!("\t\t\t{},\n", line.join(",")));
+        }
+        out.push_str("\t\t],\n");
+    }
+    out.push_str("\t]\n};\n");
+    out
+}
+
+fn main() {
+    let name = std::env::args().nth(1).expect("circuit name");
+    let (prover, constant_name) = match name.as_str() {
+        "withdraw_association" => (setup::<WithdrawAssociation>(), "WITHDRAW_ASSOCIATION_VK"),
+        "indexed_insert" => (setup::<IndexedInsert>(), "INDEXED_INSERT_VK"),
+        other => panic!("{} is not a native circuit", other),
+    };
+    assert_eq!(
+        prover.circuit(),
+        Circuit::ALL
+            .iter()
+            .copied()
+            .find(|c| c.name() == name)
+            .unwrap()
+    );
+
+    print!("{}", constant(constant_name, &prover.verifying_key_bytes()));
+}
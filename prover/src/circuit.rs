@@ -0,0 +1,195 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::AnchorSerialize;
+use solnado::inputs::*;
+use solnado::DEFAULT_LEAF;
+
+use crate::ProverError;
+
+/// Every circuit the program verifies proofs of
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Circuit {
+    /// `deposit_variable` with a single new leaf
+    Deposit1,
+    /// `deposit_variable` with two new leaves
+    Deposit2,
+    /// `combine_deposit_shard_single`
+    Combine1To2,
+    /// `combine_deposit_shard_double`
+    Combine2To1,
+    /// `withdraw_variable_shard` mode 0
+    Withdraw,
+    /// `withdraw_variable_shard` mode 1
+    WithdrawAndAdd,
+    /// `withdraw_on_behalf_shard`
+    WithdrawOnBehalf,
+    /// `withdraw_with_association`, native (see `native::association`)
+    WithdrawAssociation,
+    /// `nullifier_insertion_proof` of indexed pools, native (see `native::indexed`)
+    IndexedInsert,
+}
+
+impl Circuit {
+    pub const ALL: [Circuit; 9] = [
+        Circuit::Deposit1,
+        Circuit::Deposit2,
+        Circuit::Combine1To2,
+        Circuit::Combine2To1,
+        Circuit::Withdraw,
+        Circuit::WithdrawAndAdd,
+        Circuit::WithdrawOnBehalf,
+        Circuit::WithdrawAssociation,
+        Circuit::IndexedInsert,
+    ];
+
+    /// Base name of the `<name>.wasm` witness generator and `<name>.zkey` proving key, or
+    /// of the `keys/<name>.pk` proving key of a native circuit
+    pub fn name(&self) -> &'static str {
+        match self {
+            Circuit::Deposit1 => "deposit1",
+            Circuit::Deposit2 => "deposit2",
+            Circuit::Combine1To2 => "combine1to2",
+            Circuit::Combine2To1 => "combine2to1",
+            Circuit::Withdraw => "withdraw",
+            Circuit::WithdrawAndAdd => "withdraw_and_add",
+            Circuit::WithdrawOnBehalf => "withdraw_on_behalf",
+            Circuit::WithdrawAssociation => "withdraw_association",
+            Circuit::IndexedInsert => "indexed_insert",
+        }
+    }
+
+    /// Number of public signals, same as `nr_pubinputs - 1` of the on-chain key
+    pub fn num_public_inputs(&self) -> usize {
+        match self {
+            Circuit::Deposit1 => 2,
+            Circuit::Deposit2 => 3,
+            Circuit::Combine1To2 | Circuit::Combine2To1 => 4,
+            Circuit::Withdraw | Circuit::WithdrawOnBehalf => 4,
+            Circuit::WithdrawAndAdd => 5,
+            Circuit::IndexedInsert => 6,
+            Circuit::WithdrawAssociation => 7,
+        }
+    }
+
+    /// Rebuilds the instruction argument from the public signals, in verifier order
+    pub fn instruction_inputs(
+        &self,
+        signals: &[[u8; 32]],
+    ) -> Result<InstructionInputs, ProverError> {
+        if signals.len() != self.num_public_inputs() {
+            return Err(ProverError::PublicInputCount {
+                expected: self.num_public_inputs(),
+                got: signals.len(),
+            });
+        }
+        let s = signals;
+        Ok(match self {
+            Circuit::Deposit1 => InstructionInputs::Deposit(DepositInputs {
+                amount: field_to_amount(&s[0])?,
+                leaf1: s[1],
+                leaf2: DEFAULT_LEAF,
+            }),
+            Circuit::Deposit2 => InstructionInputs::Deposit(DepositInputs {
+                amount: field_to_amount(&s[0])?,
+                leaf1: s[1],
+                leaf2: s[2],
+            }),
+            Circuit::Combine1To2 => InstructionInputs::CombineSingle(CombineSingleInputs {
+                nullifier: s[0],
+                leaf1: s[1],
+                leaf2: s[2],
+                root: s[3],
+            }),
+            Circuit::Combine2To1 => InstructionInputs::CombineDouble(CombineDoubleInputs {
+                nullifier1: s[0],
+                nullifier2: s[1],
+                new_leaf: s[2],
+                root: s[3],
+            }),
+            Circuit::Withdraw => InstructionInputs::Withdraw(WithdrawInputs {
+                amount: field_to_amount(&s[0])?,
+                asset_id: s[1],
+                nullifier: s[2],
+                root: s[3],
+                new_leaf: DEFAULT_LEAF,
+            }),
+            Circuit::WithdrawAndAdd => InstructionInputs::Withdraw(WithdrawInputs {
+                amount: field_to_amount(&s[0])?,
+                asset_id: s[1],
+                nullifier: s[2],
+                new_leaf: s[3],
+                root: s[4],
+            }),
+            Circuit::WithdrawOnBehalf => InstructionInputs::OnBehalf(OnBehalfInputs {
+                amount: field_to_amount(&s[0])?,
+                nullifier: s[1],
+                withdrawer: Pubkey::new_from_array(s[2]),
+                root: s[3],
+            }),
+            Circuit::WithdrawAssociation => {
+                InstructionInputs::Association(AssociationWithdrawInputs {
+                    amount: field_to_amount(&s[0])?,
+                    asset_id: s[1],
+                    nullifier: s[2],
+                    root: s[3],
+                    association_root: s[4],
+                    recipient: limbs_to_pubkey(&s[5], &s[6])?,
+                })
+            }
+            Circuit::IndexedInsert => InstructionInputs::Insertion(IndexedInsertInputs {
+                nullifier: s[0],
+                old_root: s[1],
+                next_index: field_to_amount(&s[2])?,
+                low_index: field_to_amount(&s[3])?,
+                low_subtree: s[4],
+                new_leaf: s[5],
+            }),
+        })
+    }
+}
+
+/// The typed argument of the instruction a circuit's proof is submitted to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstructionInputs {
+    Deposit(DepositInputs),
+    CombineSingle(CombineSingleInputs),
+    CombineDouble(CombineDoubleInputs),
+    Withdraw(WithdrawInputs),
+    OnBehalf(OnBehalfInputs),
+    Association(AssociationWithdrawInputs),
+    Insertion(IndexedInsertInputs),
+}
+
+impl InstructionInputs {
+    /// Borsh bytes of the argument, as they appear in the instruction data after the proof
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let res = match self {
+            InstructionInputs::Deposit(i) => i.try_to_vec(),
+            InstructionInputs::CombineSingle(i) => i.try_to_vec(),
+            InstructionInputs::CombineDouble(i) => i.try_to_vec(),
+            InstructionInputs::Withdraw(i) => i.try_to_vec(),
+            InstructionInputs::OnBehalf(i) => i.try_to_vec(),
+            InstructionInputs::Association(i) => i.try_to_vec(),
+            InstructionInputs::Insertion(i) => i.try_to_vec(),
+        };
+        res.expect("fixed size structs always serialize")
+    }
+}
+
+/// Inverse of `amount_to_field`, fails if the field element does not fit in a u64
+pub fn field_to_amount(field: &[u8; 32]) -> Result<u64, ProverError> {
+    if field[..24].iter().any(|b| *b != 0) {
+        return Err(ProverError::AmountOverflow);
+    }
+    Ok(u64::from_be_bytes(field[24..].try_into().unwrap()))
+}
+
+/// Inverse of `pubkey_to_limbs`
+pub fn limbs_to_pubkey(hi: &[u8; 32], lo: &[u8; 32]) -> Result<Pubkey, ProverError> {
+    if hi[..16].iter().chain(&lo[..16]).any(|b| *b != 0) {
+        return Err(ProverError::KeyLimbOverflow);
+    }
+    let mut key = [0u8; 32];
+    key[..16].copy_from_slice(&hi[16..]);
+    key[16..].copy_from_slice(&lo[16..]);
+    Ok(Pubkey::new_from_array(key))
+}
@@ -0,0 +1,250 @@
+//! Native Groth16 prover for the solnado circuits.
+//!
+//! Loads the circom witness generator (`<circuit>.wasm`), the snarkjs proving key
+//! (`<circuit>.zkey`) and the signal names (`<circuit>.sym`) of each circuit, generates
+//! proofs with arkworks and serializes them into the exact 256 byte layout and typed
+//! public inputs the program expects. Circuits without circom sources are written as
+//! arkworks constraints in `native` and proven with development keys.
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use ark_bn254::{Bn254, Fr};
+use ark_circom::{read_zkey, CircomReduction, WitnessCalculator};
+use ark_groth16::{Groth16, Proof, ProvingKey};
+use ark_relations::r1cs::ConstraintMatrices;
+use ark_std::rand::{CryptoRng, RngCore};
+use ark_std::UniformRand;
+use num_bigint::BigInt;
+use wasmer::Store;
+
+pub mod circuit;
+pub mod native;
+mod probestack;
+pub mod serialize;
+pub mod symbols;
+
+pub use circuit::{Circuit, InstructionInputs};
+pub use serialize::*;
+pub use symbols::SignalNames;
+
+#[derive(Debug)]
+pub enum ProverError {
+    /// A circuit artifact could not be opened
+    Io(PathBuf, std::io::Error),
+    /// The zkey could not be parsed
+    Zkey(String),
+    /// The witness generator rejected the inputs
+    Witness(String),
+    /// Groth16 proving failed
+    Proving(String),
+    PublicInputCount {
+        expected: usize,
+        got: usize,
+    },
+    /// An amount signal is larger than a u64
+    AmountOverflow,
+    /// A key limb signal is larger than 16 bytes
+    KeyLimbOverflow,
+    /// The sym file could not be parsed
+    Symbols(String),
+    /// An input is not a signal of the circuit
+    UnknownSignal(String),
+    /// An input has a different number of elements than its signal
+    SignalLength {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
+}
+
+impl fmt::Display for ProverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProverError::Io(path, e) => write!(f, "failed to open {}: {}", path.display(), e),
+            ProverError::Zkey(e) => write!(f, "invalid zkey: {}", e),
+            ProverError::Witness(e) => write!(f, "witness generation failed: {}", e),
+            ProverError::Proving(e) => write!(f, "proving failed: {}", e),
+            ProverError::PublicInputCount { expected, got } => {
+                write!(f, "expected {} public inputs, got {}", expected, got)
+            }
+            ProverError::AmountOverflow => write!(f, "amount does not fit in a u64"),
+            ProverError::KeyLimbOverflow => write!(f, "key limb does not fit in 16 bytes"),
+            ProverError::Symbols(e) => write!(f, "invalid sym file: {}", e),
+            ProverError::UnknownSignal(name) => write!(f, "no input signal named {}", name),
+            ProverError::SignalLength {
+                name,
+                expected,
+                got,
+            } => write!(f, "signal {} has {} elements, got {}", name, expected, got),
+        }
+    }
+}
+
+impl std::error::Error for ProverError {}
+
+/// A proof ready to be put in an instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SerializedProof {
+    pub circuit: Circuit,
+    /// A || B || C, as taken by every proof-carrying instruction
+    pub proof: [u8; 256],
+    /// Public signals in verifier order, big-endian
+    pub public_signals: Vec<[u8; 32]>,
+}
+
+impl SerializedProof {
+    pub fn from_proof(circuit: Circuit, proof: &Proof<Bn254>, public_inputs: &[Fr]) -> Self {
+        SerializedProof {
+            circuit,
+            proof: proof_to_bytes(proof),
+            public_signals: public_inputs_to_bytes(public_inputs),
+        }
+    }
+
+    /// Typed instruction argument matching the public signals
+    pub fn instruction_inputs(&self) -> Result<InstructionInputs, ProverError> {
+        self.circuit.instruction_inputs(&self.public_signals)
+    }
+}
+
+/// Proving key and witness generator of one circuit
+pub struct CircuitProver {
+    circuit: Circuit,
+    store: Store,
+    witness: WitnessCalculator,
+    signals: SignalNames,
+    proving_key: ProvingKey<Bn254>,
+    matrices: ConstraintMatrices<Fr>,
+}
+
+impl CircuitProver {
+    /// Loads `<dir>/<name>.wasm`, `<dir>/<name>.zkey` and `<dir>/<name>.sym`
+    pub fn load(circuit: Circuit, dir: impl AsRef<Path>) -> Result<Self, ProverError> {
+        let dir = dir.as_ref();
+        let wasm = dir.join(format!("{}.wasm", circuit.name()));
+        let zkey = dir.join(format!("{}.zkey", circuit.name()));
+        let sym = dir.join(format!("{}.sym", circuit.name()));
+        Self::from_files(circuit, &wasm, &zkey, &sym)
+    }
+
+    pub fn from_files(
+        circuit: Circuit,
+        wasm: &Path,
+        zkey: &Path,
+        sym: &Path,
+    ) -> Result<Self, ProverError> {
+        let symbols =
+            std::fs::read_to_string(sym).map_err(|e| ProverError::Io(sym.to_path_buf(), e))?;
+        let signals = SignalNames::parse(&symbols)?;
+        let mut file = File::open(zkey).map_err(|e| ProverError::Io(zkey.to_path_buf(), e))?;
+        let (proving_key, matrices) =
+            read_zkey(&mut file).map_err(|e| ProverError::Zkey(e.to_string()))?;
+
+        let mut store = Store::default();
+        let witness = WitnessCalculator::new(&mut store, wasm)
+            .map_err(|e| ProverError::Witness(e.to_string()))?;
+
+        Ok(CircuitProver {
+            circuit,
+            store,
+            witness,
+            signals,
+            proving_key,
+            matrices,
+        })
+    }
+
+    pub fn circuit(&self) -> Circuit {
+        self.circuit
+    }
+
+    pub fn proving_key(&self) -> &ProvingKey<Bn254> {
+        &self.proving_key
+    }
+
+    /// Verifying key in the on-chain layout, to regenerate `verifying_key.rs`
+    pub fn verifying_key_bytes(&self) -> VerifyingKeyBytes {
+        verifying_key_to_bytes(&self.proving_key.vk)
+    }
+
+    /// Signal names of the circuit, as compiled
+    pub fn signals(&self) -> &SignalNames {
+        &self.signals
+    }
+
+    /// Generates a proof from the circuit's input signals, each of which must be named
+    /// in the sym file with its length
+    pub fn prove<R: RngCore + CryptoRng>(
+        &mut self,
+        inputs: HashMap<String, Vec<BigInt>>,
+        rng: &mut R,
+    ) -> Result<SerializedProof, ProverError> {
+        self.signals.check(&inputs)?;
+        let assignment = self
+            .witness
+            .calculate_witness_element::<Fr, _>(&mut self.store, inputs, false)
+            .map_err(|e| ProverError::Witness(e.to_string()))?;
+
+        let num_inputs = self.matrices.num_instance_variables;
+        let r = Fr::rand(rng);
+        let s = Fr::rand(rng);
+        let proof = Groth16::<Bn254, CircomReduction>::create_proof_with_reduction_and_matrices(
+            &self.proving_key,
+            r,
+            s,
+            &self.matrices,
+            num_inputs,
+            self.matrices.num_constraints,
+            assignment.as_slice(),
+        )
+        .map_err(|e| ProverError::Proving(e.to_string()))?;
+
+        // assignment[0] is the constant one, public signals follow
+        let public_inputs = &assignment[1..num_inputs];
+        if public_inputs.len() != self.circuit.num_public_inputs() {
+            return Err(ProverError::PublicInputCount {
+                expected: self.circuit.num_public_inputs(),
+                got: public_inputs.len(),
+            });
+        }
+        Ok(SerializedProof::from_proof(
+            self.circuit,
+            &proof,
+            public_inputs,
+        ))
+    }
+}
+
+/// Lazily loads a prover per circuit from a single artifacts directory
+pub struct Provers {
+    dir: PathBuf,
+    loaded: HashMap<Circuit, CircuitProver>,
+}
+
+impl Provers {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Provers {
+            dir: dir.into(),
+            loaded: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, circuit: Circuit) -> Result<&mut CircuitProver, ProverError> {
+        if !self.loaded.contains_key(&circuit) {
+            let prover = CircuitProver::load(circuit, &self.dir)?;
+            self.loaded.insert(circuit, prover);
+        }
+        Ok(self.loaded.get_mut(&circuit).unwrap())
+    }
+
+    pub fn prove<R: RngCore + CryptoRng>(
+        &mut self,
+        circuit: Circuit,
+        inputs: HashMap<String, Vec<BigInt>>,
+        rng: &mut R,
+    ) -> Result<SerializedProof, ProverError> {
+        self.get(circuit)?.prove(inputs, rng)
+    }
+}
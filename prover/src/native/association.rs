@@ -0,0 +1,114 @@
+//! `withdraw_association`: a withdrawal that also opens the note in an association set.
+//! The note's leaf `poseidon(amount, nullifier, asset_id)` must be in both the pool's
+//! tree and the curator's association tree, a tree of the same depth and hashing whose
+//! leaves are the pool leaves the curator vouches for.
+//!
+//! The recipient is public too, so the proof only pays the key it was made for. It goes
+//! in as two 16 byte limbs, a key can be above the field modulus.
+//!
+//! Public inputs, in order: amount, asset_id, nullifier hash, root, association_root,
+//! recipient_hi, recipient_lo.
+use anchor_lang::prelude::Pubkey;
+use ark_bn254::Fr;
+use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::eq::EqGadget;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_r1cs_std::fields::FieldVar;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use solnado::inputs::{amount_to_field, pubkey_to_limbs};
+use solnado::TARGET_DEPTH_LARGE;
+
+use super::{field, MerklePath, NativeCircuit, PoseidonParams};
+use crate::Circuit;
+
+/// Witness of an association withdrawal
+#[derive(Clone, Debug)]
+pub struct WithdrawAssociation {
+    pub amount: u64,
+    /// The note's secret, its hash is the public nullifier
+    pub nullifier: [u8; 32],
+    pub asset_id: [u8; 32],
+    /// Path of the leaf in the pool's tree
+    pub path: MerklePath,
+    /// Path of the leaf in the association tree
+    pub association_path: MerklePath,
+    /// Receives the withdrawal
+    pub recipient: Pubkey,
+}
+
+impl WithdrawAssociation {
+    fn leaf(&self) -> Fr {
+        PoseidonParams::new(3).hash(&[
+            field(&amount_to_field(self.amount)),
+            field(&self.nullifier),
+            field(&self.asset_id),
+        ])
+    }
+}
+
+impl NativeCircuit for WithdrawAssociation {
+    const CIRCUIT: Circuit = Circuit::WithdrawAssociation;
+
+    fn blank() -> Self {
+        WithdrawAssociation {
+            amount: 0,
+            nullifier: [0u8; 32],
+            asset_id: [0u8; 32],
+            path: MerklePath::blank(TARGET_DEPTH_LARGE),
+            association_path: MerklePath::blank(TARGET_DEPTH_LARGE),
+            recipient: Pubkey::default(),
+        }
+    }
+
+    fn public_inputs(&self) -> Vec<Fr> {
+        let pair = PoseidonParams::new(2);
+        let leaf = self.leaf();
+        let [recipient_hi, recipient_lo] = pubkey_to_limbs(&self.recipient);
+        vec![
+            field(&amount_to_field(self.amount)),
+            field(&self.asset_id),
+            PoseidonParams::new(1).hash(&[field(&self.nullifier)]),
+            self.path.root(&pair, leaf),
+            self.association_path.root(&pair, leaf),
+            field(&recipient_hi),
+            field(&recipient_lo),
+        ]
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for WithdrawAssociation {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        assert_eq!(self.path.siblings.len(), TARGET_DEPTH_LARGE);
+        assert_eq!(self.association_path.siblings.len(), TARGET_DEPTH_LARGE);
+        let public = self.public_inputs();
+        let mut inputs = Vec::with_capacity(public.len());
+        for value in public {
+            inputs.push(FpVar::new_input(cs.clone(), || Ok(value))?);
+        }
+        let [amount, asset_id, nullifier_hash, root, association_root, recipient_hi, recipient_lo] =
+            &inputs[..]
+        else {
+            unreachable!("seven public inputs");
+        };
+        // nothing else reads the recipient, squared like tornado's recipientSquare so it
+        // sits in a constraint
+        let _ = recipient_hi.square()?;
+        let _ = recipient_lo.square()?;
+
+        let nullifier = FpVar::new_witness(cs.clone(), || Ok(field(&self.nullifier)))?;
+        PoseidonParams::new(1)
+            .hash_var(std::slice::from_ref(&nullifier))?
+            .enforce_equal(nullifier_hash)?;
+
+        let pair = PoseidonParams::new(2);
+        let leaf =
+            PoseidonParams::new(3).hash_var(&[amount.clone(), nullifier, asset_id.clone()])?;
+        self.path
+            .root_var(cs.clone(), &pair, leaf.clone())?
+            .enforce_equal(root)?;
+        self.association_path
+            .root_var(cs, &pair, leaf)?
+            .enforce_equal(association_root)?;
+        Ok(())
+    }
+}
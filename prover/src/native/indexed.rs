@@ -0,0 +1,208 @@
+//! `indexed_insert`: puts a nullifier in a pool's indexed nullifier tree (see the
+//! program's `indexed`). Proves the low leaf is in the tree under `old_root` and sorts
+//! just below the nullifier, then gives the two nodes the program needs to apply the
+//! insertion: the low leaf's frontier subtree once the leaf points at the nullifier, and
+//! the new leaf.
+//!
+//! Public inputs, in order: nullifier, old_root, next_index, low_index, low_subtree,
+//! new_leaf.
+use ark_bn254::Fr;
+use ark_ff::AdditiveGroup;
+use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::boolean::Boolean;
+use ark_r1cs_std::convert::ToBitsGadget;
+use ark_r1cs_std::eq::EqGadget;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_r1cs_std::fields::FieldVar;
+use ark_r1cs_std::select::CondSelectGadget;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use solnado::indexed::{frontier_level, INDEXED_TREE_DEPTH};
+use solnado::inputs::amount_to_field;
+
+use super::{field, parent_var, MerklePath, NativeCircuit, PoseidonParams};
+use crate::Circuit;
+
+/// Witness of an insertion, as `solnado_sdk::IndexedTree::insert` produces it
+#[derive(Clone, Debug)]
+pub struct IndexedInsert {
+    pub nullifier: [u8; 32],
+    /// Where the tree appends its next leaf
+    pub next_index: u64,
+    pub low_index: u64,
+    /// The low leaf before the insertion
+    pub low_value: [u8; 32],
+    pub low_next_index: u64,
+    pub low_next_value: [u8; 32],
+    /// Siblings of the low leaf, leaf level first
+    pub low_path: Vec<[u8; 32]>,
+}
+
+impl IndexedInsert {
+    fn path(&self) -> MerklePath {
+        MerklePath {
+            siblings: self.low_path.clone(),
+            bits: (0..self.low_path.len())
+                .map(|level| (self.low_index >> level & 1) as u8)
+                .collect(),
+        }
+    }
+}
+
+/// `bits` as an integer, little-endian
+fn from_bits(bits: &[Boolean<Fr>]) -> FpVar<Fr> {
+    let mut value = FpVar::zero();
+    let mut power = Fr::from(1u64);
+    for bit in bits {
+        value += FpVar::from(bit.clone()) * power;
+        power.double_in_place();
+    }
+    value
+}
+
+/// `index` as `INDEXED_TREE_DEPTH` bits, which also bounds it
+fn index_bits(
+    cs: ConstraintSystemRef<Fr>,
+    var: &FpVar<Fr>,
+    index: u64,
+) -> Result<Vec<Boolean<Fr>>, SynthesisError> {
+    let bits = (0..INDEXED_TREE_DEPTH)
+        .map(|i| Boolean::new_witness(cs.clone(), || Ok(index >> i & 1 == 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    from_bits(&bits).enforce_equal(var)?;
+    Ok(bits)
+}
+
+/// `a < b`, both taken as integers below the field modulus
+fn less_than(a: &FpVar<Fr>, b: &FpVar<Fr>) -> Result<Boolean<Fr>, SynthesisError> {
+    let (a, b) = (a.to_bits_le()?, b.to_bits_le()?);
+    let mut less = Boolean::FALSE;
+    // from the least significant bit up, a higher differing bit decides
+    for (a, b) in a.iter().zip(&b) {
+        let differ = a ^ b;
+        less = Boolean::conditionally_select(&differ, b, &less)?;
+    }
+    Ok(less)
+}
+
+impl NativeCircuit for IndexedInsert {
+    const CIRCUIT: Circuit = Circuit::IndexedInsert;
+
+    fn blank() -> Self {
+        IndexedInsert {
+            nullifier: [0u8; 32],
+            next_index: 1,
+            low_index: 0,
+            low_value: [0u8; 32],
+            low_next_index: 0,
+            low_next_value: [0u8; 32],
+            low_path: vec![[0u8; 32]; INDEXED_TREE_DEPTH],
+        }
+    }
+
+    fn public_inputs(&self) -> Vec<Fr> {
+        let leaf = PoseidonParams::new(3);
+        let pair = PoseidonParams::new(2);
+        let low = leaf.hash(&[
+            field(&self.low_value),
+            field(&amount_to_field(self.low_next_index)),
+            field(&self.low_next_value),
+        ]);
+        let updated = leaf.hash(&[
+            field(&self.low_value),
+            field(&amount_to_field(self.next_index)),
+            field(&self.nullifier),
+        ]);
+        let mut path = self.path();
+        // a low leaf at or past next_index fails the circuit anyway
+        let level = match self.low_index < self.next_index {
+            true => frontier_level(self.low_index, self.next_index),
+            false => 0,
+        };
+        path.siblings.truncate(level);
+        path.bits.truncate(level);
+        vec![
+            field(&self.nullifier),
+            self.path().root(&pair, low),
+            field(&amount_to_field(self.next_index)),
+            field(&amount_to_field(self.low_index)),
+            path.root(&pair, updated),
+            leaf.hash(&[
+                field(&self.nullifier),
+                field(&amount_to_field(self.low_next_index)),
+                field(&self.low_next_value),
+            ]),
+        ]
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for IndexedInsert {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        assert_eq!(self.low_path.len(), INDEXED_TREE_DEPTH);
+        let public = self.public_inputs();
+        let mut inputs = Vec::with_capacity(public.len());
+        for value in public {
+            inputs.push(FpVar::new_input(cs.clone(), || Ok(value))?);
+        }
+        let [nullifier, old_root, next_index, low_index, low_subtree, new_leaf] = &inputs[..]
+        else {
+            unreachable!("six public inputs");
+        };
+
+        let witness = |bytes: [u8; 32]| FpVar::new_witness(cs.clone(), || Ok(field(&bytes)));
+        let low_value = witness(self.low_value)?;
+        let low_next_index = witness(amount_to_field(self.low_next_index))?;
+        let low_next_value = witness(self.low_next_value)?;
+
+        // the low leaf sorts right below the nullifier, or is the tail
+        less_than(&low_value, nullifier)?.enforce_equal(&Boolean::TRUE)?;
+        let tail = low_next_value.is_zero()?;
+        (tail | less_than(nullifier, &low_next_value)?).enforce_equal(&Boolean::TRUE)?;
+
+        let leaf = PoseidonParams::new(3);
+        let pair = PoseidonParams::new(2);
+        leaf.hash_var(&[
+            nullifier.clone(),
+            low_next_index.clone(),
+            low_next_value.clone(),
+        ])?
+        .enforce_equal(new_leaf)?;
+
+        // the low leaf is in the tree, at low_index
+        let low_bits = index_bits(cs.clone(), low_index, self.low_index)?;
+        let siblings = self
+            .low_path
+            .iter()
+            .map(|s| witness(*s))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut node = leaf.hash_var(&[low_value.clone(), low_next_index, low_next_value])?;
+        for (sibling, right) in siblings.iter().zip(&low_bits) {
+            node = parent_var(&pair, right, sibling, &node)?;
+        }
+        node.enforce_equal(old_root)?;
+
+        // frontier_level(low_index, next_index): the highest bit where they differ, which
+        // must be set in next_index for the low leaf to come before it
+        let next_bits = index_bits(cs.clone(), next_index, self.next_index)?;
+        let mut same_above = Boolean::TRUE;
+        let mut at_level = vec![Boolean::FALSE; INDEXED_TREE_DEPTH];
+        for level in (0..INDEXED_TREE_DEPTH).rev() {
+            let differ = &low_bits[level] ^ &next_bits[level];
+            at_level[level] = &differ & &same_above;
+            (&at_level[level] & &low_bits[level]).enforce_equal(&Boolean::FALSE)?;
+            same_above = &same_above & &!differ;
+        }
+        same_above.enforce_equal(&Boolean::FALSE)?;
+
+        // the low leaf pointed at the nullifier, hashed up to that level
+        let mut node = leaf.hash_var(&[low_value, next_index.clone(), nullifier.clone()])?;
+        let mut subtree = FpVar::zero();
+        for level in 0..INDEXED_TREE_DEPTH {
+            subtree += FpVar::from(at_level[level].clone()) * &node;
+            if level + 1 < INDEXED_TREE_DEPTH {
+                node = parent_var(&pair, &low_bits[level], &siblings[level], &node)?;
+            }
+        }
+        subtree.enforce_equal(low_subtree)?;
+        Ok(())
+    }
+}
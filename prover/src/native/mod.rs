@@ -0,0 +1,211 @@
+//! Circuits written directly as arkworks constraints instead of circom. No ceremony has
+//! run for them yet: their only keys are the development ones `NativeProver::dev_setup`
+//! derives from a public seed, which the program verifies against only when built with
+//! its `dev-keys` feature. `examples/native_setup.rs` prints them as `verifying_key.rs`
+//! constants.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use ark_bn254::{Bn254, Fr};
+use ark_ff::PrimeField;
+use ark_groth16::{Groth16, ProvingKey};
+use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::boolean::Boolean;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_r1cs_std::select::CondSelectGadget;
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, SynthesisError,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::{CryptoRng, RngCore, SeedableRng};
+
+use crate::{verifying_key_to_bytes, Circuit, ProverError, SerializedProof, VerifyingKeyBytes};
+
+pub mod association;
+pub mod indexed;
+pub mod poseidon;
+
+pub use association::WithdrawAssociation;
+pub use indexed::IndexedInsert;
+pub use poseidon::PoseidonParams;
+
+/// Seed of the development keys
+pub const DEV_SEED: u64 = 0x0073_6f6c_6e61_646f;
+
+/// Directory `NativeProver::load` reads proving keys from, none are committed
+pub fn keys_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("keys")
+}
+
+/// Big-endian bytes as a field element, reduced like the on-chain inputs
+pub fn field(bytes: &[u8; 32]) -> Fr {
+    Fr::from_be_bytes_mod_order(bytes)
+}
+
+/// Siblings of a leaf from the bottom up, `bits[i]` set when the path goes through the
+/// right child at level `i`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MerklePath {
+    pub siblings: Vec<[u8; 32]>,
+    pub bits: Vec<u8>,
+}
+
+impl MerklePath {
+    /// Path of the right length for key generation, the values are never read
+    pub fn blank(depth: usize) -> Self {
+        MerklePath {
+            siblings: vec![[0u8; 32]; depth],
+            bits: vec![0; depth],
+        }
+    }
+
+    pub fn root(&self, poseidon: &PoseidonParams, leaf: Fr) -> Fr {
+        self.siblings
+            .iter()
+            .zip(&self.bits)
+            .fold(leaf, |node, (sibling, bit)| {
+                let sibling = field(sibling);
+                match bit {
+                    0 => poseidon.hash(&[node, sibling]),
+                    _ => poseidon.hash(&[sibling, node]),
+                }
+            })
+    }
+
+    /// Root of `leaf` in the circuit, the siblings and bits being private witnesses
+    pub fn root_var(
+        &self,
+        cs: ConstraintSystemRef<Fr>,
+        poseidon: &PoseidonParams,
+        leaf: FpVar<Fr>,
+    ) -> Result<FpVar<Fr>, SynthesisError> {
+        let mut node = leaf;
+        for (sibling, bit) in self.siblings.iter().zip(&self.bits) {
+            let sibling = FpVar::new_witness(cs.clone(), || Ok(field(sibling)))?;
+            let right = Boolean::new_witness(cs.clone(), || Ok(*bit != 0))?;
+            node = parent_var(poseidon, &right, &sibling, &node)?;
+        }
+        Ok(node)
+    }
+}
+
+/// Parent of `node` and `sibling`, `node` being the right child when `right` is set
+pub(crate) fn parent_var(
+    poseidon: &PoseidonParams,
+    right: &Boolean<Fr>,
+    sibling: &FpVar<Fr>,
+    node: &FpVar<Fr>,
+) -> Result<FpVar<Fr>, SynthesisError> {
+    let left = FpVar::conditionally_select(right, sibling, node)?;
+    let other = FpVar::conditionally_select(right, node, sibling)?;
+    poseidon.hash_var(&[left, other])
+}
+
+/// A circuit of this module along with its witness
+pub trait NativeCircuit: ConstraintSynthesizer<Fr> + Clone {
+    const CIRCUIT: Circuit;
+
+    /// Instance with the right shape and any values, to generate the keys from
+    fn blank() -> Self;
+
+    /// Public inputs in verifier order
+    fn public_inputs(&self) -> Vec<Fr>;
+}
+
+/// Proving key of a native circuit
+pub struct NativeProver {
+    circuit: Circuit,
+    proving_key: ProvingKey<Bn254>,
+}
+
+impl NativeProver {
+    /// Fresh keys for `C`. Whoever runs this knows the setup's trapdoor and can forge
+    /// proofs, deployments need a key from a ceremony or a trusted party
+    pub fn setup<C: NativeCircuit, R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> Result<Self, ProverError> {
+        let proving_key =
+            Groth16::<Bn254>::generate_random_parameters_with_reduction(C::blank(), rng)
+                .map_err(|e| ProverError::Proving(e.to_string()))?;
+        Ok(NativeProver {
+            circuit: C::CIRCUIT,
+            proving_key,
+        })
+    }
+
+    /// Keys of `C` drawn from `DEV_SEED`, the ones the program's `dev-keys` feature
+    /// holds. Anyone can rerun this and forge proofs, they're for tests and devnets
+    pub fn dev_setup<C: NativeCircuit>() -> Result<Self, ProverError> {
+        Self::setup::<C, _>(&mut StdRng::seed_from_u64(DEV_SEED))
+    }
+
+    /// Loads `keys/<name>.pk`
+    pub fn load(circuit: Circuit) -> Result<Self, ProverError> {
+        Self::from_file(circuit, &keys_dir().join(format!("{}.pk", circuit.name())))
+    }
+
+    pub fn from_file(circuit: Circuit, path: &Path) -> Result<Self, ProverError> {
+        let file = File::open(path).map_err(|e| ProverError::Io(path.to_path_buf(), e))?;
+        // keys are written by `write`, skip the subgroup checks
+        let proving_key = ProvingKey::deserialize_compressed_unchecked(BufReader::new(file))
+            .map_err(|e| ProverError::Zkey(e.to_string()))?;
+        Ok(NativeProver {
+            circuit,
+            proving_key,
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), ProverError> {
+        let file = File::create(path).map_err(|e| ProverError::Io(path.to_path_buf(), e))?;
+        self.proving_key
+            .serialize_compressed(file)
+            .map_err(|e| ProverError::Zkey(e.to_string()))
+    }
+
+    pub fn circuit(&self) -> Circuit {
+        self.circuit
+    }
+
+    pub fn proving_key(&self) -> &ProvingKey<Bn254> {
+        &self.proving_key
+    }
+
+    /// Verifying key in the on-chain layout, to regenerate `verifying_key.rs`
+    pub fn verifying_key_bytes(&self) -> VerifyingKeyBytes {
+        verifying_key_to_bytes(&self.proving_key.vk)
+    }
+
+    pub fn prove<C: NativeCircuit, R: RngCore + CryptoRng>(
+        &self,
+        circuit: C,
+        rng: &mut R,
+    ) -> Result<SerializedProof, ProverError> {
+        assert_eq!(C::CIRCUIT, self.circuit, "key of another circuit");
+        // groth16 only checks the witness in debug builds, a bad one gives a proof the
+        // program rejects
+        let cs = ConstraintSystem::new_ref();
+        circuit
+            .clone()
+            .generate_constraints(cs.clone())
+            .map_err(|e| ProverError::Witness(e.to_string()))?;
+        if !cs
+            .is_satisfied()
+            .map_err(|e| ProverError::Witness(e.to_string()))?
+        {
+            return Err(ProverError::Witness(
+                "the witness doesn't satisfy the circuit".into(),
+            ));
+        }
+        let public_inputs = circuit.public_inputs();
+        let proof =
+            Groth16::<Bn254>::create_random_proof_with_reduction(circuit, &self.proving_key, rng)
+                .map_err(|e| ProverError::Proving(e.to_string()))?;
+        Ok(SerializedProof::from_proof(
+            self.circuit,
+            &proof,
+            &public_inputs,
+        ))
+    }
+}
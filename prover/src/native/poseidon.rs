@@ -0,0 +1,102 @@
+//! Poseidon over BN254 with the circom parameters, natively and as an R1CS gadget for the
+//! native circuits. Mirrors `light-poseidon` (what `sol_poseidon` runs on chain):
+//! the state is a zero domain tag followed by the inputs, half the full rounds, the
+//! partial rounds, the other half, and the output is the first state element.
+use ark_bn254::Fr;
+use ark_ff::Field;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_r1cs_std::fields::FieldVar;
+use ark_relations::r1cs::SynthesisError;
+use light_poseidon::parameters::bn254_x5::get_poseidon_parameters;
+use num_bigint::BigUint;
+
+const ALPHA: u64 = 5;
+
+/// Round constants and MDS matrix for one width
+#[derive(Clone, Debug)]
+pub struct PoseidonParams {
+    ark: Vec<Fr>,
+    mds: Vec<Vec<Fr>>,
+    full_rounds: usize,
+    partial_rounds: usize,
+    width: usize,
+}
+
+// light-poseidon is on ark 0.4, carry its constants over in decimal
+fn carry<F: std::fmt::Display>(value: &F) -> Fr {
+    // zero prints as the empty string
+    let int: BigUint = value.to_string().parse().unwrap_or_default();
+    Fr::from(int)
+}
+
+impl PoseidonParams {
+    /// Parameters hashing `nr_inputs` elements, at most 12
+    pub fn new(nr_inputs: usize) -> Self {
+        let params = get_poseidon_parameters::<ark_bn254_v04::Fr>((nr_inputs + 1) as u8)
+            .expect("circom parameters go up to 12 inputs");
+        PoseidonParams {
+            ark: params.ark.iter().map(carry).collect(),
+            mds: params
+                .mds
+                .iter()
+                .map(|row| row.iter().map(carry).collect())
+                .collect(),
+            full_rounds: params.full_rounds,
+            partial_rounds: params.partial_rounds,
+            width: params.width,
+        }
+    }
+
+    fn is_full(&self, round: usize) -> bool {
+        let half = self.full_rounds / 2;
+        round < half || round >= half + self.partial_rounds
+    }
+
+    pub fn hash(&self, inputs: &[Fr]) -> Fr {
+        assert_eq!(inputs.len() + 1, self.width, "wrong number of inputs");
+        let mut state = vec![Fr::from(0u64)];
+        state.extend_from_slice(inputs);
+        for round in 0..self.full_rounds + self.partial_rounds {
+            for (i, s) in state.iter_mut().enumerate() {
+                *s += self.ark[round * self.width + i];
+            }
+            let sboxed = if self.is_full(round) { self.width } else { 1 };
+            for s in &mut state[..sboxed] {
+                *s = s.pow([ALPHA]);
+            }
+            state = self
+                .mds
+                .iter()
+                .map(|row| row.iter().zip(&state).map(|(m, s)| *m * s).sum())
+                .collect();
+        }
+        state[0]
+    }
+
+    pub fn hash_var(&self, inputs: &[FpVar<Fr>]) -> Result<FpVar<Fr>, SynthesisError> {
+        assert_eq!(inputs.len() + 1, self.width, "wrong number of inputs");
+        let mut state = vec![FpVar::zero()];
+        state.extend_from_slice(inputs);
+        for round in 0..self.full_rounds + self.partial_rounds {
+            for (i, s) in state.iter_mut().enumerate() {
+                *s += self.ark[round * self.width + i];
+            }
+            let sboxed = if self.is_full(round) { self.width } else { 1 };
+            for s in &mut state[..sboxed] {
+                // x^5 in three constraints
+                let square = s.square()?;
+                *s = square.square()? * &*s;
+            }
+            state = self
+                .mds
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .zip(&state)
+                        .fold(FpVar::zero(), |acc, (m, s)| acc + s * *m)
+                })
+                .collect();
+        }
+        Ok(state.swap_remove(0))
+    }
+}
@@ -0,0 +1,33 @@
+//! wasmer-vm 4.4.0 (`src/probestack.rs`) resolves its `LibCall::Probestack` libcall on
+//! x86 and x86_64 outside Windows to an `extern "C" fn __rust_probestack()`. Rust stopped
+//! exporting that symbol from compiler-builtins once LLVM emitted stack probes inline, so
+//! the prover fails to link against wasmer on current toolchains with an undefined
+//! `__rust_probestack`. This is the routine compiler-builtins used to ship: probe every
+//! page down to `rsp - rax`, leaving `rsp` unchanged. It is weak, a toolchain that still
+//! exports the symbol keeps its own. wasmer is pinned to 4.4.0 in Cargo.toml, drop this
+//! when moving to a wasmer-vm that no longer imports the symbol. Only x86_64 Linux is
+//! covered, other x86 targets still fail to link.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+std::arch::global_asm!(
+    ".weak __rust_probestack",
+    ".type __rust_probestack, @function",
+    "__rust_probestack:",
+    "    push rbp",
+    "    mov rbp, rsp",
+    "    mov r11, rax",
+    "    cmp r11, 0x1000",
+    "    jna 3f",
+    "2:",
+    "    sub rsp, 0x1000",
+    "    test qword ptr [rsp + 8], rsp",
+    "    sub r11, 0x1000",
+    "    cmp r11, 0x1000",
+    "    ja 2b",
+    "3:",
+    "    sub rsp, r11",
+    "    test qword ptr [rsp + 8], rsp",
+    "    add rsp, rax",
+    "    leave",
+    "    ret",
+    ".size __rust_probestack, . - __rust_probestack",
+);
@@ -0,0 +1,79 @@
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::AffineRepr;
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::{Proof, VerifyingKey};
+
+//Serialization into the byte layout `proof_verification` and the alt_bn128 syscalls expect:
+//every coordinate is 32 bytes big-endian, G2 coordinates are written c1 || c0.
+
+pub fn field_to_be_bytes<F: PrimeField>(f: &F) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let bytes = f.into_bigint().to_bytes_be();
+    out[32 - bytes.len()..].copy_from_slice(&bytes);
+    out
+}
+
+fn fq2_to_be_bytes(f: &Fq2) -> [u8; 64] {
+    let mut out = [0u8; 64];
+    out[..32].copy_from_slice(&field_to_be_bytes::<Fq>(&f.c1));
+    out[32..].copy_from_slice(&field_to_be_bytes::<Fq>(&f.c0));
+    out
+}
+
+pub fn g1_to_be_bytes(p: &G1Affine) -> [u8; 64] {
+    let mut out = [0u8; 64];
+    //The point at infinity is encoded as all zeroes
+    if let Some((x, y)) = p.xy() {
+        out[..32].copy_from_slice(&field_to_be_bytes(&x));
+        out[32..].copy_from_slice(&field_to_be_bytes(&y));
+    }
+    out
+}
+
+pub fn g2_to_be_bytes(p: &G2Affine) -> [u8; 128] {
+    let mut out = [0u8; 128];
+    if let Some((x, y)) = p.xy() {
+        out[..64].copy_from_slice(&fq2_to_be_bytes(&x));
+        out[64..].copy_from_slice(&fq2_to_be_bytes(&y));
+    }
+    out
+}
+
+/// Proof as the instructions take it: A || B || C. A is *not* negated, the program does that.
+pub fn proof_to_bytes(proof: &Proof<Bn254>) -> [u8; 256] {
+    let mut out = [0u8; 256];
+    out[..64].copy_from_slice(&g1_to_be_bytes(&proof.a));
+    out[64..192].copy_from_slice(&g2_to_be_bytes(&proof.b));
+    out[192..].copy_from_slice(&g1_to_be_bytes(&proof.c));
+    out
+}
+
+pub fn public_inputs_to_bytes(inputs: &[Fr]) -> Vec<[u8; 32]> {
+    inputs.iter().map(field_to_be_bytes).collect()
+}
+
+/// Verifying key in the layout of the `Groth16Verifyingkey` constants in `verifying_key.rs`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyingKeyBytes {
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+    pub ic: Vec<[u8; 64]>,
+}
+
+impl VerifyingKeyBytes {
+    pub fn nr_pubinputs(&self) -> usize {
+        self.ic.len()
+    }
+}
+
+pub fn verifying_key_to_bytes(vk: &VerifyingKey<Bn254>) -> VerifyingKeyBytes {
+    VerifyingKeyBytes {
+        alpha_g1: g1_to_be_bytes(&vk.alpha_g1),
+        beta_g2: g2_to_be_bytes(&vk.beta_g2),
+        gamma_g2: g2_to_be_bytes(&vk.gamma_g2),
+        delta_g2: g2_to_be_bytes(&vk.delta_g2),
+        ic: vk.gamma_abc_g1.iter().map(g1_to_be_bytes).collect(),
+    }
+}
@@ -0,0 +1,58 @@
+//! Signal names of a compiled circuit, read from the `<circuit>.sym` file circom writes
+//! next to the witness generator. Each line is `label,witness,component,name`, the
+//! template's own signals being named `main.<signal>` with `[i]` per array element.
+use std::collections::HashMap;
+
+use num_bigint::BigInt;
+
+use crate::ProverError;
+
+/// Signals of the main component and their number of elements
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignalNames {
+    sizes: HashMap<String, usize>,
+}
+
+impl SignalNames {
+    pub fn parse(sym: &str) -> Result<Self, ProverError> {
+        let mut sizes = HashMap::new();
+        for line in sym.lines().filter(|l| !l.trim().is_empty()) {
+            let name = line
+                .splitn(4, ',')
+                .nth(3)
+                .ok_or_else(|| ProverError::Symbols(format!("malformed line {line:?}")))?;
+            let Some(signal) = name.trim().strip_prefix("main.") else {
+                continue;
+            };
+            // signals of subcomponents
+            if signal.contains('.') {
+                continue;
+            }
+            let base = signal.split('[').next().unwrap_or(signal);
+            *sizes.entry(base.to_string()).or_insert(0) += 1;
+        }
+        Ok(SignalNames { sizes })
+    }
+
+    /// Number of elements of `name`, one for a scalar signal
+    pub fn size(&self, name: &str) -> Option<usize> {
+        self.sizes.get(name).copied()
+    }
+
+    /// Fails unless every input names a signal of the circuit and has its length
+    pub fn check(&self, inputs: &HashMap<String, Vec<BigInt>>) -> Result<(), ProverError> {
+        for (name, values) in inputs {
+            let expected = self
+                .size(name)
+                .ok_or_else(|| ProverError::UnknownSignal(name.clone()))?;
+            if values.len() != expected {
+                return Err(ProverError::SignalLength {
+                    name: name.clone(),
+                    expected,
+                    got: values.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
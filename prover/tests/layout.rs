@@ -0,0 +1,117 @@
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::Groth16;
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_std::test_rng;
+use groth16_solana::groth16::Groth16Verifyingkey;
+use solnado::inputs::{amount_to_field, DepositInputs, PublicInputs};
+use solnado::utils::proof_verification;
+use solnado_prover::{
+    verifying_key_to_bytes, Circuit, InstructionInputs, ProverError, SerializedProof,
+    VerifyingKeyBytes,
+};
+
+// amount * factor == leaf, with amount and leaf public: the same public input
+// shape as the single leaf deposit circuit.
+struct ToyDeposit {
+    amount: Fr,
+    factor: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for ToyDeposit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let amount = cs.new_input_variable(|| Ok(self.amount))?;
+        let leaf = cs.new_input_variable(|| Ok(self.amount * self.factor))?;
+        let factor = cs.new_witness_variable(|| Ok(self.factor))?;
+        cs.enforce_constraint(lc!() + amount, lc!() + factor, lc!() + leaf)?;
+        Ok(())
+    }
+}
+
+fn on_chain_key(vk: &VerifyingKeyBytes) -> Groth16Verifyingkey<'_> {
+    Groth16Verifyingkey {
+        nr_pubinputs: vk.nr_pubinputs(),
+        vk_alpha_g1: vk.alpha_g1,
+        vk_beta_g2: vk.beta_g2,
+        vk_gamme_g2: vk.gamma_g2,
+        vk_delta_g2: vk.delta_g2,
+        vk_ic: &vk.ic,
+    }
+}
+
+fn toy_proof() -> (SerializedProof, VerifyingKeyBytes) {
+    let mut rng = test_rng();
+    let amount = Fr::from(5_000_000u64);
+    let factor = Fr::from(7u64);
+    let pk = Groth16::<Bn254>::generate_random_parameters_with_reduction(
+        ToyDeposit { amount, factor },
+        &mut rng,
+    )
+    .unwrap();
+    let proof = Groth16::<Bn254>::create_random_proof_with_reduction(
+        ToyDeposit { amount, factor },
+        &pk,
+        &mut rng,
+    )
+    .unwrap();
+    let proof = SerializedProof::from_proof(Circuit::Deposit1, &proof, &[amount, amount * factor]);
+    (proof, verifying_key_to_bytes(&pk.vk))
+}
+
+#[test]
+fn serialized_proof_passes_on_chain_verification() {
+    let (proof, vk) = toy_proof();
+    let InstructionInputs::Deposit(inputs) = proof.instruction_inputs().unwrap() else {
+        panic!("deposit circuit must produce deposit inputs");
+    };
+    assert_eq!(inputs.amount, 5_000_000);
+
+    let fields: [[u8; 32]; 2] = inputs.to_field_elements();
+    assert_eq!(fields.to_vec(), proof.public_signals);
+    assert!(proof_verification(&proof.proof, &on_chain_key(&vk), &fields).is_ok());
+}
+
+#[test]
+fn tampered_inputs_fail_on_chain_verification() {
+    let (proof, vk) = toy_proof();
+    let inputs = DepositInputs {
+        amount: 5_000_001,
+        leaf1: proof.public_signals[1],
+        leaf2: solnado::DEFAULT_LEAF,
+    };
+    let fields: [[u8; 32]; 2] = inputs.to_field_elements();
+    assert!(proof_verification(&proof.proof, &on_chain_key(&vk), &fields).is_err());
+}
+
+#[test]
+fn instruction_bytes_are_borsh_of_typed_inputs() {
+    let (proof, _) = toy_proof();
+    let bytes = proof.instruction_inputs().unwrap().to_bytes();
+    // amount (u64 LE) || leaf1 || leaf2
+    assert_eq!(bytes.len(), 8 + 32 + 32);
+    assert_eq!(bytes[..8], 5_000_000u64.to_le_bytes());
+    assert_eq!(amount_to_field(5_000_000), proof.public_signals[0]);
+}
+
+#[test]
+fn oversized_amount_is_rejected() {
+    let mut signals = vec![[0u8; 32]; 2];
+    signals[0][0] = 1;
+    assert!(Circuit::Deposit1.instruction_inputs(&signals).is_err());
+    assert!(Circuit::Withdraw.instruction_inputs(&signals).is_err());
+
+    let mut signals = vec![[0u8; 32]; 7];
+    signals[0][0] = 1;
+    assert!(matches!(
+        Circuit::WithdrawAssociation.instruction_inputs(&signals),
+        Err(ProverError::AmountOverflow)
+    ));
+
+    // a recipient limb wider than half a key
+    signals[0][0] = 0;
+    signals[6][15] = 1;
+    assert!(matches!(
+        Circuit::WithdrawAssociation.instruction_inputs(&signals),
+        Err(ProverError::KeyLimbOverflow)
+    ));
+}
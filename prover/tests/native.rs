@@ -0,0 +1,250 @@
+//! The native circuits: their poseidon matches the one the program hashes with, and their
+//! development keys give proofs the program's `dev-keys` verifying keys accept.
+use anchor_lang::prelude::Pubkey;
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_r1cs_std::R1CSVar;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+use groth16_solana::groth16::Groth16Verifyingkey;
+use light_poseidon::{Poseidon, PoseidonBytesHasher};
+use solnado::inputs::{amount_to_field, pubkey_to_limbs, PublicInputs};
+use solnado::utils::{get_default_root_depth, proof_verification};
+use solnado::verifying_key::{INDEXED_INSERT_VK, WITHDRAW_ASSOCIATION_VK};
+use solnado::{DEFAULT_LEAF, TARGET_DEPTH_LARGE};
+use solnado_prover::native::{
+    field, IndexedInsert, MerklePath, NativeCircuit, NativeProver, PoseidonParams,
+    WithdrawAssociation,
+};
+use solnado_prover::InstructionInputs;
+use solnado_sdk::indexed::{IndexedTree, Insertion};
+
+fn bytes(value: u64) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[24..].copy_from_slice(&value.to_be_bytes());
+    out[0] = 0x1f;
+    out
+}
+
+fn light_poseidon(inputs: &[[u8; 32]]) -> [u8; 32] {
+    let inputs: Vec<&[u8]> = inputs.iter().map(|i| i.as_slice()).collect();
+    Poseidon::<ark_bn254_v04::Fr>::new_circom(inputs.len())
+        .unwrap()
+        .hash_bytes_be(&inputs)
+        .unwrap()
+}
+
+fn to_bytes(value: Fr) -> [u8; 32] {
+    value.into_bigint().to_bytes_be().try_into().unwrap()
+}
+
+#[test]
+fn poseidon_matches_light_poseidon() {
+    for n in 1..=3 {
+        let inputs: Vec<[u8; 32]> = (0..n).map(|i| bytes(i as u64 * 7 + 3)).collect();
+        let fields: Vec<Fr> = inputs.iter().map(field).collect();
+        assert_eq!(
+            to_bytes(PoseidonParams::new(n).hash(&fields)),
+            light_poseidon(&inputs),
+            "{n} inputs"
+        );
+    }
+    assert_eq!(
+        to_bytes(PoseidonParams::new(2).hash(&[Fr::from(0u64), Fr::from(0u64)])),
+        get_default_root_depth(1)
+    );
+}
+
+#[test]
+fn poseidon_gadget_matches_native_hash() {
+    let params = PoseidonParams::new(2);
+    let inputs = [field(&bytes(1)), field(&bytes(2))];
+    let cs = ConstraintSystem::<Fr>::new_ref();
+    let vars: Vec<_> = inputs
+        .iter()
+        .map(|i| FpVar::new_witness(cs.clone(), || Ok(*i)).unwrap())
+        .collect();
+    let hash = params.hash_var(&vars).unwrap();
+    assert_eq!(hash.value().unwrap(), params.hash(&inputs));
+    assert!(cs.is_satisfied().unwrap());
+}
+
+fn assert_deployed(prover: NativeProver, deployed: &Groth16Verifyingkey) {
+    let vk = prover.verifying_key_bytes();
+    assert_eq!(vk.nr_pubinputs(), deployed.nr_pubinputs);
+    assert_eq!(vk.alpha_g1, deployed.vk_alpha_g1);
+    assert_eq!(vk.beta_g2, deployed.vk_beta_g2);
+    assert_eq!(vk.gamma_g2, deployed.vk_gamme_g2);
+    assert_eq!(vk.delta_g2, deployed.vk_delta_g2);
+    assert_eq!(vk.ic, deployed.vk_ic);
+}
+
+#[test]
+fn dev_keys_are_the_deployed_ones() {
+    assert_deployed(
+        NativeProver::dev_setup::<WithdrawAssociation>().unwrap(),
+        &WITHDRAW_ASSOCIATION_VK,
+    );
+    assert_deployed(
+        NativeProver::dev_setup::<IndexedInsert>().unwrap(),
+        &INDEXED_INSERT_VK,
+    );
+}
+
+fn default_node(level: usize) -> [u8; 32] {
+    if level == 0 {
+        DEFAULT_LEAF
+    } else {
+        get_default_root_depth(level)
+    }
+}
+
+/// Path of the leaf at `index` in a tree holding only `neighbour` at `index ^ 1`
+fn path(index: u8, neighbour: [u8; 32]) -> MerklePath {
+    let mut siblings: Vec<_> = (0..TARGET_DEPTH_LARGE).map(default_node).collect();
+    siblings[0] = neighbour;
+    let mut bits = vec![0; TARGET_DEPTH_LARGE];
+    bits[0] = index;
+    MerklePath { siblings, bits }
+}
+
+/// Root over `leaf` along `path`, hashed the way the program does
+fn root(leaf: [u8; 32], path: &MerklePath) -> [u8; 32] {
+    path.siblings
+        .iter()
+        .zip(&path.bits)
+        .fold(leaf, |node, (sibling, bit)| match bit {
+            0 => light_poseidon(&[node, *sibling]),
+            _ => light_poseidon(&[*sibling, node]),
+        })
+}
+
+#[test]
+fn association_proof_passes_on_chain_verification() {
+    let nullifier = bytes(42);
+    let witness = WithdrawAssociation {
+        amount: 2_000_000_000,
+        nullifier,
+        asset_id: [0u8; 32],
+        path: path(0, DEFAULT_LEAF),
+        association_path: path(1, bytes(9)),
+        recipient: Pubkey::new_unique(),
+    };
+    let prover = NativeProver::dev_setup::<WithdrawAssociation>().unwrap();
+    let proof = prover
+        .prove(witness.clone(), &mut rand::thread_rng())
+        .unwrap();
+    let InstructionInputs::Association(inputs) = proof.instruction_inputs().unwrap() else {
+        panic!("association circuit must produce association inputs");
+    };
+    assert_eq!(inputs.amount, 2_000_000_000);
+    assert_eq!(inputs.nullifier, light_poseidon(&[nullifier]));
+    let leaf = light_poseidon(&[amount_to_field(2_000_000_000), nullifier, [0u8; 32]]);
+    assert_eq!(inputs.root, root(leaf, &witness.path));
+    assert_eq!(
+        inputs.association_root,
+        root(leaf, &witness.association_path)
+    );
+
+    assert_eq!(inputs.recipient, witness.recipient);
+
+    let fields: [[u8; 32]; 7] = inputs.to_field_elements();
+    assert!(proof_verification(&proof.proof, &WITHDRAW_ASSOCIATION_VK, &fields).is_ok());
+
+    // another association set
+    let mut tampered = fields;
+    tampered[4] = root(bytes(9), &witness.association_path);
+    assert!(proof_verification(&proof.proof, &WITHDRAW_ASSOCIATION_VK, &tampered).is_err());
+
+    // another recipient
+    let mut redirected = fields;
+    redirected[5..].copy_from_slice(&pubkey_to_limbs(&Pubkey::new_unique()));
+    assert!(proof_verification(&proof.proof, &WITHDRAW_ASSOCIATION_VK, &redirected).is_err());
+}
+
+fn insertion_witness(insertion: &Insertion) -> IndexedInsert {
+    IndexedInsert {
+        nullifier: insertion.inputs.nullifier,
+        next_index: insertion.inputs.next_index,
+        low_index: insertion.inputs.low_index,
+        low_value: insertion.low_leaf.value,
+        low_next_index: insertion.low_leaf.next_index,
+        low_next_value: insertion.low_leaf.next_value,
+        low_path: insertion.low_path.clone(),
+    }
+}
+
+fn satisfied(witness: IndexedInsert) -> bool {
+    let cs = ConstraintSystem::<Fr>::new_ref();
+    witness.generate_constraints(cs.clone()).unwrap();
+    cs.is_satisfied().unwrap()
+}
+
+#[test]
+fn sdk_insertions_satisfy_the_indexed_circuit() {
+    let mut tree = IndexedTree::new();
+    // tail, middle, new head successor, and a low leaf far from next_index
+    for value in [50u64, 20, 90, 30, 10, 60, 70, 25] {
+        let insertion = tree.insert(&bytes(value)).unwrap();
+        let witness = insertion_witness(&insertion);
+        let public: Vec<[u8; 32]> = witness.public_inputs().into_iter().map(to_bytes).collect();
+        assert_eq!(public, insertion.inputs.to_field_elements().to_vec());
+        assert!(satisfied(witness), "inserting {value}");
+    }
+}
+
+#[test]
+fn indexed_circuit_refuses_a_wrong_low_leaf() {
+    let mut tree = IndexedTree::new();
+    for value in [50u64, 20] {
+        tree.insert(&bytes(value)).unwrap();
+    }
+    let insertion = tree.clone().insert(&bytes(30)).unwrap();
+
+    // 30 doesn't come right after 50
+    let mut past = insertion_witness(&tree.clone().insert(&bytes(60)).unwrap());
+    past.nullifier = bytes(30);
+    assert!(!satisfied(past));
+
+    // nor is 20 the low leaf of 10
+    let mut below = insertion_witness(&insertion);
+    below.nullifier = bytes(10);
+    assert!(!satisfied(below));
+
+    // a nullifier already spent has no low leaf
+    let mut again = insertion_witness(&insertion);
+    again.nullifier = bytes(20);
+    assert!(!satisfied(again));
+
+    // a low leaf that isn't in the tree proves against another root, which the program
+    // holds against its own
+    let mut forged = insertion_witness(&insertion);
+    forged.low_path[3] = bytes(1);
+    assert_ne!(to_bytes(forged.public_inputs()[1]), tree.root());
+    assert_eq!(insertion.inputs.old_root, tree.root());
+
+    assert!(satisfied(insertion_witness(&insertion)));
+}
+
+#[test]
+fn insertion_proof_passes_on_chain_verification() {
+    let mut tree = IndexedTree::new();
+    tree.insert(&bytes(50)).unwrap();
+    let insertion = tree.insert(&bytes(20)).unwrap();
+    let prover = NativeProver::dev_setup::<IndexedInsert>().unwrap();
+    let proof = prover
+        .prove(insertion_witness(&insertion), &mut rand::thread_rng())
+        .unwrap();
+    assert_eq!(
+        proof.instruction_inputs().unwrap(),
+        InstructionInputs::Insertion(insertion.inputs)
+    );
+
+    let fields = insertion.inputs.to_field_elements();
+    assert!(proof_verification(&proof.proof, &INDEXED_INSERT_VK, &fields).is_ok());
+    // another subtree
+    let mut tampered = fields;
+    tampered[4] = bytes(1);
+    assert!(proof_verification(&proof.proof, &INDEXED_INSERT_VK, &tampered).is_err());
+}
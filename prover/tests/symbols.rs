@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use num_bigint::BigInt;
+use solnado_prover::{ProverError, SignalNames};
+
+// What circom writes for a withdrawal over a depth 2 tree
+const WITHDRAW_SYM: &str = "\
+1,1,0,main.nullifierHash
+2,2,0,main.root
+3,3,0,main.amount
+4,4,0,main.nullifier
+5,5,0,main.assetId
+6,6,0,main.pathElements[0]
+7,7,0,main.pathElements[1]
+8,8,0,main.pathIndices[0]
+9,9,0,main.pathIndices[1]
+10,10,1,main.leafHasher.inputs[0]
+11,11,1,main.leafHasher.out
+12,-1,2,main.levels[0].hasher.out
+";
+
+fn inputs(signals: &[(&str, usize)]) -> HashMap<String, Vec<BigInt>> {
+    signals
+        .iter()
+        .map(|(name, len)| (name.to_string(), vec![BigInt::from(1); *len]))
+        .collect()
+}
+
+#[test]
+fn main_signals_and_their_lengths() {
+    let names = SignalNames::parse(WITHDRAW_SYM).unwrap();
+    assert_eq!(names.size("amount"), Some(1));
+    assert_eq!(names.size("pathElements"), Some(2));
+    assert_eq!(names.size("pathIndices"), Some(2));
+    // subcomponent signals are not inputs
+    assert_eq!(names.size("leafHasher"), None);
+    assert_eq!(names.size("levels"), None);
+
+    let withdraw = [
+        ("amount", 1),
+        ("nullifier", 1),
+        ("assetId", 1),
+        ("pathElements", 2),
+        ("pathIndices", 2),
+        ("nullifierHash", 1),
+        ("root", 1),
+    ];
+    assert!(names.check(&inputs(&withdraw)).is_ok());
+}
+
+#[test]
+fn misnamed_or_missized_inputs_are_rejected() {
+    let names = SignalNames::parse(WITHDRAW_SYM).unwrap();
+    assert!(matches!(
+        names.check(&inputs(&[("asset_id", 1)])),
+        Err(ProverError::UnknownSignal(name)) if name == "asset_id"
+    ));
+    assert!(matches!(
+        names.check(&inputs(&[("pathElements", 3)])),
+        Err(ProverError::SignalLength {
+            expected: 2,
+            got: 3,
+            ..
+        })
+    ));
+    assert!(matches!(
+        SignalNames::parse("1,1,0"),
+        Err(ProverError::Symbols(_))
+    ));
+}
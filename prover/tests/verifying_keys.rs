@@ -0,0 +1,168 @@
+//! The deployed verifying keys and the proof encoding under groth16-solana 0.2.0. The
+//! keys must decode to valid curve points sized for their circuit's public inputs, and
+//! the verifier must reject public inputs that aren't canonical field elements.
+use anchor_lang::prelude::Pubkey;
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ff::PrimeField;
+use ark_groth16::Groth16;
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable};
+use ark_std::test_rng;
+use groth16_solana::groth16::Groth16Verifyingkey;
+use num_bigint::BigUint;
+use solnado::inputs::{PublicInputs, FIELD_MODULUS};
+use solnado::utils::proof_verification;
+use solnado::verifying_key::*;
+use solnado_prover::{
+    verifying_key_to_bytes, Circuit, InstructionInputs, SerializedProof, VerifyingKeyBytes,
+};
+
+fn fq(bytes: &[u8]) -> Fq {
+    let int = BigUint::from_bytes_be(bytes).try_into().unwrap();
+    Fq::from_bigint(int).expect("coordinate above the base field modulus")
+}
+
+fn check_g1(bytes: &[u8; 64]) {
+    let p = G1Affine::new_unchecked(fq(&bytes[..32]), fq(&bytes[32..]));
+    assert!(p.is_on_curve() && p.is_in_correct_subgroup_assuming_on_curve());
+}
+
+// c1 || c0 for each coordinate
+fn check_g2(bytes: &[u8; 128]) {
+    let x = Fq2::new(fq(&bytes[32..64]), fq(&bytes[..32]));
+    let y = Fq2::new(fq(&bytes[96..]), fq(&bytes[64..96]));
+    let p = G2Affine::new_unchecked(x, y);
+    assert!(p.is_on_curve() && p.is_in_correct_subgroup_assuming_on_curve());
+}
+
+fn check_key(vk: &Groth16Verifyingkey, circuit: Option<Circuit>) {
+    check_g1(&vk.vk_alpha_g1);
+    check_g2(&vk.vk_beta_g2);
+    check_g2(&vk.vk_gamme_g2);
+    check_g2(&vk.vk_delta_g2);
+    vk.vk_ic.iter().for_each(check_g1);
+    assert_eq!(vk.nr_pubinputs, vk.vk_ic.len());
+    if let Some(circuit) = circuit {
+        assert_eq!(
+            vk.vk_ic.len(),
+            circuit.num_public_inputs() + 1,
+            "{circuit:?}"
+        );
+    }
+}
+
+#[test]
+fn deployed_keys_are_valid_points_sized_for_their_circuits() {
+    // the original two input deposit and the two to two combine have no prover circuit
+    check_key(&VERIFYINGKEY, None);
+    check_key(&VERIFYINGKEY_DEPOSIT1, Some(Circuit::Deposit1));
+    check_key(&VERIFYINGKEY_DEPOSIT2, Some(Circuit::Deposit2));
+    check_key(&COMBINE1TO2_VERIFYINGKEY, Some(Circuit::Combine1To2));
+    check_key(&COMBINE2TO1_VERIFYINGKEY, Some(Circuit::Combine2To1));
+    check_key(&COMBINE2TO2_VERIFYINGKEY, None);
+    check_key(&WITHDRAW_VAR_VK, Some(Circuit::Withdraw));
+    check_key(
+        &WITHDRAW_AND_ADD_VERIFYINGKEY,
+        Some(Circuit::WithdrawAndAdd),
+    );
+    check_key(&WITHDRAW_ON_BEHALF_VK, Some(Circuit::WithdrawOnBehalf));
+    check_key(&WITHDRAW_ASSOCIATION_VK, Some(Circuit::WithdrawAssociation));
+    check_key(&INDEXED_INSERT_VK, Some(Circuit::IndexedInsert));
+}
+
+// Public inputs shaped like the on-behalf circuit's: [amount, nullifier, withdrawer,
+// root], with root == amount + nullifier + withdrawer
+struct ToyOnBehalf {
+    inputs: [Fr; 3],
+}
+
+impl ConstraintSynthesizer<Fr> for ToyOnBehalf {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let [a, b, c] = self.inputs;
+        let amount = cs.new_input_variable(|| Ok(a))?;
+        let nullifier = cs.new_input_variable(|| Ok(b))?;
+        let withdrawer = cs.new_input_variable(|| Ok(c))?;
+        let root = cs.new_input_variable(|| Ok(a + b + c))?;
+        cs.enforce_constraint(
+            lc!() + amount + nullifier + withdrawer,
+            lc!() + Variable::One,
+            lc!() + root,
+        )?;
+        Ok(())
+    }
+}
+
+fn on_chain_key(vk: &VerifyingKeyBytes) -> Groth16Verifyingkey<'_> {
+    Groth16Verifyingkey {
+        nr_pubinputs: vk.nr_pubinputs(),
+        vk_alpha_g1: vk.alpha_g1,
+        vk_beta_g2: vk.beta_g2,
+        vk_gamme_g2: vk.gamma_g2,
+        vk_delta_g2: vk.delta_g2,
+        vk_ic: &vk.ic,
+    }
+}
+
+/// A proof for a withdrawer whose key is just below the scalar field modulus
+fn on_behalf_proof() -> (SerializedProof, VerifyingKeyBytes, Pubkey) {
+    let mut below = FIELD_MODULUS;
+    below[31] -= 1;
+    let withdrawer = Pubkey::new_from_array(below);
+    let inputs = [
+        Fr::from(5_000_000u64),
+        Fr::from(42u64),
+        Fr::from_be_bytes_mod_order(&withdrawer.to_bytes()),
+    ];
+    let mut rng = test_rng();
+    let pk = Groth16::<Bn254>::generate_random_parameters_with_reduction(
+        ToyOnBehalf { inputs },
+        &mut rng,
+    )
+    .unwrap();
+    let proof =
+        Groth16::<Bn254>::create_random_proof_with_reduction(ToyOnBehalf { inputs }, &pk, &mut rng)
+            .unwrap();
+    let public = [
+        inputs[0],
+        inputs[1],
+        inputs[2],
+        inputs[0] + inputs[1] + inputs[2],
+    ];
+    let proof = SerializedProof::from_proof(Circuit::WithdrawOnBehalf, &proof, &public);
+    (proof, verifying_key_to_bytes(&pk.vk), withdrawer)
+}
+
+#[test]
+fn withdrawer_is_bound_whole() {
+    let (proof, vk, withdrawer) = on_behalf_proof();
+    let InstructionInputs::OnBehalf(inputs) = proof.instruction_inputs().unwrap() else {
+        panic!("on-behalf circuit must produce on-behalf inputs");
+    };
+    assert_eq!(inputs.amount, 5_000_000);
+    assert_eq!(inputs.withdrawer, withdrawer);
+
+    let fields: [[u8; 32]; 4] = inputs.to_field_elements();
+    assert_eq!(fields[2], withdrawer.to_bytes());
+    assert_eq!(fields.to_vec(), proof.public_signals);
+    assert!(proof_verification(&proof.proof, &on_chain_key(&vk), &fields).is_ok());
+}
+
+#[test]
+fn non_canonical_inputs_are_rejected() {
+    let (proof, vk, withdrawer) = on_behalf_proof();
+    let vk = on_chain_key(&vk);
+    let signals: [[u8; 32]; 4] = proof.public_signals.clone().try_into().unwrap();
+
+    // withdrawer + r, a key without a private key the proof would otherwise bind
+    let mut alias = signals;
+    let key = BigUint::from_bytes_be(&withdrawer.to_bytes()) + BigUint::from(Fr::MODULUS);
+    alias[2].copy_from_slice(&key.to_bytes_be());
+    assert!(proof_verification(&proof.proof, &vk, &alias).is_err());
+
+    // nullifier + r, an alias of the proven nullifier 0.0.3 accepted
+    let mut alias = signals;
+    let nullifier = BigUint::from_bytes_be(&signals[1]) + BigUint::from(Fr::MODULUS);
+    alias[1][32 - nullifier.to_bytes_be().len()..].copy_from_slice(&nullifier.to_bytes_be());
+    assert_ne!(alias[1], signals[1]);
+    assert!(proof_verification(&proof.proof, &vk, &alias).is_err());
+}
@@ -0,0 +1,84 @@
+//! Read-only wallet history. A viewing key opens the notes sent to its address, the
+//! indexer's leaves place them in the pool's tree and the shards' nullifiers tell which
+//! were spent, enough for an auditor to follow a balance without spending power.
+use std::collections::{HashMap, HashSet};
+
+use solnado::inbox::EncryptedNote;
+use solnado::shard::BitShard;
+
+use crate::inbox::view_note;
+use crate::keys::ViewingKey;
+
+/// A note received by the address, spent or not
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub leaf: [u8; 32],
+    /// Position in the pool's tree, None while the leaf isn't in the indexer's leaves
+    pub leaf_index: Option<u64>,
+    pub amount: u64,
+    pub asset_id: [u8; 32],
+    pub nullifier_hash: [u8; 32],
+    pub memo: Vec<u8>,
+    pub spent: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct History {
+    /// In note stream order
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// `notes` are the (leaf, note) pairs of the pool's note logs, `leaves` the indexer's
+    /// leaves in tree order and `shards` the pool's live shards
+    pub fn build<'a>(
+        key: &ViewingKey,
+        notes: impl IntoIterator<Item = ([u8; 32], EncryptedNote)>,
+        leaves: &[[u8; 32]],
+        shards: impl IntoIterator<Item = &'a BitShard>,
+    ) -> Self {
+        let spent: HashSet<[u8; 32]> = shards
+            .into_iter()
+            .flat_map(|s| s.nullifiers().iter().copied())
+            .collect();
+        let positions: HashMap<[u8; 32], u64> = leaves
+            .iter()
+            .enumerate()
+            .map(|(i, l)| (*l, i as u64))
+            .collect();
+        let entries = notes
+            .into_iter()
+            .filter_map(|(leaf, encrypted)| {
+                let viewed = view_note(key, &encrypted)?;
+                Some(HistoryEntry {
+                    leaf,
+                    leaf_index: positions.get(&leaf).copied(),
+                    amount: viewed.amount,
+                    asset_id: viewed.asset_id,
+                    nullifier_hash: viewed.nullifier_hash,
+                    memo: viewed.memo,
+                    spent: spent.contains(&viewed.nullifier_hash),
+                })
+            })
+            .collect();
+        History { entries }
+    }
+
+    /// Notes that landed in the tree
+    pub fn incoming(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter().filter(|e| e.leaf_index.is_some())
+    }
+
+    /// Notes spent since
+    pub fn outgoing(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.incoming().filter(|e| e.spent)
+    }
+
+    /// Value of the unspent notes of `asset_id`
+    pub fn balance(&self, asset_id: &[u8; 32]) -> u64 {
+        self.incoming()
+            .filter(|e| !e.spent && e.asset_id == *asset_id)
+            .map(|e| e.amount)
+            .sum()
+    }
+}
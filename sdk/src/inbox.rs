@@ -0,0 +1,254 @@
+//! Encrypted notes. A sender encrypts a leaf's preimage to the recipient's `NoteAddress`
+//! under a fresh ephemeral key, the program logs it next to the leaf, and the recipient
+//! trial-decrypts every note log to find the leaves it can spend.
+//!
+//! The ChaCha20-Poly1305 key is `sha256("solnado note" || view_shared || ephemeral || view)`,
+//! unique per note, so the nonce is zero. The plaintext is
+//! `amount (u64 BE) || asset_id || nullifier_hash || masked nullifier || memo`, the
+//! nullifier masked with `sha256("solnado nullifier" || spend_shared || ephemeral)`: a
+//! viewing key reads the amounts and recognises the spends, only the spending key
+//! recovers the nullifier.
+use std::fmt;
+
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use solana_poseidon::{hashv, Endianness, Parameters};
+use solnado::inbox::{EncryptedNote, MAX_NOTE_CIPHERTEXT, NOTE_LOG_TAG};
+use solnado::inputs::amount_to_field;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::keys::{NoteAddress, SpendingKey, ViewingKey};
+
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = 8 + 32 + 32 + 32;
+/// Longest memo a note carries
+pub const MAX_MEMO_LEN: usize = MAX_NOTE_CIPHERTEXT - TAG_LEN - HEADER_LEN;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoteError {
+    MemoTooLong,
+    /// The nullifier isn't under the BN254 modulus
+    InvalidNullifier,
+}
+
+impl fmt::Display for NoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteError::MemoTooLong => write!(f, "memo longer than {} bytes", MAX_MEMO_LEN),
+            NoteError::InvalidNullifier => write!(f, "nullifier is not a field element"),
+        }
+    }
+}
+
+impl std::error::Error for NoteError {}
+
+fn poseidon(inputs: &[&[u8]]) -> Option<[u8; 32]> {
+    hashv(Parameters::Bn254X5, Endianness::BigEndian, inputs)
+        .ok()
+        .map(|h| h.to_bytes())
+}
+
+/// A leaf's preimage, what the recipient needs to spend it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Note {
+    pub amount: u64,
+    pub nullifier: [u8; 32],
+    /// All zeroes for SOL
+    pub asset_id: [u8; 32],
+    pub memo: Vec<u8>,
+}
+
+impl Note {
+    /// `poseidon(amount, nullifier, asset_id)`, panics unless the nullifier and asset id
+    /// are under the BN254 modulus
+    pub fn leaf(&self) -> [u8; 32] {
+        self.try_leaf().expect("note fields are field elements")
+    }
+
+    fn try_leaf(&self) -> Option<[u8; 32]> {
+        poseidon(&[
+            &amount_to_field(self.amount),
+            &self.nullifier,
+            &self.asset_id,
+        ])
+    }
+
+    /// `poseidon(nullifier)`, what the program stores once the note is spent
+    pub fn nullifier_hash(&self) -> [u8; 32] {
+        nullifier_hash(&self.nullifier).expect("nullifier is a field element")
+    }
+}
+
+fn nullifier_hash(nullifier: &[u8; 32]) -> Option<[u8; 32]> {
+    poseidon(&[nullifier])
+}
+
+/// What a viewing key reads of a note
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ViewedNote {
+    pub amount: u64,
+    pub asset_id: [u8; 32],
+    /// Shows up in the pool's nullifier store once the note is spent
+    pub nullifier_hash: [u8; 32],
+    pub memo: Vec<u8>,
+    masked_nullifier: [u8; 32],
+}
+
+impl ViewedNote {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.amount.to_be_bytes()[..],
+            &self.asset_id,
+            &self.nullifier_hash,
+            &self.masked_nullifier,
+            &self.memo,
+        ]
+        .concat()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LEN {
+            return None;
+        }
+        Some(ViewedNote {
+            amount: u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+            asset_id: bytes[8..40].try_into().unwrap(),
+            nullifier_hash: bytes[40..72].try_into().unwrap(),
+            masked_nullifier: bytes[72..104].try_into().unwrap(),
+            memo: bytes[HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+/// A note of the stream the recipient could open
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingNote {
+    pub leaf: [u8; 32],
+    pub note: Note,
+}
+
+fn cipher(view_shared: &[u8; 32], ephemeral: &PublicKey, view: &PublicKey) -> ChaCha20Poly1305 {
+    let key = Sha256::new()
+        .chain_update(b"solnado note")
+        .chain_update(view_shared)
+        .chain_update(ephemeral.as_bytes())
+        .chain_update(view.as_bytes())
+        .finalize();
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+fn nullifier_mask(spend_shared: &[u8; 32], ephemeral: &PublicKey) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(b"solnado nullifier")
+        .chain_update(spend_shared)
+        .chain_update(ephemeral.as_bytes())
+        .finalize()
+        .into()
+}
+
+fn xor(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    std::array::from_fn(|i| a[i] ^ b[i])
+}
+
+/// Encrypts `note` so only `to`'s keys can read it
+pub fn encrypt_note<R: RngCore + CryptoRng>(
+    to: &NoteAddress,
+    note: &Note,
+    rng: &mut R,
+) -> Result<EncryptedNote, NoteError> {
+    if note.memo.len() > MAX_MEMO_LEN {
+        return Err(NoteError::MemoTooLong);
+    }
+    let nullifier_hash = nullifier_hash(&note.nullifier).ok_or(NoteError::InvalidNullifier)?;
+    let ephemeral_secret = StaticSecret::random_from_rng(rng);
+    let ephemeral = PublicKey::from(&ephemeral_secret);
+    let spend_shared = ephemeral_secret.diffie_hellman(&to.spend);
+    let viewed = ViewedNote {
+        amount: note.amount,
+        asset_id: note.asset_id,
+        nullifier_hash,
+        memo: note.memo.clone(),
+        masked_nullifier: xor(
+            &note.nullifier,
+            &nullifier_mask(spend_shared.as_bytes(), &ephemeral),
+        ),
+    };
+    let view_shared = ephemeral_secret.diffie_hellman(&to.view);
+    let ciphertext = cipher(view_shared.as_bytes(), &ephemeral, &to.view)
+        .encrypt(&Nonce::default(), viewed.to_bytes().as_slice())
+        .expect("ChaCha20-Poly1305 encrypts any length under 256 GiB");
+    Ok(EncryptedNote {
+        ephemeral_key: ephemeral.to_bytes(),
+        ciphertext,
+    })
+}
+
+/// The readable part of `encrypted` if it was made for `key`'s address
+pub fn view_note(key: &ViewingKey, encrypted: &EncryptedNote) -> Option<ViewedNote> {
+    let ephemeral = PublicKey::from(encrypted.ephemeral_key);
+    let shared = key.view_secret.diffie_hellman(&ephemeral);
+    let plaintext = cipher(shared.as_bytes(), &ephemeral, &key.address().view)
+        .decrypt(&Nonce::default(), encrypted.ciphertext.as_slice())
+        .ok()?;
+    ViewedNote::from_bytes(&plaintext)
+}
+
+/// The note inside `encrypted` if it was made for `key` and opens `leaf`
+pub fn decrypt_note(key: &SpendingKey, leaf: &[u8; 32], encrypted: &EncryptedNote) -> Option<Note> {
+    let viewed = view_note(&key.viewing_key(), encrypted)?;
+    let ephemeral = PublicKey::from(encrypted.ephemeral_key);
+    let spend_shared = key.spend_secret().diffie_hellman(&ephemeral);
+    let nullifier = xor(
+        &viewed.masked_nullifier,
+        &nullifier_mask(spend_shared.as_bytes(), &ephemeral),
+    );
+    let note = Note {
+        amount: viewed.amount,
+        nullifier,
+        asset_id: viewed.asset_id,
+        memo: viewed.memo,
+    };
+    (nullifier_hash(&nullifier) == Some(viewed.nullifier_hash) && note.try_leaf() == Some(*leaf))
+        .then_some(note)
+}
+
+/// The leaf and note of a `Program data:` note log line, None for any other line
+pub fn parse_note_log(line: &str) -> Option<([u8; 32], EncryptedNote)> {
+    let mut fields = line
+        .strip_prefix("Program data: ")?
+        .split(' ')
+        .map(|f| general_purpose::STANDARD.decode(f).ok());
+    if fields.next()?? != NOTE_LOG_TAG {
+        return None;
+    }
+    let leaf = fields.next()??.try_into().ok()?;
+    let ephemeral_key = fields.next()??.try_into().ok()?;
+    let ciphertext = fields.next()??;
+    if fields.next().is_some() {
+        return None;
+    }
+    Some((
+        leaf,
+        EncryptedNote {
+            ephemeral_key,
+            ciphertext,
+        },
+    ))
+}
+
+/// The notes of `logs` addressed to `secret`, in log order
+pub fn scan_notes<'a>(
+    key: &SpendingKey,
+    logs: impl IntoIterator<Item = &'a str>,
+) -> Vec<IncomingNote> {
+    logs.into_iter()
+        .filter_map(parse_note_log)
+        .filter_map(|(leaf, encrypted)| {
+            let note = decrypt_note(key, &leaf, &encrypted)?;
+            Some(IncomingNote { leaf, note })
+        })
+        .collect()
+}
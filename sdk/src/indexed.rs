@@ -0,0 +1,165 @@
+//! Client copy of a pool's indexed nullifier tree. The chain keeps only the root and the
+//! append frontier, the leaves are rebuilt from the pool's spends and give the witness of
+//! the next insertion.
+use std::fmt;
+
+use solnado::indexed::{frontier_level, indexed_leaf, node_hash, zero_subtree, INDEXED_TREE_DEPTH};
+use solnado::inputs::IndexedInsertInputs;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertError {
+    /// The nullifier is already a leaf
+    AlreadySpent,
+    TreeFull,
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertError::AlreadySpent => write!(f, "nullifier already in the tree"),
+            InsertError::TreeFull => write!(f, "nullifier tree is full"),
+        }
+    }
+}
+
+impl std::error::Error for InsertError {}
+
+/// One link of the sorted list, `next_value` zero for the tail
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexedLeaf {
+    pub value: [u8; 32],
+    pub next_index: u64,
+    pub next_value: [u8; 32],
+}
+
+impl IndexedLeaf {
+    pub fn hash(&self) -> [u8; 32] {
+        indexed_leaf(&self.value, self.next_index, &self.next_value)
+    }
+}
+
+/// Everything an `indexed_insert` proof is made of
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Insertion {
+    pub inputs: IndexedInsertInputs,
+    /// Low leaf before it's pointed at the nullifier
+    pub low_leaf: IndexedLeaf,
+    /// Siblings of the low leaf under `inputs.old_root`, leaf level first
+    pub low_path: Vec<[u8; 32]>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedTree {
+    leaves: Vec<IndexedLeaf>,
+}
+
+impl IndexedTree {
+    /// Tree holding only the list head, as `initialize_nullifier_tree` creates it
+    pub fn new() -> Self {
+        IndexedTree {
+            leaves: vec![IndexedLeaf {
+                value: [0u8; 32],
+                next_index: 0,
+                next_value: [0u8; 32],
+            }],
+        }
+    }
+
+    /// Tree after the pool's spends, in the order they landed
+    pub fn from_spent<'a>(
+        nullifiers: impl IntoIterator<Item = &'a [u8; 32]>,
+    ) -> Result<Self, InsertError> {
+        let mut tree = Self::new();
+        for nullifier in nullifiers {
+            tree.insert(nullifier)?;
+        }
+        Ok(tree)
+    }
+
+    pub fn leaves(&self) -> &[IndexedLeaf] {
+        &self.leaves
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.node(INDEXED_TREE_DEPTH, 0)
+    }
+
+    /// Root of the subtree of height `level` at position `index` in its row
+    fn node(&self, level: usize, index: u64) -> [u8; 32] {
+        if index << level >= self.leaves.len() as u64 {
+            return zero_subtree(level);
+        }
+        match level {
+            0 => self.leaves[index as usize].hash(),
+            l => node_hash(
+                &self.node(l - 1, 2 * index),
+                &self.node(l - 1, 2 * index + 1),
+            ),
+        }
+    }
+
+    fn path(&self, index: u64) -> Vec<[u8; 32]> {
+        (0..INDEXED_TREE_DEPTH)
+            .map(|level| self.node(level, (index >> level) ^ 1))
+            .collect()
+    }
+
+    /// Leaf the nullifier goes after: the largest value under it
+    pub fn low_index(&self, nullifier: &[u8; 32]) -> Result<u64, InsertError> {
+        if self.leaves.iter().any(|l| l.value == *nullifier) {
+            return Err(InsertError::AlreadySpent);
+        }
+        let low = self
+            .leaves
+            .iter()
+            .position(|l| {
+                l.value < *nullifier && (l.next_value == [0u8; 32] || *nullifier < l.next_value)
+            })
+            .expect("the head is below every nullifier");
+        Ok(low as u64)
+    }
+
+    /// Inserts `nullifier` the way the program will, returning the proof's witness
+    pub fn insert(&mut self, nullifier: &[u8; 32]) -> Result<Insertion, InsertError> {
+        let next_index = self.leaves.len() as u64;
+        if next_index >= 1 << INDEXED_TREE_DEPTH {
+            return Err(InsertError::TreeFull);
+        }
+        let low_index = self.low_index(nullifier)?;
+        let old_root = self.root();
+        let low_path = self.path(low_index);
+
+        let low = &mut self.leaves[low_index as usize];
+        let low_leaf = *low;
+        let new_leaf = IndexedLeaf {
+            value: *nullifier,
+            next_index: low.next_index,
+            next_value: low.next_value,
+        };
+        low.next_index = next_index;
+        low.next_value = *nullifier;
+
+        let level = frontier_level(low_index, next_index);
+        let low_subtree = self.node(level, low_index >> level);
+        self.leaves.push(new_leaf);
+
+        Ok(Insertion {
+            inputs: IndexedInsertInputs {
+                nullifier: *nullifier,
+                old_root,
+                next_index,
+                low_index,
+                low_subtree,
+                new_leaf: new_leaf.hash(),
+            },
+            low_leaf,
+            low_path,
+        })
+    }
+}
+
+impl Default for IndexedTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,159 @@
+//! Key hierarchy of a pool user. One 32 byte spending secret derives two X25519 keys: the
+//! viewing key opens the notes sent to the user, the spend key unmasks their nullifiers.
+//! Sharing the viewing key shows amounts and spends without giving the spending power,
+//! which is the nullifier preimage.
+//!
+//! The same secret derives two ed25519 scalars behind the stealth meta-address, see
+//! [`crate::stealth`].
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::Scalar;
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256, Sha512};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+fn derive(domain: &[u8], seed: &[u8; 32]) -> StaticSecret {
+    let bytes: [u8; 32] = Sha256::new()
+        .chain_update(domain)
+        .chain_update(seed)
+        .finalize()
+        .into();
+    StaticSecret::from(bytes)
+}
+
+fn derive_scalar(domain: &[u8], seed: &[u8; 32]) -> Scalar {
+    let bytes: [u8; 64] = Sha512::new()
+        .chain_update(domain)
+        .chain_update(seed)
+        .finalize()
+        .into();
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+/// Where senders encrypt notes to, safe to publish
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoteAddress {
+    pub view: PublicKey,
+    pub spend: PublicKey,
+}
+
+impl NoteAddress {
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(self.view.as_bytes());
+        bytes[32..].copy_from_slice(self.spend.as_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; 64]) -> Self {
+        NoteAddress {
+            view: PublicKey::from(<[u8; 32]>::try_from(&bytes[..32]).unwrap()),
+            spend: PublicKey::from(<[u8; 32]>::try_from(&bytes[32..]).unwrap()),
+        }
+    }
+}
+
+/// Where withdrawers derive one-time withdrawal addresses from, safe to publish
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StealthMetaAddress {
+    pub scan: EdwardsPoint,
+    pub spend: EdwardsPoint,
+}
+
+impl StealthMetaAddress {
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(self.scan.compress().as_bytes());
+        bytes[32..].copy_from_slice(self.spend.compress().as_bytes());
+        bytes
+    }
+
+    /// None unless both halves are ed25519 points
+    pub fn from_bytes(bytes: &[u8; 64]) -> Option<Self> {
+        let point = |b: &[u8]| CompressedEdwardsY::from_slice(b).ok()?.decompress();
+        Some(StealthMetaAddress {
+            scan: point(&bytes[..32])?,
+            spend: point(&bytes[32..])?,
+        })
+    }
+}
+
+/// Full access: opens notes and spends them
+#[derive(Clone)]
+pub struct SpendingKey([u8; 32]);
+
+impl SpendingKey {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        SpendingKey(bytes)
+    }
+
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut bytes = [0u8; 32];
+        rng.fill_bytes(&mut bytes);
+        SpendingKey(bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    pub(crate) fn spend_secret(&self) -> StaticSecret {
+        derive(b"solnado spend key", &self.0)
+    }
+
+    pub fn viewing_key(&self) -> ViewingKey {
+        ViewingKey {
+            view_secret: derive(b"solnado view key", &self.0),
+            spend: PublicKey::from(&self.spend_secret()),
+        }
+    }
+
+    pub fn address(&self) -> NoteAddress {
+        self.viewing_key().address()
+    }
+
+    pub(crate) fn stealth_scan_secret(&self) -> Scalar {
+        derive_scalar(b"solnado stealth scan key", &self.0)
+    }
+
+    pub(crate) fn stealth_spend_secret(&self) -> Scalar {
+        derive_scalar(b"solnado stealth spend key", &self.0)
+    }
+
+    pub fn stealth_meta_address(&self) -> StealthMetaAddress {
+        StealthMetaAddress {
+            scan: EdwardsPoint::mul_base(&self.stealth_scan_secret()),
+            spend: EdwardsPoint::mul_base(&self.stealth_spend_secret()),
+        }
+    }
+}
+
+/// Read-only access: opens notes, can't learn their nullifiers
+#[derive(Clone)]
+pub struct ViewingKey {
+    pub(crate) view_secret: StaticSecret,
+    spend: PublicKey,
+}
+
+impl ViewingKey {
+    pub fn address(&self) -> NoteAddress {
+        NoteAddress {
+            view: PublicKey::from(&self.view_secret),
+            spend: self.spend,
+        }
+    }
+
+    /// View secret || spend public key, what an auditor is handed
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.view_secret.to_bytes());
+        bytes[32..].copy_from_slice(self.spend.as_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; 64]) -> Self {
+        ViewingKey {
+            view_secret: StaticSecret::from(<[u8; 32]>::try_from(&bytes[..32]).unwrap()),
+            spend: PublicKey::from(<[u8; 32]>::try_from(&bytes[32..]).unwrap()),
+        }
+    }
+}
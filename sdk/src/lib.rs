@@ -0,0 +1,24 @@
+//! Client helpers for the solnado program.
+//!
+//! The program checks the accounts a spend passes but can't tell a client which ones to
+//! pass, this crate derives them from the pool's on-chain state.
+pub mod history;
+pub mod inbox;
+pub mod indexed;
+pub mod keys;
+pub mod memo;
+pub mod shard;
+pub mod stealth;
+
+pub use history::{History, HistoryEntry};
+pub use inbox::{
+    decrypt_note, encrypt_note, scan_notes, view_note, IncomingNote, Note, NoteError, ViewedNote,
+};
+pub use indexed::{IndexedTree, InsertError, Insertion};
+pub use keys::{NoteAddress, SpendingKey, StealthMetaAddress, ViewingKey};
+pub use memo::{memo_ix, pool_memos, required_memos};
+pub use shard::{resolve_shard, ResolveError, ShardAccounts};
+pub use stealth::{
+    claim_ix, derive_stealth_address, open_stealth_address, scan_withdrawals, StealthAddress,
+    StealthKeypair,
+};
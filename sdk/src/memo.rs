@@ -0,0 +1,64 @@
+//! Memos of a deposit. A client puts the ones `required_memos` lists before its deposit
+//! instruction, an indexer reads them back with `pool_memos` to rebuild the pool's tree.
+//! Encoding and decoding are the program's, in `solnado::memo`.
+use anchor_lang::solana_program::instruction::Instruction;
+use solnado::memo::{PoolMemo, SmallTreeMemo, SubBatchMemo};
+use solnado::state::MerkleMountainRange;
+use solnado::utils::{default_leaves, get_root, MEMO_PROGRAM_ID, SUB_BATCH_SIZE};
+use solnado::{BATCHES_PER_SMALL_TREE, LEAVES_LENGTH};
+
+/// Memo program instruction carrying `memo`
+pub fn memo_ix(memo: &PoolMemo) -> Instruction {
+    Instruction {
+        program_id: MEMO_PROGRAM_ID,
+        accounts: vec![],
+        data: memo.encode(),
+    }
+}
+
+/// Memos a transaction inserting `leaves` into `pool` must carry, in insertion order: a
+/// sub batch memo per half batch it fills, and the small tree memo if a leaf opens a
+/// small tree. A two leaf deposit closing the last batch of a small tree needs both.
+pub fn required_memos(pool: &MerkleMountainRange, leaves: &[[u8; 32]]) -> Vec<PoolMemo> {
+    let mut pool = pool.clone();
+    let mut idx = pool.find_first_match();
+    let mut memos = vec![];
+    for leaf in leaves {
+        let opens_small_tree = idx == 0
+            && pool.batch_number != 0
+            && pool.batch_number.is_multiple_of(BATCHES_PER_SMALL_TREE);
+        if opens_small_tree {
+            memos.push(PoolMemo::SmallTree(SmallTreeMemo {
+                closed_batch: pool.batch_number - 1,
+                root: pool.last_small_tree_root,
+            }));
+        }
+        pool.batch_leaves[idx] = *leaf;
+        if (idx + 1).is_multiple_of(SUB_BATCH_SIZE) {
+            let half = &pool.batch_leaves[idx + 1 - SUB_BATCH_SIZE..=idx];
+            memos.push(PoolMemo::SubBatch(SubBatchMemo {
+                batch_number: pool.batch_number,
+                leaves: half.try_into().unwrap(),
+            }));
+        }
+        idx += 1;
+        if idx == LEAVES_LENGTH {
+            // only the peaks matter from here, they give the next small tree root
+            pool.update_peaks(get_root(&pool.batch_leaves));
+            pool.batch_number += 1;
+            pool.batch_leaves = default_leaves();
+            idx = 0;
+        }
+    }
+    memos
+}
+
+/// The pool memos among a transaction's instructions, in order. Memos that decode as
+/// neither kind aren't the pool's and are skipped.
+pub fn pool_memos<'a>(instructions: impl IntoIterator<Item = &'a Instruction>) -> Vec<PoolMemo> {
+    instructions
+        .into_iter()
+        .filter(|ix| ix.program_id == MEMO_PROGRAM_ID)
+        .filter_map(|ix| PoolMemo::decode(&ix.data).ok())
+        .collect()
+}
@@ -0,0 +1,64 @@
+//! Shard accounts of a nullifier in a sharded pool, read from the pool's shard directory.
+use std::fmt;
+
+use anchor_lang::prelude::Pubkey;
+use solnado::directory::{derive_directory_pda_key, directory_entries, find_shard, ShardPrefix};
+use solnado::shard::MAX_PREFIX_BITS;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The data isn't a shard directory
+    InvalidDirectory,
+    /// No live shard covers the nullifier, the directory is corrupt or not the pool's
+    NoShard,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::InvalidDirectory => write!(f, "account is not a shard directory"),
+            ResolveError::NoShard => write!(f, "no live shard holds the nullifier"),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// What a spend passes for one nullifier: its shard, and the children a split of that
+/// shard would create. Shards at the deepest prefix never split, their children are the
+/// shard itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShardAccounts {
+    pub prefix: ShardPrefix,
+    pub shard: Pubkey,
+    pub child0: Pubkey,
+    pub child1: Pubkey,
+}
+
+/// Address of the pool's shard directory
+pub fn directory_address(pool_id: [u8; 16]) -> Pubkey {
+    derive_directory_pda_key(pool_id).0
+}
+
+/// Shard accounts for `nullifier`, `directory_data` being the directory account's data
+pub fn resolve_shard(
+    pool_id: [u8; 16],
+    directory_data: &[u8],
+    nullifier: &[u8; 32],
+) -> Result<ShardAccounts, ResolveError> {
+    let entries = directory_entries(directory_data).map_err(|_| ResolveError::InvalidDirectory)?;
+    let prefix = *find_shard(entries, nullifier).ok_or(ResolveError::NoShard)?;
+    let shard = prefix.pda(pool_id);
+    let (child0, child1) = if (prefix.len as usize) < MAX_PREFIX_BITS {
+        let [c0, c1] = prefix.children();
+        (c0.pda(pool_id), c1.pda(pool_id))
+    } else {
+        (shard, shard)
+    };
+    Ok(ShardAccounts {
+        prefix,
+        shard,
+        child0,
+        child1,
+    })
+}
@@ -0,0 +1,146 @@
+//! Stealth withdrawals. A recipient publishes a `StealthMetaAddress` once, a withdrawer
+//! derives a fresh one-time address from it for every `withdraw_on_behalf_shard` and
+//! passes the ephemeral key along, the program logs both. The recipient's wallet scans
+//! those logs, recognises its addresses and signs their claims with the derived secret.
+//!
+//! With `r` the ephemeral scalar and `scan`, `spend` the meta-address points, the shared
+//! tweak is `t = sha512("solnado stealth" || r·scan || r·G)` and the one-time address
+//! `spend + t·G`, whose secret `spend_secret + t` only the recipient knows.
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use base64::{engine::general_purpose, Engine as _};
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::Scalar;
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha512};
+use solana_system_interface::instruction as system_instruction;
+use solnado::stealth::STEALTH_LOG_TAG;
+
+use crate::keys::{SpendingKey, StealthMetaAddress};
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+fn tweak(shared: &EdwardsPoint, ephemeral: &CompressedEdwardsY) -> Scalar {
+    hash_to_scalar(&[
+        b"solnado stealth",
+        shared.compress().as_bytes(),
+        ephemeral.as_bytes(),
+    ])
+}
+
+/// A one-time address and the ephemeral key to pass as `stealth_ephemeral`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StealthAddress {
+    pub address: Pubkey,
+    pub ephemeral_key: [u8; 32],
+}
+
+/// Derives a one-time address for `to`. The withdrawal proof takes the address as a public
+/// input, ephemeral keys are drawn until it's under the BN254 modulus.
+pub fn derive_stealth_address<R: RngCore + CryptoRng>(
+    to: &StealthMetaAddress,
+    rng: &mut R,
+) -> StealthAddress {
+    loop {
+        let mut wide = [0u8; 64];
+        rng.fill_bytes(&mut wide);
+        let r = Scalar::from_bytes_mod_order_wide(&wide);
+        let ephemeral = EdwardsPoint::mul_base(&r).compress();
+        let point = to.spend + EdwardsPoint::mul_base(&tweak(&(r * to.scan), &ephemeral));
+        let address = point.compress().to_bytes();
+        if address[0] < 0x30 {
+            return StealthAddress {
+                address: Pubkey::new_from_array(address),
+                ephemeral_key: ephemeral.to_bytes(),
+            };
+        }
+    }
+}
+
+/// Signing key of a one-time address. The secret is a bare scalar rather than an ed25519
+/// seed, so it signs here instead of through a `Keypair`.
+#[derive(Clone)]
+pub struct StealthKeypair {
+    secret: Scalar,
+    pubkey: Pubkey,
+}
+
+impl StealthKeypair {
+    pub fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    /// Ed25519 signature of `message`, with the nonce derived from the secret and message
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        let prefix = hash_to_scalar(&[b"solnado stealth nonce", self.secret.as_bytes()]);
+        let nonce = hash_to_scalar(&[prefix.as_bytes(), message]);
+        let r = EdwardsPoint::mul_base(&nonce).compress();
+        let k = hash_to_scalar(&[r.as_bytes(), self.pubkey.as_ref(), message]);
+        let s = nonce + k * self.secret;
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(r.as_bytes());
+        signature[32..].copy_from_slice(s.as_bytes());
+        signature
+    }
+}
+
+/// The signing key of `address` if it was derived for `key` under `ephemeral_key`
+pub fn open_stealth_address(
+    key: &SpendingKey,
+    address: &Pubkey,
+    ephemeral_key: &[u8; 32],
+) -> Option<StealthKeypair> {
+    let compressed = CompressedEdwardsY(*ephemeral_key);
+    let ephemeral = compressed.decompress()?;
+    let tweak = tweak(&(key.stealth_scan_secret() * ephemeral), &compressed);
+    let secret = key.stealth_spend_secret() + tweak;
+    (EdwardsPoint::mul_base(&secret).compress().to_bytes() == address.to_bytes()).then_some(
+        StealthKeypair {
+            secret,
+            pubkey: *address,
+        },
+    )
+}
+
+/// The withdrawer and ephemeral key of a `Program data:` stealth log line, None for any
+/// other line
+pub fn parse_stealth_log(line: &str) -> Option<(Pubkey, [u8; 32])> {
+    let mut fields = line
+        .strip_prefix("Program data: ")?
+        .split(' ')
+        .map(|f| general_purpose::STANDARD.decode(f).ok());
+    if fields.next()?? != STEALTH_LOG_TAG {
+        return None;
+    }
+    let withdrawer = Pubkey::try_from(fields.next()??.as_slice()).ok()?;
+    let ephemeral_key = fields.next()??.try_into().ok()?;
+    if fields.next().is_some() {
+        return None;
+    }
+    Some((withdrawer, ephemeral_key))
+}
+
+/// The one-time addresses of `logs` derived for `key`, in log order
+pub fn scan_withdrawals<'a>(
+    key: &SpendingKey,
+    logs: impl IntoIterator<Item = &'a str>,
+) -> Vec<StealthKeypair> {
+    logs.into_iter()
+        .filter_map(parse_stealth_log)
+        .filter_map(|(withdrawer, ephemeral_key)| {
+            open_stealth_address(key, &withdrawer, &ephemeral_key)
+        })
+        .collect()
+}
+
+/// Moves `lamports` out of a one-time address. The address pays the transaction fee
+/// itself, the transaction needs `keypair.sign` over its message as the only signature.
+pub fn claim_ix(keypair: &StealthKeypair, to: &Pubkey, lamports: u64) -> Instruction {
+    system_instruction::transfer(&keypair.pubkey, to, lamports)
+}
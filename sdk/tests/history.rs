@@ -0,0 +1,106 @@
+//! A viewing key sees what its spending key received and spent, and nothing it could spend.
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use solnado::inbox::EncryptedNote;
+use solnado::shard::BitShard;
+use solnado_sdk::history::History;
+use solnado_sdk::inbox::{encrypt_note, view_note, Note};
+use solnado_sdk::keys::{NoteAddress, SpendingKey, ViewingKey};
+
+fn random_note(rng: &mut StdRng, amount: u64) -> Note {
+    let mut nullifier = [0u8; 32];
+    rng.fill_bytes(&mut nullifier);
+    nullifier[0] = rng.gen_range(0..0x30);
+    Note {
+        amount,
+        nullifier,
+        asset_id: [0u8; 32],
+        memo: vec![],
+    }
+}
+
+fn shard_with(nullifiers: &[[u8; 32]]) -> BitShard {
+    let mut shard: BitShard = bytemuck::Zeroable::zeroed();
+    for n in nullifiers {
+        shard.insert(n).unwrap();
+    }
+    shard
+}
+
+#[test]
+fn viewing_key_shares_the_address_not_the_spend_key() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let key = SpendingKey::random(&mut rng);
+    let viewing = key.viewing_key();
+    assert_eq!(viewing.address(), key.address());
+    assert_eq!(
+        NoteAddress::from_bytes(&key.address().to_bytes()),
+        key.address()
+    );
+
+    let restored = ViewingKey::from_bytes(&viewing.to_bytes());
+    assert_eq!(restored.address(), key.address());
+    // what an auditor holds doesn't contain the spending secret
+    assert!(!viewing.to_bytes().windows(32).any(|w| w == key.to_bytes()));
+    assert_eq!(
+        SpendingKey::from_bytes(key.to_bytes()).address(),
+        key.address()
+    );
+}
+
+#[test]
+fn viewing_key_reads_amounts_and_nullifier_hash() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let key = SpendingKey::random(&mut rng);
+    let mut note = random_note(&mut rng, 42);
+    note.memo = b"invoice 7".to_vec();
+    let encrypted = encrypt_note(&key.address(), &note, &mut rng).unwrap();
+
+    let viewed = view_note(&key.viewing_key(), &encrypted).unwrap();
+    assert_eq!(viewed.amount, 42);
+    assert_eq!(viewed.nullifier_hash, note.nullifier_hash());
+    assert_eq!(viewed.memo, b"invoice 7");
+}
+
+#[test]
+fn history_tracks_incoming_and_spent_notes() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let key = SpendingKey::random(&mut rng);
+    let other = SpendingKey::random(&mut rng);
+
+    let kept = random_note(&mut rng, 5_000);
+    let spent = random_note(&mut rng, 7_000);
+    let pending = random_note(&mut rng, 11_000);
+    let theirs = random_note(&mut rng, 13_000);
+    let mut leaves: Vec<[u8; 32]> = (0..5u8).map(|i| [i + 1; 32]).collect();
+    leaves.insert(2, kept.leaf());
+    leaves.push(spent.leaf());
+    leaves.push(theirs.leaf());
+
+    let sent: Vec<([u8; 32], EncryptedNote)> = [
+        (&kept, key.address()),
+        (&theirs, other.address()),
+        (&spent, key.address()),
+        (&pending, key.address()),
+    ]
+    .into_iter()
+    .map(|(note, to)| (note.leaf(), encrypt_note(&to, note, &mut rng).unwrap()))
+    .collect();
+    let shards = [
+        shard_with(&[[9u8; 32], spent.nullifier_hash()]),
+        shard_with(&[theirs.nullifier_hash()]),
+    ];
+
+    let history = History::build(&key.viewing_key(), sent, &leaves, &shards);
+    assert_eq!(history.entries.len(), 3);
+    assert_eq!(history.entries[0].leaf_index, Some(2));
+    assert_eq!(history.entries[1].leaf_index, Some(6));
+    assert_eq!(history.entries[2].leaf_index, None);
+
+    let incoming: Vec<u64> = history.incoming().map(|e| e.amount).collect();
+    assert_eq!(incoming, [5_000, 7_000]);
+    let outgoing: Vec<u64> = history.outgoing().map(|e| e.amount).collect();
+    assert_eq!(outgoing, [7_000]);
+    assert_eq!(history.balance(&[0u8; 32]), 5_000);
+    assert_eq!(history.balance(&[1u8; 32]), 0);
+}
@@ -0,0 +1,125 @@
+//! Notes round trip through the program's log format and open only for their recipient.
+use base64::{engine::general_purpose, Engine as _};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use solnado::inbox::{EncryptedNote, MAX_NOTE_CIPHERTEXT, NOTE_LOG_TAG};
+use solnado_sdk::inbox::{
+    decrypt_note, encrypt_note, parse_note_log, scan_notes, view_note, Note, NoteError,
+    MAX_MEMO_LEN,
+};
+use solnado_sdk::keys::SpendingKey;
+
+fn random_note(rng: &mut StdRng, memo: &[u8]) -> Note {
+    let mut nullifier = [0u8; 32];
+    rng.fill_bytes(&mut nullifier);
+    nullifier[0] = rng.gen_range(0..0x30);
+    Note {
+        amount: rng.gen(),
+        nullifier,
+        asset_id: [0u8; 32],
+        memo: memo.to_vec(),
+    }
+}
+
+/// The line `sol_log_data(&[NOTE_LOG_TAG, leaf, ephemeral_key, ciphertext])` shows up as
+fn log_line(leaf: &[u8; 32], note: &EncryptedNote) -> String {
+    let fields: Vec<String> = [
+        NOTE_LOG_TAG,
+        leaf,
+        &note.ephemeral_key,
+        note.ciphertext.as_slice(),
+    ]
+    .iter()
+    .map(|f| general_purpose::STANDARD.encode(f))
+    .collect();
+    format!("Program data: {}", fields.join(" "))
+}
+
+#[test]
+fn recipient_opens_its_note() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let key = SpendingKey::random(&mut rng);
+    let note = random_note(&mut rng, b"for the rent");
+    let encrypted = encrypt_note(&key.address(), &note, &mut rng).unwrap();
+
+    assert_eq!(
+        decrypt_note(&key, &note.leaf(), &encrypted),
+        Some(note.clone())
+    );
+    let stranger = SpendingKey::random(&mut rng);
+    assert_eq!(decrypt_note(&stranger, &note.leaf(), &encrypted), None);
+    assert_eq!(view_note(&stranger.viewing_key(), &encrypted), None);
+    // a note that doesn't open the leaf it's logged next to is useless
+    assert_eq!(decrypt_note(&key, &[1u8; 32], &encrypted), None);
+}
+
+#[test]
+fn tampered_note_is_rejected() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let key = SpendingKey::random(&mut rng);
+    let note = random_note(&mut rng, b"");
+    let mut encrypted = encrypt_note(&key.address(), &note, &mut rng).unwrap();
+    encrypted.ciphertext[3] ^= 1;
+    assert_eq!(decrypt_note(&key, &note.leaf(), &encrypted), None);
+}
+
+#[test]
+fn longest_memo_fits_the_program_limit() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let recipient = SpendingKey::random(&mut rng).address();
+    let note = random_note(&mut rng, &[7u8; MAX_MEMO_LEN]);
+    let encrypted = encrypt_note(&recipient, &note, &mut rng).unwrap();
+    assert_eq!(encrypted.ciphertext.len(), MAX_NOTE_CIPHERTEXT);
+
+    let note = random_note(&mut rng, &[7u8; MAX_MEMO_LEN + 1]);
+    assert_eq!(
+        encrypt_note(&recipient, &note, &mut rng),
+        Err(NoteError::MemoTooLong)
+    );
+}
+
+#[test]
+fn scan_finds_own_notes_in_the_log_stream() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let key = SpendingKey::random(&mut rng);
+    let other = SpendingKey::random(&mut rng).address();
+    let mine: Vec<Note> = (0..3).map(|_| random_note(&mut rng, b"hi")).collect();
+
+    let mut logs = vec![
+        "Program log: Instruction: DepositVariable".to_string(),
+        "Program data: bm90ZQ== garbage".to_string(),
+    ];
+    for note in &mine {
+        let theirs = random_note(&mut rng, b"");
+        let encrypted = encrypt_note(&other, &theirs, &mut rng).unwrap();
+        logs.push(log_line(&theirs.leaf(), &encrypted));
+        let encrypted = encrypt_note(&key.address(), note, &mut rng).unwrap();
+        logs.push(log_line(&note.leaf(), &encrypted));
+    }
+
+    let found = scan_notes(&key, logs.iter().map(String::as_str));
+    assert_eq!(found.len(), 3);
+    for (incoming, note) in found.iter().zip(&mine) {
+        assert_eq!(incoming.leaf, note.leaf());
+        assert_eq!(&incoming.note, note);
+    }
+}
+
+#[test]
+fn only_note_logs_parse() {
+    let note = EncryptedNote {
+        ephemeral_key: [2u8; 32],
+        ciphertext: vec![3u8; 90],
+    };
+    let line = log_line(&[1u8; 32], &note);
+    assert_eq!(parse_note_log(&line), Some(([1u8; 32], note.clone())));
+
+    let other_tag = line.replacen(
+        &general_purpose::STANDARD.encode(NOTE_LOG_TAG),
+        &general_purpose::STANDARD.encode(b"leaf"),
+        1,
+    );
+    assert_eq!(parse_note_log(&other_tag), None);
+    assert_eq!(parse_note_log(&format!("{} AA==", line)), None);
+    assert_eq!(parse_note_log("Program log: note"), None);
+}
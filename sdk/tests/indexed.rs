@@ -0,0 +1,129 @@
+//! The client tree and the program's frontier agree on every root, insertion after insertion.
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use solnado::indexed::{frontier_level, node_hash, NullifierTree, INDEXED_TREE_DEPTH};
+use solnado::inputs::{amount_to_field, IndexedInsertInputs, PublicInputs};
+use solnado_sdk::indexed::{IndexedTree, InsertError};
+
+/// Nullifiers under the BN254 modulus
+fn random_nullifiers(count: usize, seed: u64) -> Vec<[u8; 32]> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..count)
+        .map(|_| {
+            let mut n = [0u8; 32];
+            rng.fill_bytes(&mut n);
+            n[0] = rng.gen_range(0..0x30);
+            n
+        })
+        .collect()
+}
+
+fn root_from_path(leaf: [u8; 32], index: u64, path: &[[u8; 32]]) -> [u8; 32] {
+    path.iter()
+        .enumerate()
+        .fold(leaf, |node, (level, sibling)| {
+            if index >> level & 1 == 1 {
+                node_hash(sibling, &node)
+            } else {
+                node_hash(&node, sibling)
+            }
+        })
+}
+
+#[test]
+fn empty_trees_agree() {
+    let chain = NullifierTree::new();
+    assert_eq!(chain.next_index, 1);
+    assert_eq!(chain.root, IndexedTree::new().root());
+}
+
+#[test]
+fn frontier_insertion_matches_full_tree() {
+    let mut chain = NullifierTree::new();
+    let mut client = IndexedTree::new();
+    for n in random_nullifiers(150, 21) {
+        let insertion = client.insert(&n).unwrap();
+        let inputs = insertion.inputs;
+        assert_eq!(inputs.old_root, chain.root);
+        assert_eq!(inputs.next_index, chain.next_index);
+        assert_eq!(
+            root_from_path(
+                insertion.low_leaf.hash(),
+                inputs.low_index,
+                &insertion.low_path
+            ),
+            chain.root
+        );
+        assert_eq!(insertion.low_path.len(), INDEXED_TREE_DEPTH);
+
+        chain
+            .insert(inputs.low_index, inputs.low_subtree, inputs.new_leaf)
+            .unwrap();
+        assert_eq!(chain.root, client.root());
+    }
+
+    // walking the links from the head visits every value in order
+    let leaves = client.leaves();
+    let mut at = leaves[0];
+    let mut visited = 0;
+    while at.next_value != [0u8; 32] {
+        let next = leaves[at.next_index as usize];
+        assert_eq!(next.value, at.next_value);
+        assert!(at.value < next.value);
+        at = next;
+        visited += 1;
+    }
+    assert_eq!(visited, 150);
+}
+
+#[test]
+fn spent_nullifier_is_rejected() {
+    let nullifiers = random_nullifiers(20, 4);
+    let mut client = IndexedTree::from_spent(&nullifiers).unwrap();
+    let before = client.clone();
+    assert_eq!(
+        client.insert(&nullifiers[7]),
+        Err(InsertError::AlreadySpent)
+    );
+    assert_eq!(client, before);
+}
+
+#[test]
+fn low_subtree_sits_where_indices_diverge() {
+    assert_eq!(frontier_level(0, 1), 0);
+    assert_eq!(frontier_level(2, 3), 0);
+    assert_eq!(frontier_level(1, 2), 1);
+    assert_eq!(frontier_level(3, 4), 2);
+    assert_eq!(frontier_level(5, 8), 3);
+    assert_eq!(frontier_level(0, 1 << 29), 29);
+}
+
+#[test]
+fn stale_low_index_is_rejected() {
+    let mut chain = NullifierTree::new();
+    assert!(chain.insert(1, [0u8; 32], [0u8; 32]).is_err());
+    assert_eq!(chain.next_index, 1);
+}
+
+#[test]
+fn insert_inputs_keep_circuit_order() {
+    let inputs = IndexedInsertInputs {
+        nullifier: [1u8; 32],
+        old_root: [2u8; 32],
+        next_index: 3,
+        low_index: 4,
+        low_subtree: [5u8; 32],
+        new_leaf: [6u8; 32],
+    };
+    assert_eq!(
+        inputs.to_field_elements(),
+        [
+            [1u8; 32],
+            [2u8; 32],
+            amount_to_field(3),
+            amount_to_field(4),
+            [5u8; 32],
+            [6u8; 32],
+        ]
+    );
+}
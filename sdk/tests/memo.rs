@@ -0,0 +1,103 @@
+//! The memos a deposit needs around batch and small tree boundaries, read back the way
+//! an indexer does.
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::AccountDeserialize;
+use solana_poseidon::{hashv, Endianness, Parameters};
+use solnado::memo::{PoolMemo, SmallTreeMemo, SubBatchMemo};
+use solnado::state::MerkleMountainRange;
+use solnado::utils::{get_root, MEMO_PROGRAM_ID};
+use solnado::BATCHES_PER_SMALL_TREE;
+use solnado_sdk::memo::{memo_ix, pool_memos, required_memos};
+
+const LEAF: [u8; 32] = [5u8; 32];
+
+fn hash(node: &[u8; 32]) -> [u8; 32] {
+    hashv(Parameters::Bn254X5, Endianness::BigEndian, &[node, node])
+        .unwrap()
+        .to_bytes()
+}
+
+/// A pool whose every leaf is `LEAF`, `batches` closed and `leaves` in the open one
+fn pool(batches: u64, leaves: usize) -> MerkleMountainRange {
+    let data = vec![0u8; 8 + MerkleMountainRange::SIZE];
+    let mut pool = MerkleMountainRange::try_deserialize_unchecked(&mut &data[..]).unwrap();
+    for bit in (0..u64::BITS - batches.leading_zeros()).rev() {
+        if batches & (1 << bit) != 0 {
+            let mut root = get_root(&[LEAF; 16]);
+            for _ in 0..bit {
+                root = hash(&root);
+            }
+            let i = pool.number_of_peaks as usize;
+            pool.peaks[i] = root;
+            pool.depth[i] = bit as u8 + 4;
+            pool.number_of_peaks += 1;
+        }
+    }
+    pool.batch_number = batches;
+    pool.batch_leaves[..leaves].fill(LEAF);
+    pool
+}
+
+#[test]
+fn no_memo_inside_a_half_batch() {
+    assert!(required_memos(&pool(3, 2), &[LEAF, LEAF]).is_empty());
+}
+
+#[test]
+fn filling_a_half_batch_posts_its_leaves() {
+    let memos = required_memos(&pool(3, 7), &[[9u8; 32], LEAF]);
+    let mut leaves = [LEAF; 8];
+    leaves[7] = [9u8; 32];
+    assert_eq!(
+        memos,
+        [PoolMemo::SubBatch(SubBatchMemo {
+            batch_number: 3,
+            leaves,
+        })]
+    );
+}
+
+#[test]
+fn closing_a_small_tree_and_opening_the_next_needs_both_memos() {
+    let last = BATCHES_PER_SMALL_TREE - 1;
+    let memos = required_memos(&pool(last, 15), &[LEAF, LEAF]);
+
+    let mut small_tree_root = get_root(&[LEAF; 16]);
+    for _ in 0..BATCHES_PER_SMALL_TREE.trailing_zeros() {
+        small_tree_root = hash(&small_tree_root);
+    }
+    assert_eq!(
+        memos,
+        [
+            PoolMemo::SubBatch(SubBatchMemo {
+                batch_number: last,
+                leaves: [LEAF; 8],
+            }),
+            PoolMemo::SmallTree(SmallTreeMemo {
+                closed_batch: last,
+                root: small_tree_root,
+            }),
+        ]
+    );
+}
+
+#[test]
+fn indexer_reads_back_the_pool_memos() {
+    let memos = required_memos(&pool(BATCHES_PER_SMALL_TREE - 1, 15), &[LEAF, LEAF]);
+    let mut ixs: Vec<Instruction> = memos.iter().map(memo_ix).collect();
+    // someone else's memo and the deposit itself
+    ixs.insert(
+        1,
+        Instruction {
+            program_id: MEMO_PROGRAM_ID,
+            accounts: vec![],
+            data: b"thanks".to_vec(),
+        },
+    );
+    ixs.push(Instruction {
+        program_id: solnado::ID,
+        accounts: vec![],
+        data: memos[0].encode(),
+    });
+    assert_eq!(pool_memos(&ixs), memos);
+}
@@ -0,0 +1,139 @@
+//! Resolving against directories grown by random splits, as the program grows them.
+use anchor_lang::Discriminator;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use solnado::directory::{
+    directory_entries, directory_space, replace_with_children, ShardDirectory, ShardPrefix,
+    SHARD_PREFIX_SIZE,
+};
+use solnado::shard::{derive_shard_pda_key, leading_bits, MAX_PREFIX_BITS, PREFIX_BYTES};
+use solnado_sdk::shard::{resolve_shard, ResolveError};
+
+const POOL_ID: [u8; 16] = [9u8; 16];
+
+fn directory(entries: &[ShardPrefix]) -> Vec<u8> {
+    let mut data = ShardDirectory::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    data.extend_from_slice(bytemuck::cast_slice(entries));
+    data
+}
+
+/// The directory `initialize_shards` creates
+fn fresh_directory() -> Vec<u8> {
+    let mut high = [0u8; PREFIX_BYTES];
+    high[0] = 0x80;
+    directory(&[
+        ShardPrefix::new(1, [0u8; PREFIX_BYTES]),
+        ShardPrefix::new(1, high),
+    ])
+}
+
+/// Grows the data by one entry and splits `parent`, like `record_split`
+fn split(data: &mut Vec<u8>, parent: &ShardPrefix) {
+    data.resize(data.len() + SHARD_PREFIX_SIZE, 0);
+    replace_with_children(data, parent).unwrap();
+}
+
+fn random_nullifier(rng: &mut StdRng) -> [u8; 32] {
+    let mut n = [0u8; 32];
+    rng.fill_bytes(&mut n);
+    n
+}
+
+#[test]
+fn fresh_directory_resolves_depth_one_shards() {
+    let data = fresh_directory();
+    for (first, bit) in [(0x00, 0u8), (0x7f, 0), (0x80, 1), (0xff, 1)] {
+        let mut n = [0x55u8; 32];
+        n[0] = first;
+        let accounts = resolve_shard(POOL_ID, &data, &n).unwrap();
+        assert_eq!(accounts.prefix.len, 1);
+        assert_eq!(accounts.prefix.prefix[0], bit << 7);
+        assert_eq!(accounts.shard, accounts.prefix.pda(POOL_ID));
+        let [c0, c1] = accounts.prefix.children();
+        assert_eq!(accounts.child0, c0.pda(POOL_ID));
+        assert_eq!(accounts.child1, c1.pda(POOL_ID));
+    }
+}
+
+#[test]
+fn resolves_the_live_shard_after_thousands_of_splits() {
+    let mut rng = StdRng::seed_from_u64(35);
+    let mut data = fresh_directory();
+    for _ in 0..3_000 {
+        let n = random_nullifier(&mut rng);
+        let owner = resolve_shard(POOL_ID, &data, &n).unwrap().prefix;
+        if (owner.len as usize) < MAX_PREFIX_BITS {
+            split(&mut data, &owner);
+        }
+    }
+    let entries = directory_entries(&data).unwrap().to_vec();
+    assert_eq!(entries.len(), 3_002);
+    assert_eq!(data.len(), directory_space(entries.len()));
+    assert!(entries.windows(2).all(|w| w[0].prefix < w[1].prefix));
+
+    for _ in 0..3_000 {
+        let n = random_nullifier(&mut rng);
+        let accounts = resolve_shard(POOL_ID, &data, &n).unwrap();
+        // exactly one live shard covers any nullifier
+        let owners: Vec<_> = entries.iter().filter(|e| e.contains(&n)).collect();
+        assert_eq!(owners, [&accounts.prefix]);
+        assert_eq!(
+            leading_bits(&n, accounts.prefix.len),
+            accounts.prefix.prefix
+        );
+        assert_eq!(
+            accounts.shard,
+            derive_shard_pda_key(POOL_ID, &accounts.prefix.prefix, accounts.prefix.len).0
+        );
+    }
+}
+
+#[test]
+fn deepest_shard_has_no_children() {
+    let mut data = fresh_directory();
+    let n = [0u8; 32];
+    for _ in 1..MAX_PREFIX_BITS {
+        let owner = resolve_shard(POOL_ID, &data, &n).unwrap().prefix;
+        split(&mut data, &owner);
+    }
+    let accounts = resolve_shard(POOL_ID, &data, &n).unwrap();
+    assert_eq!(accounts.prefix.len as usize, MAX_PREFIX_BITS);
+    assert_eq!(
+        (accounts.child0, accounts.child1),
+        (accounts.shard, accounts.shard)
+    );
+    assert_eq!(directory_entries(&data).unwrap().len(), MAX_PREFIX_BITS + 1);
+}
+
+#[test]
+fn rejects_foreign_or_incomplete_directories() {
+    let n = [1u8; 32];
+    let mut data = fresh_directory();
+    assert_eq!(
+        resolve_shard(POOL_ID, &data[..data.len() - 1], &n),
+        Err(ResolveError::InvalidDirectory)
+    );
+    data[0] ^= 1;
+    assert_eq!(
+        resolve_shard(POOL_ID, &data, &n),
+        Err(ResolveError::InvalidDirectory)
+    );
+
+    // only the high half listed
+    let mut high = [0u8; PREFIX_BYTES];
+    high[0] = 0x80;
+    let partial = directory(&[ShardPrefix::new(1, high)]);
+    assert_eq!(
+        resolve_shard(POOL_ID, &partial, &n),
+        Err(ResolveError::NoShard)
+    );
+}
+
+#[test]
+fn splitting_an_unlisted_shard_fails() {
+    let mut data = fresh_directory();
+    let stale = ShardPrefix::new(2, [0u8; PREFIX_BYTES]);
+    data.resize(data.len() + SHARD_PREFIX_SIZE, 0);
+    assert!(replace_with_children(&mut data, &stale).is_err());
+}
@@ -0,0 +1,96 @@
+//! One-time addresses open for their recipient only and are found in the program's log.
+use anchor_lang::prelude::Pubkey;
+use base64::{engine::general_purpose, Engine as _};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use solnado::stealth::STEALTH_LOG_TAG;
+use solnado_sdk::keys::{SpendingKey, StealthMetaAddress};
+use solnado_sdk::stealth::{
+    derive_stealth_address, open_stealth_address, parse_stealth_log, scan_withdrawals,
+};
+
+/// The line `sol_log_data(&[STEALTH_LOG_TAG, withdrawer, ephemeral_key])` shows up as
+fn log_line(withdrawer: &Pubkey, ephemeral_key: &[u8; 32]) -> String {
+    let fields: Vec<String> = [STEALTH_LOG_TAG, withdrawer.as_ref(), ephemeral_key]
+        .iter()
+        .map(|f| general_purpose::STANDARD.encode(f))
+        .collect();
+    format!("Program data: {}", fields.join(" "))
+}
+
+#[test]
+fn meta_address_round_trips() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let meta = SpendingKey::random(&mut rng).stealth_meta_address();
+    assert_eq!(StealthMetaAddress::from_bytes(&meta.to_bytes()), Some(meta));
+}
+
+#[test]
+fn only_the_recipient_opens_the_address() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let recipient = SpendingKey::random(&mut rng);
+    let stranger = SpendingKey::random(&mut rng);
+    let stealth = derive_stealth_address(&recipient.stealth_meta_address(), &mut rng);
+    let keypair = open_stealth_address(&recipient, &stealth.address, &stealth.ephemeral_key);
+    assert_eq!(keypair.map(|k| k.pubkey()), Some(stealth.address));
+    assert!(open_stealth_address(&stranger, &stealth.address, &stealth.ephemeral_key).is_none());
+
+    // the ephemeral key of another withdrawal doesn't open it either
+    let other = derive_stealth_address(&recipient.stealth_meta_address(), &mut rng);
+    assert!(open_stealth_address(&recipient, &stealth.address, &other.ephemeral_key).is_none());
+}
+
+#[test]
+fn addresses_are_fresh_field_elements() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let meta = SpendingKey::random(&mut rng).stealth_meta_address();
+    let addresses: Vec<Pubkey> = (0..32)
+        .map(|_| derive_stealth_address(&meta, &mut rng).address)
+        .collect();
+    // the withdrawal proof takes the address as a public input
+    assert!(addresses.iter().all(|a| a.to_bytes()[0] < 0x30));
+    assert!(addresses[1..].iter().all(|a| *a != addresses[0]));
+}
+
+#[test]
+fn scan_finds_own_withdrawals_in_the_log_stream() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let key = SpendingKey::random(&mut rng);
+    let other = SpendingKey::random(&mut rng).stealth_meta_address();
+
+    let mut logs = vec![
+        "Program log: Instruction: WithdrawOnBehalfShard".to_string(),
+        log_line(&Pubkey::new_unique(), &[0u8; 32]),
+    ];
+    let mut mine = vec![];
+    for _ in 0..3 {
+        let theirs = derive_stealth_address(&other, &mut rng);
+        logs.push(log_line(&theirs.address, &theirs.ephemeral_key));
+        let stealth = derive_stealth_address(&key.stealth_meta_address(), &mut rng);
+        logs.push(log_line(&stealth.address, &stealth.ephemeral_key));
+        mine.push(stealth.address);
+    }
+
+    let found: Vec<Pubkey> = scan_withdrawals(&key, logs.iter().map(String::as_str))
+        .iter()
+        .map(|k| k.pubkey())
+        .collect();
+    assert_eq!(found, mine);
+}
+
+#[test]
+fn only_stealth_logs_parse() {
+    let withdrawer = Pubkey::new_unique();
+    let line = log_line(&withdrawer, &[2u8; 32]);
+    assert_eq!(parse_stealth_log(&line), Some((withdrawer, [2u8; 32])));
+    assert_eq!(parse_stealth_log(&format!("{} AA==", line)), None);
+    assert_eq!(
+        parse_stealth_log(&line.replacen(
+            &general_purpose::STANDARD.encode(STEALTH_LOG_TAG),
+            &general_purpose::STANDARD.encode(b"note"),
+            1,
+        )),
+        None
+    );
+    assert_eq!(parse_stealth_log("Program log: stealth"), None);
+}